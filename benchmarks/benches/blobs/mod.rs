@@ -22,7 +22,10 @@ pub fn inserts(c: &mut Criterion) {
     let mut group = c.benchmark_group("blobs-insert");
 
     for blob_size in [1024, 1024 * 1024, 1024 * 1024 * 64] {
-        let config = InsertConfig { blob_size };
+        let config = InsertConfig {
+            blob_size,
+            write_buffer_size: None,
+        };
 
         nebari::InsertBlobs::<VersionedBenchmark>::run(&mut group, &config);
         nebari::InsertBlobs::<UnversionedBenchmark>::run(&mut group, &config);
@@ -34,12 +37,24 @@ pub fn inserts(c: &mut Criterion) {
         persy::InsertBlobs::run(&mut group, &config);
         // #[cfg(feature = "couchdb")]
         // couchdb::InsertLogs::run(&mut group, &config);
+
+        // Compares Nebari's default write buffer against one large enough to
+        // write most blobs in a single write.
+        let config = InsertConfig {
+            blob_size,
+            write_buffer_size: Some(1024 * 1024),
+        };
+        nebari::InsertBlobs::<VersionedBenchmark>::run(&mut group, &config);
+        nebari::InsertBlobs::<UnversionedBenchmark>::run(&mut group, &config);
     }
 }
 
 #[derive(Clone)]
 pub struct InsertConfig {
     pub blob_size: usize,
+    /// The write buffer size used by Nebari, if not the default. Other
+    /// backends are only benchmarked with the default.
+    pub write_buffer_size: Option<usize>,
 }
 
 #[derive(Clone)]
@@ -90,6 +105,10 @@ impl BenchConfig for InsertConfig {
 
 impl Display for InsertConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.blob_size.bytes())
+        write!(f, "{}", self.blob_size.bytes())?;
+        if let Some(write_buffer_size) = self.write_buffer_size {
+            write!(f, " ({} write buffer)", write_buffer_size.bytes())?;
+        }
+        Ok(())
     }
 }
@@ -37,10 +37,14 @@ impl<B: NebariBenchmark> SimpleBench for InsertBlobs<B> {
         config_group_state: &<Self::Config as BenchConfig>::GroupState,
     ) -> Result<Self, anyhow::Error> {
         let tempfile = TempDir::new()?;
+        let mut context = Context::default();
+        if let Some(write_buffer_size) = config.write_buffer_size {
+            context = context.with_write_buffer_size(write_buffer_size);
+        }
         let tree = TreeFile::<B::Root, StdFile>::write(
             tempfile.path().join("tree"),
             State::default(),
-            &Context::default(),
+            &context,
             None,
         )?;
 
@@ -1,4 +1,10 @@
-use std::{any::Any, sync::Arc};
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use lru::LruCache;
 use parking_lot::Mutex;
@@ -19,6 +25,46 @@ use crate::ArcBytes;
 pub struct ChunkCache {
     max_block_length: usize,
     cache: Arc<Mutex<LruCache<ChunkKey, CacheEntry>>>,
+    statistics: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    insertions: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// The number of times a [`ChunkCache`] was used, as returned by
+/// [`ChunkCache::statistics()`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStatistics {
+    /// The number of lookups that found a cached chunk.
+    pub hits: u64,
+    /// The number of lookups that didn't find a cached chunk.
+    pub misses: u64,
+    /// The number of chunks added to the cache. Chunks longer than the
+    /// cache's maximum chunk length aren't added.
+    pub insertions: u64,
+    /// The number of chunks removed from the cache to make room for other
+    /// chunks.
+    pub evictions: u64,
+}
+
+impl CacheStatistics {
+    /// Returns the fraction of lookups that found a cached chunk, or 0 if no
+    /// lookups have been performed.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups > 0 {
+            self.hits as f64 / lookups as f64
+        } else {
+            0.
+        }
+    }
 }
 
 pub trait AnySendSync: Any + Send + Sync {
@@ -55,6 +101,7 @@ impl ChunkCache {
         Self {
             max_block_length: max_chunk_length,
             cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+            statistics: Arc::default(),
         }
     }
 
@@ -62,7 +109,12 @@ impl ChunkCache {
     pub fn insert(&self, file_id: u64, position: u64, buffer: ArcBytes<'static>) {
         if buffer.len() <= self.max_block_length {
             let mut cache = self.cache.lock();
-            cache.put(ChunkKey { position, file_id }, CacheEntry::ArcBytes(buffer));
+            self.put(
+                &mut cache,
+                ChunkKey { position, file_id },
+                CacheEntry::ArcBytes(buffer),
+            );
+            self.statistics.insertions.fetch_add(1, Ordering::Relaxed);
         }
     }
 
@@ -74,17 +126,60 @@ impl ChunkCache {
         value: T,
     ) {
         let mut cache = self.cache.lock();
-        cache.put(
+        self.put(
+            &mut cache,
             ChunkKey { position, file_id },
             CacheEntry::Decoded(Arc::new(value)),
         );
     }
 
+    fn put(&self, cache: &mut LruCache<ChunkKey, CacheEntry>, key: ChunkKey, entry: CacheEntry) {
+        if cache.len() == cache.cap() && !cache.contains(&key) {
+            self.statistics.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        cache.put(key, entry);
+    }
+
     /// Looks up a previously read chunk for `file_path` at `position`,
     #[must_use]
     pub fn get(&self, file_id: u64, position: u64) -> Option<CacheEntry> {
         let mut cache = self.cache.lock();
-        cache.get(&ChunkKey { position, file_id }).cloned()
+        let entry = cache.get(&ChunkKey { position, file_id }).cloned();
+        let counter = if entry.is_some() {
+            &self.statistics.hits
+        } else {
+            &self.statistics.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    /// Returns the number of hits, misses, insertions, and evictions since
+    /// this cache was created or [`reset_statistics()`](Self::reset_statistics)
+    /// was last called. Clones of this cache share their statistics.
+    #[must_use]
+    pub fn statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            hits: self.statistics.hits.load(Ordering::Relaxed),
+            misses: self.statistics.misses.load(Ordering::Relaxed),
+            insertions: self.statistics.insertions.load(Ordering::Relaxed),
+            evictions: self.statistics.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets all of the statistics returned from
+    /// [`statistics()`](Self::statistics) to 0.
+    pub fn reset_statistics(&self) {
+        self.statistics.hits.store(0, Ordering::Relaxed);
+        self.statistics.misses.store(0, Ordering::Relaxed);
+        self.statistics.insertions.store(0, Ordering::Relaxed);
+        self.statistics.evictions.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the number of chunks currently cached.
+    #[cfg(test)]
+    pub fn cached_chunks(&self) -> usize {
+        self.cache.lock().len()
     }
 }
 
@@ -93,3 +188,41 @@ pub enum CacheEntry {
     ArcBytes(ArcBytes<'static>),
     Decoded(Arc<dyn AnySendSync>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics() {
+        let cache = ChunkCache::new(2, 4);
+        assert!(cache.get(0, 0).is_none());
+        cache.insert(0, 0, ArcBytes::from(b"a"));
+        cache.insert(0, 1, ArcBytes::from(b"b"));
+        // Chunks that are too long aren't cached.
+        cache.insert(0, 2, ArcBytes::from(b"too long"));
+        // Replacing a cached chunk doesn't evict another chunk.
+        cache.insert(0, 1, ArcBytes::from(b"c"));
+        assert!(cache.get(0, 0).is_some());
+        cache.insert(1, 0, ArcBytes::from(b"d"));
+        assert!(cache.get(0, 1).is_none());
+
+        let statistics = cache.statistics();
+        assert_eq!(
+            statistics,
+            CacheStatistics {
+                hits: 1,
+                misses: 2,
+                insertions: 4,
+                evictions: 1,
+            }
+        );
+        assert!((statistics.hit_ratio() - 1. / 3.).abs() < f64::EPSILON);
+        // Clones share their statistics.
+        assert_eq!(cache.clone().statistics(), statistics);
+
+        cache.reset_statistics();
+        assert_eq!(cache.statistics(), CacheStatistics::default());
+        assert!(cache.statistics().hit_ratio().abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,24 @@
+use std::{fmt::Debug, time::SystemTime};
+
+/// A source of the current time.
+///
+/// A clock is configured using [`Config::clock`](crate::Config::clock) and
+/// returned by [`Roots::clock()`](crate::Roots::clock). Nebari doesn't read the
+/// time itself; the clock allows code built on a database to share one source
+/// of time, which tests can replace to control the passage of time
+/// deterministically.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// A [`Clock`] that returns the system's current time. This is the default
+/// clock.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -1,6 +1,12 @@
 use std::sync::Arc;
 
-use crate::{io::FileManager, vault::AnyVault, ChunkCache, Vault};
+use crate::{
+    io::FileManager,
+    recovery::{RecoveryHandler, RecoverySituation},
+    tree,
+    vault::AnyVault,
+    ChunkCache, RecoveryAction, Vault,
+};
 
 /// A shared environment for database operations.
 #[derive(Default, Debug, Clone)]
@@ -12,6 +18,17 @@ pub struct Context<M: FileManager> {
     pub(crate) vault: Option<Arc<dyn AnyVault>>,
     /// The optional chunk cache to use.
     pub(crate) cache: Option<ChunkCache>,
+    /// The optional handler deciding how to recover trees that weren't
+    /// written completely.
+    pub(crate) recovery_handler: Option<RecoveryHandler>,
+    /// The number of bytes in each page of tree files. Uses
+    /// [`PAGE_SIZE`](crate::tree::PAGE_SIZE) if not set.
+    pub(crate) page_size: Option<usize>,
+    /// The number of bytes buffered while writing to tree files. Uses
+    /// [`WRITE_BUFFER_SIZE`](crate::tree::WRITE_BUFFER_SIZE) if not set.
+    pub(crate) write_buffer_size: Option<usize>,
+    /// Whether keys within leaf nodes are written using prefix compression.
+    pub(crate) key_compression: bool,
 }
 
 impl<M: FileManager> Context<M> {
@@ -41,4 +58,73 @@ impl<M: FileManager> Context<M> {
         self.vault = Some(vault);
         self
     }
+
+    /// Replaces the recovery handler currently set with `handler`. The handler
+    /// is invoked each time loading a tree requires a recovery decision, and
+    /// the returned [`RecoveryAction`] is taken. Without a handler, each
+    /// situation's [default action](RecoverySituation::default_action) is
+    /// taken.
+    pub fn with_recovery_handler<Handler>(mut self, handler: Handler) -> Self
+    where
+        Handler: for<'a> Fn(RecoverySituation<'a>) -> RecoveryAction + Send + Sync + 'static,
+    {
+        self.recovery_handler = Some(RecoveryHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Replaces the page size currently set with `page_size`. Tree files must
+    /// be opened using the page size they were created with. Defaults to
+    /// [`PAGE_SIZE`](crate::tree::PAGE_SIZE).
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `page_size` isn't a power of two between 16 and 2^31 bytes.
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        tree::assert_valid_page_size(page_size);
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Returns the number of bytes in each page of tree files.
+    pub fn page_size(&self) -> usize {
+        self.page_size.unwrap_or(tree::PAGE_SIZE)
+    }
+
+    /// Replaces the write buffer size currently set with `write_buffer_size`.
+    /// Writes to tree files are buffered in memory until this many bytes are
+    /// pending. Defaults to
+    /// [`WRITE_BUFFER_SIZE`](crate::tree::WRITE_BUFFER_SIZE).
+    pub fn with_write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = Some(write_buffer_size);
+        self
+    }
+
+    /// Returns the number of bytes buffered while writing to tree files.
+    pub fn write_buffer_size(&self) -> usize {
+        self.write_buffer_size.unwrap_or(tree::WRITE_BUFFER_SIZE)
+    }
+
+    /// Sets whether keys within leaf nodes are written using prefix
+    /// compression. Each key only stores the bytes that differ from the key
+    /// before it, which reduces the size of nodes whose keys share common
+    /// prefixes. Nodes written either way can always be read. Defaults to
+    /// false.
+    pub fn with_key_compression(mut self, key_compression: bool) -> Self {
+        self.key_compression = key_compression;
+        self
+    }
+
+    /// Returns true if keys within leaf nodes are written using prefix
+    /// compression.
+    pub fn key_compression(&self) -> bool {
+        self.key_compression
+    }
+
+    /// Returns the action to take in `situation`.
+    pub(crate) fn recovery_action(&self, situation: RecoverySituation<'_>) -> RecoveryAction {
+        self.recovery_handler.as_ref().map_or_else(
+            || situation.default_action(),
+            |handler| (handler.0)(situation),
+        )
+    }
 }
@@ -2,13 +2,14 @@ use std::{
     array::TryFromSliceError,
     convert::Infallible,
     fmt::{Debug, Display},
+    path::PathBuf,
 };
 
 use backtrace::Backtrace;
 use parking_lot::{Mutex, MutexGuard};
 use thiserror::Error;
 
-use crate::AbortError;
+use crate::{AbortError, ArcBytes};
 
 /// An error from Nebari as well as an associated backtrace.
 pub struct Error {
@@ -26,6 +27,49 @@ impl Error {
         }
     }
 
+    /// Wraps this error's kind in [`ErrorKind::Context`], annotating it with
+    /// `message`. The original kind remains accessible through
+    /// [`ErrorKind::root_kind()`], and the backtrace is preserved.
+    #[must_use]
+    pub fn with_context(self, message: impl Display) -> Self {
+        Self {
+            kind: ErrorKind::Context {
+                message: message.to_string(),
+                source: Box::new(self.kind),
+            },
+            backtrace: self.backtrace,
+        }
+    }
+
+    /// Returns true if this error is an [`ErrorKind::Io`] error whose kind is
+    /// [`NotFound`](std::io::ErrorKind::NotFound). See
+    /// [`ErrorKind::is_file_not_found()`].
+    #[must_use]
+    pub fn is_file_not_found(&self) -> bool {
+        self.kind.is_file_not_found()
+    }
+
+    /// Returns true if this error is an [`ErrorKind::Io`] error. See
+    /// [`ErrorKind::is_io()`].
+    #[must_use]
+    pub fn is_io(&self) -> bool {
+        self.kind.is_io()
+    }
+
+    /// Returns true if this error is an [`ErrorKind::DataIntegrity`] error. See
+    /// [`ErrorKind::is_data_integrity()`].
+    #[must_use]
+    pub fn is_data_integrity(&self) -> bool {
+        self.kind.is_data_integrity()
+    }
+
+    /// Returns true if this error is [`ErrorKind::TreeCompacted`]. See
+    /// [`ErrorKind::is_tree_compacted()`].
+    #[must_use]
+    pub fn is_tree_compacted(&self) -> bool {
+        self.kind.is_tree_compacted()
+    }
+
     /// Returns the backtrace of where this error was created.
     pub fn backtrace(&self) -> MutexGuard<'_, Backtrace> {
         let mut backtrace = self.backtrace.lock();
@@ -191,7 +235,7 @@ pub enum ErrorKind {
     Message(String),
     /// An error occurred while performing IO.
     #[error("io error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
     /// An unrecoverable data integrity error was encountered.
     #[error("an unrecoverable error with the data on disk has been found: {0}")]
     DataIntegrity(Box<Error>),
@@ -213,6 +257,15 @@ pub enum ErrorKind {
     /// A value was too large.
     #[error("value too large")]
     ValueTooLarge,
+    /// A value was small enough to be stored, but the output of the vault
+    /// exceeded the 4 gigabyte (2^32) limit of a stored chunk.
+    #[error("value of {value_length} bytes grew to {vault_length} bytes in the vault, which is too large to store")]
+    VaultOverheadTooLarge {
+        /// The length of the value before it was processed by the vault.
+        value_length: usize,
+        /// The length of the value after it was processed by the vault.
+        vault_length: usize,
+    },
     /// A multi-key operation did not have its keys ordered.
     #[error("multi-key operation did not have its keys ordered")]
     KeysNotOrdered,
@@ -230,6 +283,45 @@ pub enum ErrorKind {
     /// An transaction was pushed to the log out of order.
     #[error("transaction pushed out of order")]
     TransactionPushedOutOfOrder,
+    /// The database was opened with a different vault than it was created
+    /// with, or was opened without a vault when it was created with one. Also
+    /// returned when a [`Tree`](crate::Tree) is used after its vault was
+    /// replaced by [`Tree::rotate_vault()`](crate::Tree::rotate_vault).
+    #[error("the database was created with a different vault")]
+    VaultMismatch,
+    /// There is not enough space available to complete a write.
+    #[error("out of space")]
+    OutOfSpace,
+    /// An operation was cancelled using a
+    /// [`CancellationToken`](crate::tree::CancellationToken).
+    #[error("operation cancelled")]
+    Cancelled,
+    /// A tree opened using
+    /// [`Roots::tree_read_only()`](crate::Roots::tree_read_only), or a
+    /// database opened using [`Config::read_only()`](crate::Config::read_only),
+    /// was asked to perform a modification.
+    #[error("the tree was opened read-only")]
+    ReadOnly,
+    /// Loading a tree was stopped because a recovery handler returned
+    /// [`RecoveryAction::Abort`](crate::RecoveryAction::Abort).
+    #[error("recovery of tree {tree_path:?} was aborted")]
+    RecoveryAborted {
+        /// The path of the tree file that was being loaded.
+        tree_path: PathBuf,
+    },
+    /// A [`ModificationBuilder`](crate::tree::ModificationBuilder) or
+    /// [`Batch`](crate::tree::Batch) was given the same key more than once.
+    #[error("key {0:?} was provided more than once")]
+    DuplicateKey(ArcBytes<'static>),
+    /// An error annotated with additional information about the operation
+    /// that caused it.
+    #[error("{message}: {source}")]
+    Context {
+        /// A description of the operation being performed.
+        message: String,
+        /// The error that occurred.
+        source: Box<Self>,
+    },
 }
 
 pub trait SendSyncError: std::error::Error + Send + Sync + 'static {}
@@ -245,11 +337,97 @@ impl ErrorKind {
         Self::DataIntegrity(Box::new(error.into()))
     }
 
-    pub(crate) fn is_file_not_found(&self) -> bool {
-        matches!(self, Self::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+    /// Returns a copy of this error kind, allowing one error to be reported
+    /// to multiple callers. Kinds containing values that can't be copied,
+    /// such as vault errors, are copied as [`ErrorKind::Message`] containing
+    /// their description.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Self::Message(message) => Self::Message(message.clone()),
+            Self::Io(err) => Self::Io(std::io::Error::new(err.kind(), err.to_string())),
+            Self::OutOfSpace => Self::OutOfSpace,
+            Self::ReadOnly => Self::ReadOnly,
+            Self::TreeCompacted => Self::TreeCompacted,
+            Self::VaultMismatch => Self::VaultMismatch,
+            Self::TransactionPushedOutOfOrder => Self::TransactionPushedOutOfOrder,
+            Self::Context { message, source } => Self::Context {
+                message: message.clone(),
+                source: Box::new(source.duplicate()),
+            },
+            other => Self::Message(other.to_string()),
+        }
+    }
+
+    /// Returns the underlying error kind, looking through any
+    /// [`ErrorKind::Context`] annotations.
+    #[must_use]
+    pub fn root_kind(&self) -> &Self {
+        let mut kind = self;
+        while let Self::Context { source, .. } = kind {
+            kind = source;
+        }
+        kind
+    }
+
+    /// Returns true if this error is an [`ErrorKind::Io`] error whose kind is
+    /// [`NotFound`](std::io::ErrorKind::NotFound).
+    #[must_use]
+    pub fn is_file_not_found(&self) -> bool {
+        matches!(self.root_kind(), Self::Io(err) if err.kind() == std::io::ErrorKind::NotFound)
+    }
+
+    /// Returns true if this error is an [`ErrorKind::Io`] error.
+    #[must_use]
+    pub fn is_io(&self) -> bool {
+        matches!(self.root_kind(), Self::Io(_))
+    }
+
+    /// Returns true if this error is an [`ErrorKind::DataIntegrity`] error,
+    /// indicating that data read from a file was corrupt.
+    #[must_use]
+    pub fn is_data_integrity(&self) -> bool {
+        matches!(self.root_kind(), Self::DataIntegrity(_))
+    }
+
+    /// Returns true if this error is [`ErrorKind::TreeCompacted`]. The
+    /// operation can be retried after reopening the tree's file.
+    #[must_use]
+    pub fn is_tree_compacted(&self) -> bool {
+        matches!(self.root_kind(), Self::TreeCompacted)
     }
 }
 
+impl From<std::io::Error> for ErrorKind {
+    fn from(err: std::io::Error) -> Self {
+        if is_out_of_space(&err)
+            || matches!(
+                err.get_ref().and_then(|inner| inner.downcast_ref::<Self>()),
+                Some(Self::OutOfSpace)
+            )
+        {
+            Self::OutOfSpace
+        } else {
+            Self::Io(err)
+        }
+    }
+}
+
+/// Returns true if `err` was caused by the operating system running out of
+/// space on the storage device.
+fn is_out_of_space(err: &std::io::Error) -> bool {
+    // ENOSPC
+    #[cfg(unix)]
+    const OUT_OF_SPACE_CODES: &[i32] = &[28];
+    // ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL
+    #[cfg(windows)]
+    const OUT_OF_SPACE_CODES: &[i32] = &[39, 112];
+    #[cfg(not(any(unix, windows)))]
+    const OUT_OF_SPACE_CODES: &[i32] = &[];
+
+    err.raw_os_error()
+        .map_or(false, |code| OUT_OF_SPACE_CODES.contains(&code))
+}
+
 impl From<&'static str> for ErrorKind {
     fn from(message: &'static str) -> Self {
         Self::message(message)
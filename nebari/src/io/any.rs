@@ -152,6 +152,17 @@ impl FileManager for AnyFileManager {
         }
     }
 
+    fn rename(
+        &self,
+        from: impl AsRef<std::path::Path>,
+        to: impl AsRef<std::path::Path>,
+    ) -> Result<(), crate::Error> {
+        match self {
+            Self::Std(manager) => manager.rename(from, to),
+            Self::Memory(manager) => manager.rename(from, to),
+        }
+    }
+
     fn delete_directory(&self, path: impl AsRef<std::path::Path>) -> Result<(), crate::Error> {
         match self {
             Self::Std(manager) => manager.delete_directory(path),
@@ -159,6 +170,20 @@ impl FileManager for AnyFileManager {
         }
     }
 
+    fn truncate(&self, path: impl AsRef<Path>, length: u64) -> Result<(), crate::Error> {
+        match self {
+            Self::Std(manager) => manager.truncate(path, length),
+            Self::Memory(manager) => manager.truncate(path, length),
+        }
+    }
+
+    fn file_names(&self, directory: impl AsRef<Path>) -> Result<Vec<String>, crate::Error> {
+        match self {
+            Self::Std(manager) => manager.file_names(directory),
+            Self::Memory(manager) => manager.file_names(directory),
+        }
+    }
+
     fn exists(&self, path: impl AsRef<std::path::Path>) -> Result<bool, crate::Error> {
         match self {
             Self::Std(manager) => manager.exists(path),
@@ -172,6 +197,13 @@ impl FileManager for AnyFileManager {
             Self::Memory(manager) => manager.file_length(path),
         }
     }
+
+    fn available_space(&self, path: impl AsRef<Path>) -> Result<Option<u64>, crate::Error> {
+        match self {
+            Self::Std(manager) => manager.available_space(path),
+            Self::Memory(manager) => manager.available_space(path),
+        }
+    }
 }
 
 impl ManagedFileOpener<AnyFile> for AnyFileManager {
@@ -220,6 +220,28 @@ impl FileManager for StdFileManager {
         }
     }
 
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        if from == to {
+            return Ok(());
+        }
+        std::fs::rename(from, to)?;
+
+        // Cached handles report the path they were opened with, so they can't
+        // be reused.
+        let mut open_files = self.open_files.lock();
+        let mut reader_files = self.reader_files.lock();
+        if let Some(file_id) = self.file_ids.remove_file_id_for_path(to) {
+            open_files.remove(&file_id);
+            reader_files.remove(&file_id);
+        }
+        if let Some(file_id) = self.file_ids.rename_path(from, to) {
+            open_files.remove(&file_id);
+            reader_files.remove(&file_id);
+        }
+        Ok(())
+    }
+
     fn delete_directory(&self, path: impl AsRef<Path>) -> Result<(), Error> {
         let path = path.as_ref();
         let removed_ids = self.file_ids.remove_file_ids_for_path_prefix(path);
@@ -237,6 +259,26 @@ impl FileManager for StdFileManager {
         Ok(())
     }
 
+    fn truncate(&self, path: impl AsRef<Path>, length: u64) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(file_id) = self.file_ids.file_id_for_path(path, false) {
+            let mut open_files = self.open_files.lock();
+            let mut reader_files = self.reader_files.lock();
+            open_files.remove(&file_id);
+            reader_files.remove(&file_id);
+        }
+        truncate_file(path, length)
+    }
+
+    fn file_names(&self, directory: impl AsRef<Path>) -> Result<Vec<String>, Error> {
+        file_names(directory.as_ref())
+    }
+
+    #[cfg(feature = "available-space")]
+    fn available_space(&self, path: impl AsRef<Path>) -> Result<Option<u64>, Error> {
+        Ok(Some(fs2::available_space(path)?))
+    }
+
     fn close_handles<F: FnOnce(u64)>(&self, path: impl AsRef<Path>, publish_callback: F) {
         if let Some(result) = self.file_ids.recreate_file_id_for_path(path.as_ref()) {
             let mut open_files = self.open_files.lock();
@@ -259,6 +301,26 @@ impl FileManager for StdFileManager {
     }
 }
 
+/// Shortens the file at `path` to `length` bytes and synchronizes it.
+pub(crate) fn truncate_file(path: &Path, length: u64) -> Result<(), Error> {
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(length)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Returns the names of the files within `directory`.
+pub(crate) fn file_names(directory: &Path) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
 impl ManagedFileOpener<StdFile> for StdFileManager {
     fn open_for_read(
         &self,
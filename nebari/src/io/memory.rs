@@ -7,7 +7,7 @@ use std::{
 };
 
 use once_cell::sync::Lazy;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Mutex, MutexGuard, RwLock};
 
 use super::{FileManager, FileOp, ManagedFile, OpenableFile};
 use crate::{
@@ -26,6 +26,7 @@ pub struct MemoryFile {
     path: PathBuf,
     buffer: FileBuffer,
     position: usize,
+    manager: Option<MemoryFileManager>,
 }
 
 impl std::fmt::Debug for MemoryFile {
@@ -36,7 +37,7 @@ impl std::fmt::Debug for MemoryFile {
             .field("path", &self.path)
             .field("buffer", &buffer.len())
             .field("position", &self.position)
-            .finish()
+            .finish_non_exhaustive()
     }
 }
 
@@ -103,6 +104,7 @@ impl ManagedFileOpener<MemoryFile> for MemoryFileOpener {
             path: path.to_path_buf(),
             buffer: lookup_buffer(path, true).unwrap(),
             position: 0,
+            manager: None,
         })
     }
 
@@ -122,6 +124,7 @@ impl ManagedFileOpener<MemoryFile> for MemoryFileOpener {
             buffer,
             position,
             path: path.to_path_buf(),
+            manager: None,
         })
     }
 }
@@ -171,6 +174,10 @@ impl std::io::Read for MemoryFile {
 
 impl std::io::Write for MemoryFile {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _usage = match &self.manager {
+            Some(manager) => manager.reserve(&self.buffer, self.id, buf.len())?,
+            None => None,
+        };
         let mut file_buffer = self.buffer.write();
 
         file_buffer.extend_from_slice(buf);
@@ -190,9 +197,144 @@ impl std::io::Write for MemoryFile {
 pub struct MemoryFileManager {
     file_ids: PathIds,
     open_files: Arc<Mutex<HashMap<u64, FileBuffer>>>,
+    capacity: Option<Arc<Capacity>>,
+}
+
+/// The behavior of a [`MemoryFileManager`] when a write would exceed its
+/// capacity.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CapacityPolicy {
+    /// The write fails with [`ErrorKind::OutOfSpace`].
+    Error,
+    /// Entire files are removed, starting with the least recently used file,
+    /// until the write fits. The write fails with [`ErrorKind::OutOfSpace`] if
+    /// it does not fit after all other files are removed.
+    ///
+    /// Any file can be removed, including a database's transaction log. This
+    /// policy is only suitable when the stored data can be regenerated, such
+    /// as when the files are used as a cache.
+    EvictLeastRecentlyUsed,
+}
+
+#[derive(Debug)]
+struct Capacity {
+    maximum_bytes: u64,
+    policy: CapacityPolicy,
+    // Held while writing to ensure usage does not change between checking the
+    // capacity and writing.
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    access_counter: u64,
+    last_accessed: HashMap<u64, u64>,
+}
+
+impl Usage {
+    fn accessed(&mut self, file_id: u64) {
+        self.access_counter += 1;
+        self.last_accessed.insert(file_id, self.access_counter);
+    }
 }
 
 impl MemoryFileManager {
+    /// Returns a new file manager that stores at most `maximum_bytes` across
+    /// all of its files. When a write would exceed the capacity, `policy`
+    /// determines what happens.
+    #[must_use]
+    pub fn with_capacity(maximum_bytes: u64, policy: CapacityPolicy) -> Self {
+        Self {
+            capacity: Some(Arc::new(Capacity {
+                maximum_bytes,
+                policy,
+                usage: Mutex::default(),
+            })),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the total number of bytes stored in the files of this manager.
+    #[must_use]
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes_excluding(None)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn used_bytes_excluding(&self, excluded: Option<&FileBuffer>) -> u64 {
+        let open_files = self.open_files.lock();
+        open_files
+            .values()
+            .filter(|buffer| excluded.map_or(true, |excluded| !Arc::ptr_eq(buffer, excluded)))
+            .map(|buffer| buffer.read().len() as u64)
+            .sum()
+    }
+
+    /// Waits until `additional` bytes can be written to `buffer`, evicting
+    /// other files if the policy allows. The returned guard must be held while
+    /// writing.
+    #[allow(clippy::cast_possible_truncation)]
+    fn reserve(
+        &self,
+        buffer: &FileBuffer,
+        file_id: Option<u64>,
+        additional: usize,
+    ) -> io::Result<Option<MutexGuard<'_, Usage>>> {
+        let capacity = match &self.capacity {
+            Some(capacity) => capacity,
+            None => return Ok(None),
+        };
+        let mut usage = capacity.usage.lock();
+        if let Some(file_id) = file_id {
+            usage.accessed(file_id);
+        }
+        loop {
+            let used = self.used_bytes_excluding(Some(buffer)) + buffer.read().len() as u64;
+            if used + additional as u64 <= capacity.maximum_bytes {
+                return Ok(Some(usage));
+            }
+
+            if capacity.policy == CapacityPolicy::EvictLeastRecentlyUsed {
+                if let Some((file_id, path)) = self.least_recently_used(&usage, buffer) {
+                    self.delete(&path)
+                        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+                    usage.last_accessed.remove(&file_id);
+                    continue;
+                }
+            }
+
+            return Err(io::Error::new(io::ErrorKind::Other, ErrorKind::OutOfSpace));
+        }
+    }
+
+    /// Returns the id and path of the least recently used file, excluding
+    /// `buffer`.
+    fn least_recently_used(&self, usage: &Usage, buffer: &FileBuffer) -> Option<(u64, PathBuf)> {
+        let candidate = {
+            let open_files = self.open_files.lock();
+            open_files
+                .iter()
+                .filter(|(_, open_buffer)| !Arc::ptr_eq(open_buffer, buffer))
+                .map(|(id, _)| *id)
+                .min_by_key(|id| usage.last_accessed.get(id).copied().unwrap_or_default())?
+        };
+        let file_ids = self.file_ids.file_ids.read();
+        file_ids
+            .iter()
+            .find(|(_, id)| **id == candidate)
+            .map(|(path, id)| (*id, path.clone()))
+    }
+
+    fn file_accessed(&self, file_id: Option<u64>) {
+        if let (Some(capacity), Some(file_id)) = (&self.capacity, file_id) {
+            capacity.usage.lock().accessed(file_id);
+        }
+    }
+
+    fn manager_for_file(&self) -> Option<Self> {
+        self.capacity.as_ref().map(|_| self.clone())
+    }
+
     fn lookup_file(
         &self,
         path: impl AsRef<Path>,
@@ -214,9 +356,11 @@ impl MemoryFileManager {
                 buffer: open_file.clone(),
                 path: path.to_path_buf(),
                 position: 0,
+                manager: self.manager_for_file(),
             }))
         } else if create_if_needed {
-            let file = MemoryFileOpener.open_for_append(path, Some(id))?;
+            let mut file = MemoryFileOpener.open_for_append(path, Some(id))?;
+            file.manager = self.manager_for_file();
             open_files.insert(id, file.buffer.clone());
             Ok(Some(file))
         } else {
@@ -241,6 +385,7 @@ impl FileManager for MemoryFileManager {
     fn append(&self, path: impl AsRef<Path>) -> Result<Self::FileHandle, Error> {
         let path = path.as_ref();
         let id = self.file_ids.file_id_for_path(path, true);
+        self.file_accessed(id);
         self.lookup_file(path, true, id).map(|file| OpenMemoryFile {
             file: file.unwrap(),
             manager: self.clone(),
@@ -274,6 +419,27 @@ impl FileManager for MemoryFileManager {
         Ok(self.forget_file(path))
     }
 
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        if from == to {
+            return Ok(());
+        }
+        let file = self.lookup_file(from, false, None)?.ok_or_else(|| {
+            ErrorKind::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                ErrorKind::message("not found"),
+            ))
+        })?;
+        self.delete(to)?;
+        {
+            let mut open_buffers = OPEN_BUFFERS.lock();
+            open_buffers.remove(from);
+            open_buffers.insert(to.to_path_buf(), Arc::downgrade(&file.buffer));
+        }
+        self.file_ids.rename_path(from, to);
+        Ok(())
+    }
+
     fn delete_directory(&self, path: impl AsRef<Path>) -> Result<(), Error> {
         let path = path.as_ref();
         let removed_ids = self.file_ids.remove_file_ids_for_path_prefix(path);
@@ -285,12 +451,43 @@ impl FileManager for MemoryFileManager {
         Ok(())
     }
 
+    fn truncate(&self, path: impl AsRef<Path>, length: u64) -> Result<(), Error> {
+        let file = self.lookup_file(path, false, None)?.ok_or_else(|| {
+            ErrorKind::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                ErrorKind::message("not found"),
+            ))
+        })?;
+        let mut buffer = file.buffer.write();
+        let length = usize::try_from(length).unwrap_or(usize::MAX);
+        buffer.truncate(length);
+        Ok(())
+    }
+
+    fn file_names(&self, directory: impl AsRef<Path>) -> Result<Vec<String>, Error> {
+        let directory = directory.as_ref();
+        let file_ids = self.file_ids.file_ids.read();
+        let open_files = self.open_files.lock();
+        Ok(file_ids
+            .iter()
+            .filter(|(path, id)| path.parent() == Some(directory) && open_files.contains_key(id))
+            .filter_map(|(path, _)| path.file_name()?.to_str().map(String::from))
+            .collect())
+    }
+
     fn close_handles<F: FnOnce(u64)>(&self, path: impl AsRef<Path>, publish_callback: F) {
         let path = path.as_ref();
         self.forget_file(path);
         let new_id = self.file_ids.file_id_for_path(path, true).unwrap();
         publish_callback(new_id);
     }
+
+    fn available_space(&self, _path: impl AsRef<Path>) -> Result<Option<u64>, Error> {
+        Ok(self
+            .capacity
+            .as_ref()
+            .map(|capacity| capacity.maximum_bytes.saturating_sub(self.used_bytes())))
+    }
 }
 
 impl ManagedFileOpener<MemoryFile> for MemoryFileManager {
@@ -299,7 +496,9 @@ impl ManagedFileOpener<MemoryFile> for MemoryFileManager {
         path: impl AsRef<Path> + Send,
         id: Option<u64>,
     ) -> Result<MemoryFile, Error> {
-        MemoryFileOpener.open_for_read(path, id)
+        let mut file = MemoryFileOpener.open_for_read(path, id)?;
+        file.manager = self.manager_for_file();
+        Ok(file)
     }
 
     fn open_for_append(
@@ -307,7 +506,9 @@ impl ManagedFileOpener<MemoryFile> for MemoryFileManager {
         path: impl AsRef<Path> + Send,
         id: Option<u64>,
     ) -> Result<MemoryFile, Error> {
-        MemoryFileOpener.open_for_append(path, id)
+        let mut file = MemoryFileOpener.open_for_append(path, id)?;
+        file.manager = self.manager_for_file();
+        Ok(file)
     }
 }
 
@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+use parking_lot::Mutex;
+
+use super::{FileManager, FileOp, ManagedFile, OpenableFile};
+use crate::{
+    error::Error,
+    io::{File as _, ManagedFileOpener, OperableFile, PathIds},
+};
+
+/// An open file whose reads are served from a read-only memory map.
+///
+/// Writes are appended to the underlying [`std::fs::File`]. When a read
+/// reaches past the end of the current mapping, the file is remapped to
+/// include any data appended since it was last mapped.
+///
+/// Nebari never rewrites its files in place. Files are only shortened using
+/// [`FileManager::truncate()`], which closes the manager's cached handles for
+/// the file. Before each read, the file's length is checked, and the file is
+/// remapped if it has been shortened, so that the mapping never extends past
+/// the end of the file. Files managed by an [`MmapFileManager`] must not be
+/// truncated by other processes while they are open.
+#[derive(Debug)]
+pub struct MmapFile {
+    file: File,
+    path: PathBuf,
+    id: Option<u64>,
+    map: Option<Mmap>,
+    position: u64,
+}
+
+impl MmapFile {
+    fn new(file: File, path: &Path, id: Option<u64>) -> Self {
+        Self {
+            file,
+            path: path.to_path_buf(),
+            id,
+            map: None,
+            position: 0,
+        }
+    }
+
+    fn mapped_length(&self) -> u64 {
+        self.map.as_ref().map_or(0, |map| map.len() as u64)
+    }
+
+    /// Maps the file again if it has been shortened since it was last
+    /// mapped, or if it has grown and `read_end` is past the end of the
+    /// current mapping.
+    fn remap(&mut self, read_end: u64) -> io::Result<()> {
+        let length = self.file.metadata()?.len();
+        let mapped_length = self.mapped_length();
+        if length < mapped_length || (length > mapped_length && read_end > mapped_length) {
+            // Drop the previous mapping before creating a new one, so that
+            // no mapping of truncated bytes outlives this call.
+            self.map = None;
+            if length > 0 {
+                // SAFETY: Nebari never modifies bytes that have been written
+                // to a file. Compaction writes a new file and renames it over
+                // the old one, which leaves the contents of existing mappings
+                // untouched. Files are only shortened by
+                // `FileManager::truncate()`, which is only called while the
+                // file isn't being read, and closes the manager's cached
+                // handles. Every read checks the file's length before
+                // accessing the mapping, and remaps it if the file has been
+                // shortened.
+                #[allow(unsafe_code)]
+                let map = unsafe { Mmap::map(&self.file)? };
+                self.map = Some(map);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ManagedFile for MmapFile {
+    type Manager = MmapFileManager;
+}
+
+impl super::File for MmapFile {
+    fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn length(&self) -> Result<u64, Error> {
+        let metadata = self.file.metadata()?;
+        Ok(metadata.len())
+    }
+
+    fn close(mut self) -> Result<(), Error> {
+        self.synchronize()
+    }
+
+    fn synchronize(&mut self) -> Result<(), crate::Error> {
+        self.file.sync_all().map_err(Error::from)
+    }
+}
+
+/// A [`ManagedFileOpener`] implementation that produces [`MmapFile`]s.
+pub struct MmapFileOpener;
+
+impl ManagedFileOpener<MmapFile> for MmapFileOpener {
+    fn open_for_read(
+        &self,
+        path: impl AsRef<std::path::Path> + Send,
+        id: Option<u64>,
+    ) -> Result<MmapFile, Error> {
+        let path = path.as_ref();
+        Ok(MmapFile::new(File::open(path)?, path, id))
+    }
+
+    fn open_for_append(
+        &self,
+        path: impl AsRef<std::path::Path> + Send,
+        id: Option<u64>,
+    ) -> Result<MmapFile, Error> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .write(true)
+            .append(true)
+            .read(true)
+            .create(true)
+            .open(path)?;
+        let mut file = MmapFile::new(file, path, id);
+        file.position = file.length()?;
+        Ok(file)
+    }
+}
+
+impl Seek for MmapFile {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self,)))]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.file.metadata()?.len(), offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+        let position = if offset.is_negative() {
+            base.checked_sub(offset.unsigned_abs())
+        } else {
+            base.checked_add(offset.unsigned_abs())
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl Write for MmapFile {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, buf)))]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The file is opened in append mode, so writes always land at the end
+        // of the file regardless of the current position.
+        let written = self.file.write(buf)?;
+        self.position = self.file.stream_position()?;
+        Ok(written)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for MmapFile {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, buf)))]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Accessing a mapped page past the end of the file raises SIGBUS, so
+        // the file's length is checked before every read.
+        self.remap(self.position + buf.len() as u64)?;
+        let map = match &self.map {
+            Some(map) => map,
+            None => return Ok(0),
+        };
+        let start = usize::try_from(self.position)
+            .unwrap_or(usize::MAX)
+            .min(map.len());
+        let available = &map[start..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// The [`FileManager`] for [`MmapFile`].
+#[derive(Debug, Default, Clone)]
+pub struct MmapFileManager {
+    file_ids: PathIds,
+    open_files: Arc<Mutex<HashMap<u64, FileSlot>>>,
+    reader_files: Arc<Mutex<HashMap<u64, VecDeque<MmapFile>>>>,
+}
+
+#[derive(Debug)]
+enum FileSlot {
+    Available(MmapFile),
+    Taken,
+    Waiting(flume::Sender<MmapFile>),
+}
+
+impl MmapFileManager {
+    fn forget_file_id(&self, file_id: u64) {
+        let mut open_files = self.open_files.lock();
+        let mut reader_files = self.reader_files.lock();
+        open_files.remove(&file_id);
+        reader_files.remove(&file_id);
+    }
+}
+
+impl FileManager for MmapFileManager {
+    type File = MmapFile;
+    type FileHandle = OpenMmapFile;
+    fn append(&self, path: impl AsRef<Path>) -> Result<Self::FileHandle, Error> {
+        let path = path.as_ref();
+        let file_id = self.file_ids.file_id_for_path(path, true).unwrap();
+        let mut open_files = self.open_files.lock();
+        if let Some(open_file) = open_files.get_mut(&file_id) {
+            let mut file = FileSlot::Taken;
+            std::mem::swap(&mut file, open_file);
+            let file = match file {
+                FileSlot::Available(file) => file,
+                other => {
+                    let (file_sender, file_receiver) = flume::bounded(1);
+                    *open_file = FileSlot::Waiting(file_sender);
+                    drop(open_files);
+
+                    match file_receiver.recv() {
+                        Ok(file) => {
+                            if let FileSlot::Waiting(other_sender) = other {
+                                let mut open_files = self.open_files.lock();
+                                if let Some(open_file) = open_files.get_mut(&file_id) {
+                                    *open_file = FileSlot::Waiting(other_sender);
+                                }
+                            }
+                            file
+                        }
+                        Err(flume::RecvError::Disconnected) => {
+                            return self.append(path);
+                        }
+                    }
+                }
+            };
+            Ok(OpenMmapFile {
+                file: Some(file),
+                reader: false,
+                manager: Some(self.clone()),
+            })
+        } else {
+            let file = self.open_for_append(path, Some(file_id))?;
+            open_files.insert(file_id, FileSlot::Taken);
+            Ok(OpenMmapFile {
+                file: Some(file),
+                reader: false,
+                manager: Some(self.clone()),
+            })
+        }
+    }
+
+    fn read(&self, path: impl AsRef<Path>) -> Result<Self::FileHandle, Error> {
+        let path = path.as_ref();
+        let file_id = self.file_ids.file_id_for_path(path, true).unwrap();
+
+        let mut reader_files = self.reader_files.lock();
+        let files = reader_files.entry(file_id).or_default();
+
+        if let Some(file) = files.pop_front() {
+            return Ok(OpenMmapFile {
+                file: Some(file),
+                manager: Some(self.clone()),
+                reader: true,
+            });
+        }
+
+        let file = MmapFileOpener.open_for_read(path, Some(file_id))?;
+        Ok(OpenMmapFile {
+            file: Some(file),
+            manager: Some(self.clone()),
+            reader: true,
+        })
+    }
+
+    fn delete(&self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        let path = path.as_ref();
+        if let Some(file_id) = self.file_ids.remove_file_id_for_path(path) {
+            self.forget_file_id(file_id);
+        }
+
+        if path.exists() {
+            std::fs::remove_file(path)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        if from == to {
+            return Ok(());
+        }
+        std::fs::rename(from, to)?;
+
+        // Cached handles report the path they were opened with, so they can't
+        // be reused.
+        if let Some(file_id) = self.file_ids.remove_file_id_for_path(to) {
+            self.forget_file_id(file_id);
+        }
+        if let Some(file_id) = self.file_ids.rename_path(from, to) {
+            self.forget_file_id(file_id);
+        }
+        Ok(())
+    }
+
+    fn delete_directory(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        for id in self.file_ids.remove_file_ids_for_path_prefix(path) {
+            self.forget_file_id(id);
+        }
+
+        if path.exists() {
+            std::fs::remove_dir_all(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn truncate(&self, path: impl AsRef<Path>, length: u64) -> Result<(), Error> {
+        let path = path.as_ref();
+        if let Some(file_id) = self.file_ids.file_id_for_path(path, false) {
+            self.forget_file_id(file_id);
+        }
+        super::fs::truncate_file(path, length)
+    }
+
+    fn file_names(&self, directory: impl AsRef<Path>) -> Result<Vec<String>, Error> {
+        super::fs::file_names(directory.as_ref())
+    }
+
+    #[cfg(feature = "available-space")]
+    fn available_space(&self, path: impl AsRef<Path>) -> Result<Option<u64>, Error> {
+        Ok(Some(fs2::available_space(path)?))
+    }
+
+    fn close_handles<F: FnOnce(u64)>(&self, path: impl AsRef<Path>, publish_callback: F) {
+        // Dropping the cached handles releases their mappings of the previous
+        // file, so readers of the new id will map the replacement.
+        if let Some(result) = self.file_ids.recreate_file_id_for_path(path.as_ref()) {
+            self.forget_file_id(result.previous_id);
+            publish_callback(result.new_id);
+        }
+    }
+
+    fn exists(&self, path: impl AsRef<std::path::Path>) -> Result<bool, crate::Error> {
+        Ok(path.as_ref().exists())
+    }
+
+    fn file_length(&self, path: impl AsRef<Path>) -> Result<u64, Error> {
+        path.as_ref()
+            .metadata()
+            .map_err(Error::from)
+            .map(|metadata| metadata.len())
+    }
+}
+
+impl ManagedFileOpener<MmapFile> for MmapFileManager {
+    fn open_for_read(
+        &self,
+        path: impl AsRef<Path> + Send,
+        id: Option<u64>,
+    ) -> Result<MmapFile, Error> {
+        MmapFileOpener.open_for_read(path, id)
+    }
+
+    fn open_for_append(
+        &self,
+        path: impl AsRef<Path> + Send,
+        id: Option<u64>,
+    ) -> Result<MmapFile, Error> {
+        MmapFileOpener.open_for_append(path, id)
+    }
+}
+
+/// An open [`MmapFile`] that belongs to a [`MmapFileManager`].
+#[derive(Debug)]
+pub struct OpenMmapFile {
+    file: Option<MmapFile>,
+    manager: Option<MmapFileManager>,
+    reader: bool,
+}
+
+impl OpenableFile<MmapFile> for OpenMmapFile {
+    fn id(&self) -> Option<u64> {
+        self.file.as_ref().and_then(MmapFile::id)
+    }
+
+    fn replace_with<C: FnOnce(u64)>(
+        self,
+        replacement: MmapFile,
+        manager: &MmapFileManager,
+        publish_callback: C,
+    ) -> Result<Self, Error> {
+        let current_path = self.file.as_ref().unwrap().path.clone();
+        self.close()?;
+        let path = replacement.path.clone();
+        replacement.close()?;
+
+        std::fs::rename(path, &current_path)?;
+        manager.close_handles(&current_path, publish_callback);
+        manager.append(current_path)
+    }
+
+    fn close(self) -> Result<(), Error> {
+        drop(self);
+        Ok(())
+    }
+}
+
+impl OperableFile<MmapFile> for OpenMmapFile {
+    fn execute<Output, Op: FileOp<Output>>(&mut self, operator: Op) -> Output {
+        operator.execute(self.file.as_mut().unwrap())
+    }
+}
+
+impl Drop for OpenMmapFile {
+    fn drop(&mut self) {
+        if let Some(manager) = &self.manager {
+            let file = self.file.take().unwrap();
+            if let Some(file_id) = file.id {
+                if self.reader {
+                    let mut reader_files = manager.reader_files.lock();
+                    if let Some(path_files) = reader_files.get_mut(&file_id) {
+                        path_files.push_front(file);
+                    }
+                } else {
+                    let mut writer_files = manager.open_files.lock();
+                    if let Some(writer_file) = writer_files.get_mut(&file_id) {
+                        match writer_file {
+                            FileSlot::Available(_) => unreachable!(),
+                            FileSlot::Taken => {
+                                *writer_file = FileSlot::Available(file);
+                            }
+                            FileSlot::Waiting(sender) => {
+                                if let Err(flume::SendError(file)) = sender.send(file) {
+                                    *writer_file = FileSlot::Available(file);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -25,6 +25,9 @@ pub mod any;
 pub mod fs;
 /// A virtual memory-based filesystem.
 pub mod memory;
+/// Filesystem IO that serves reads from memory-mapped files.
+#[cfg(feature = "mmap")]
+pub mod mmap;
 
 /// A file that is managed by a [`FileManager`].
 pub trait ManagedFile: File {
@@ -98,8 +101,35 @@ pub trait FileManager:
     /// Check if the file exists.
     fn delete(&self, path: impl AsRef<Path>) -> Result<bool, Error>;
 
+    /// Renames the file at `from` to `to`, replacing the file at `to` if it
+    /// exists. Open handles for `from` are closed, and the file keeps the id
+    /// it had at `from`.
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error>;
+
     /// Removes a directory and all of its contents.
     fn delete_directory(&self, path: impl AsRef<Path>) -> Result<(), Error>;
+
+    /// Shortens the file at `path` to `length` bytes and synchronizes it.
+    /// Cached handles for the file are closed, and the file keeps its id.
+    ///
+    /// Files must only be truncated while no other handles to them are in
+    /// use.
+    fn truncate(&self, path: impl AsRef<Path>, length: u64) -> Result<(), Error>;
+
+    /// Returns the names of the files stored directly within `directory`.
+    /// Names that aren't valid UTF-8 are skipped.
+    fn file_names(&self, directory: impl AsRef<Path>) -> Result<Vec<String>, Error>;
+
+    /// Returns the number of bytes that can still be written to files stored
+    /// at `path`, or `None` if it can't be determined. Used to enforce
+    /// [`Config::min_free_space()`](crate::Config::min_free_space), which
+    /// can't be used with a file manager that returns `None`.
+    ///
+    /// The default implementation returns `None`.
+    fn available_space(&self, path: impl AsRef<Path>) -> Result<Option<u64>, Error> {
+        let _ = path;
+        Ok(None)
+    }
 }
 
 /// A file that can have operations performed on it.
@@ -165,6 +195,14 @@ impl PathIds {
         file_ids.remove(path)
     }
 
+    /// Moves the id of `from` to `to`, returning the id if `from` had one.
+    fn rename_path(&self, from: &Path, to: &Path) -> Option<u64> {
+        let mut file_ids = self.file_ids.write();
+        let id = file_ids.remove(from)?;
+        file_ids.insert(to.to_path_buf(), id);
+        Some(id)
+    }
+
     fn recreate_file_id_for_path(&self, path: &Path) -> Option<RecreatedFile<'_>> {
         let mut file_ids = self.file_ids.write();
         let new_id = self.file_id_counter.fetch_add(1, Ordering::SeqCst);
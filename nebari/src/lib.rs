@@ -1,5 +1,6 @@
 #![doc = include_str!("./crate-docs.md")]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))]
 #![warn(
     clippy::cargo,
     missing_docs,
@@ -22,22 +23,37 @@ mod error;
 mod roots;
 pub mod transaction;
 pub mod tree;
-mod vault;
+pub mod vault;
 
 mod chunk_cache;
+mod clock;
 mod context;
+mod operation_log;
+mod recovery;
+#[cfg(feature = "async")]
+mod stream;
 #[cfg(test)]
 mod test_util;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
 pub use arc_bytes::ArcBytes;
 
 pub use self::{
-    chunk_cache::ChunkCache,
+    chunk_cache::{CacheStatistics, ChunkCache},
+    clock::{Clock, SystemClock},
     context::Context,
     error::{Error, ErrorKind},
+    operation_log::OperationKind,
+    recovery::{RecoveryAction, RecoveryFn, RecoverySituation},
     roots::{
-        AbortError, CompareAndSwapError, Config, ExecutingTransaction, LockedTransactionTree,
-        Roots, ThreadPool, TransactionTree, Tree, UnlockedTransactionTree,
+        retry_on_conflict, AbortError, CommitEvent, CompactionPolicy, CompareAndSwapError,
+        CompareAndSwapSequenceError, Config, ExecutingTransaction, LockedTransactionTree,
+        RepairPolicy, Roots, SharedTransactionTree, Snapshot, SnapshotTree, SpawnFn, ThreadPool,
+        TransactionBuilder, TransactionTree, Tree, TreeRepair, UnlockedTransactionTree,
     },
     vault::Vault,
 };
+
+#[cfg(feature = "async")]
+pub use self::stream::RangeStream;
@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+use parking_lot::Mutex;
+
+use crate::{transaction::TransactionId, ArcBytes};
+
+/// The kind of operation applied to a key. Returned from
+/// [`Tree::recent_operations()`](crate::Tree::recent_operations).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OperationKind {
+    /// The key was set to a value.
+    Set,
+    /// The key was removed.
+    Remove,
+}
+
+/// A bounded log of the keys modified by recently committed transactions.
+/// Enabled using [`Config::operation_log`](crate::Config::operation_log).
+#[derive(Debug)]
+pub struct OperationLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<LoggedOperation>>,
+}
+
+#[derive(Debug)]
+struct LoggedOperation {
+    tree: String,
+    key: ArcBytes<'static>,
+    transaction_id: TransactionId,
+    kind: OperationKind,
+}
+
+impl OperationLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Records `operations` as having been committed to `tree` in
+    /// `transaction_id`. The oldest entries are discarded once the log is full.
+    pub fn record(
+        &self,
+        transaction_id: TransactionId,
+        tree: &str,
+        operations: Vec<(ArcBytes<'static>, OperationKind)>,
+    ) {
+        if operations.is_empty() || self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock();
+        for (key, kind) in operations {
+            if entries.len() == self.capacity {
+                entries.pop_front();
+            }
+            entries.push_back(LoggedOperation {
+                tree: tree.to_string(),
+                key,
+                transaction_id,
+                kind,
+            });
+        }
+    }
+
+    /// Returns the logged operations for `key` in `tree`, oldest first.
+    pub fn recent_operations(&self, tree: &str, key: &[u8]) -> Vec<(TransactionId, OperationKind)> {
+        let entries = self.entries.lock();
+        entries
+            .iter()
+            .filter(|entry| entry.tree == tree && entry.key == *key)
+            .map(|entry| (entry.transaction_id, entry.kind))
+            .collect()
+    }
+}
@@ -0,0 +1,84 @@
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+use crate::transaction::TransactionId;
+
+/// A situation encountered while loading the current root of a tree file that
+/// requires a recovery decision. Passed to the handler configured using
+/// [`Config::recovery_handler()`](crate::Config::recovery_handler) or
+/// [`Context::with_recovery_handler()`](crate::Context::with_recovery_handler).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RecoverySituation<'a> {
+    /// The root stored at `position` was written by a transaction that the
+    /// transaction log doesn't list as committed. The data written by the
+    /// transaction may be incomplete.
+    ///
+    /// The default action is [`RecoveryAction::RollBack`].
+    UnconfirmedTransaction {
+        /// The path of the tree file.
+        tree_path: &'a Path,
+        /// The id of the transaction that wrote the root.
+        transaction_id: TransactionId,
+        /// The position of the root's page header in the tree file.
+        position: u64,
+    },
+    /// The tree file contains data, but no valid root could be found.
+    ///
+    /// The default action is [`RecoveryAction::Reset`].
+    NoValidRoot {
+        /// The path of the tree file.
+        tree_path: &'a Path,
+    },
+}
+
+impl<'a> RecoverySituation<'a> {
+    /// Returns the path of the tree file being loaded.
+    #[must_use]
+    pub const fn tree_path(&self) -> &'a Path {
+        match self {
+            Self::UnconfirmedTransaction { tree_path, .. } | Self::NoValidRoot { tree_path } => {
+                tree_path
+            }
+        }
+    }
+
+    /// Returns the action taken when no recovery handler is configured.
+    #[must_use]
+    pub const fn default_action(&self) -> RecoveryAction {
+        match self {
+            Self::UnconfirmedTransaction { .. } => RecoveryAction::RollBack,
+            Self::NoValidRoot { .. } => RecoveryAction::Reset,
+        }
+    }
+}
+
+/// The decision returned by a recovery handler.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RecoveryAction {
+    /// Ignore the root in question and continue searching for an earlier
+    /// root. If no earlier root exists, the tree is loaded as if it were
+    /// empty. For [`RecoverySituation::NoValidRoot`], this is the same as
+    /// [`RecoveryAction::Reset`].
+    RollBack,
+    /// Use the root in question even though its transaction wasn't confirmed.
+    /// For [`RecoverySituation::NoValidRoot`], this is the same as
+    /// [`RecoveryAction::Reset`].
+    Accept,
+    /// Load the tree as if it were empty. The existing contents of the file
+    /// are not removed, but are no longer reachable once new data is written.
+    Reset,
+    /// Stop loading the tree and return
+    /// [`ErrorKind::RecoveryAborted`](crate::ErrorKind::RecoveryAborted).
+    Abort,
+}
+
+/// A function that decides how to recover from a [`RecoverySituation`].
+pub type RecoveryFn = dyn for<'a> Fn(RecoverySituation<'a>) -> RecoveryAction + Send + Sync;
+
+#[derive(Clone)]
+pub struct RecoveryHandler(pub Arc<RecoveryFn>);
+
+impl Debug for RecoveryHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RecoveryHandler").finish()
+    }
+}
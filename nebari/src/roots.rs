@@ -1,37 +1,48 @@
 use std::{
     any::Any,
     borrow::{Borrow, Cow},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     convert::Infallible,
     fmt::{Debug, Display},
     fs,
-    ops::{Deref, DerefMut, RangeBounds},
+    hash::Hash,
+    ops::{Bound, Deref, DerefMut, RangeBounds},
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU16, Ordering},
+        atomic::{AtomicBool, AtomicU16, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use flume::Sender;
 use once_cell::sync::Lazy;
-use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
 
 use crate::{
     context::Context,
     error::Error,
-    io::{fs::StdFileManager, FileManager, ManagedFile},
+    io::{
+        self, fs::StdFileManager, File as _, FileManager, FileOp, ManagedFile, ManagedFileOpener,
+        OperableFile,
+    },
+    operation_log::OperationLog,
+    recovery::{RecoveryAction, RecoveryHandler, RecoverySituation},
     transaction::{LogEntry, ManagedTransaction, TransactionId, TransactionManager},
     tree::{
         self,
         root::{AnyReducer, AnyTreeRoot},
         state::AnyTreeState,
-        EmbeddedIndex, KeySequence, Modification, ModificationResult, Operation, PersistenceMode,
-        ScanEvaluation, SequenceEntry, SequenceId, SequenceIndex, State, TransactableCompaction,
-        TreeFile, TreeRoot, VersionedTreeRoot,
+        Batch, BorrowedRange, ByIdStats, CancellationToken, EmbeddedIndex, KeySequence,
+        Modification, ModificationResult, ModificationResultWithPrevious, Operation,
+        PersistenceMode, RangeIter, ScanCursor, ScanEvaluation, ScanProgress, SequenceEntry,
+        SequenceId, SequenceIndex, State, TransactableCompaction, TreeFile, TreeIter, TreeRoot,
+        TreeStatistics, VersionedTreeRoot,
     },
-    vault::AnyVault,
-    ArcBytes, ChunkCache, ErrorKind,
+    vault::{self, AnyVault},
+    ArcBytes, ChunkCache, Clock, ErrorKind, OperationKind, SystemClock,
 };
 
 /// A multi-tree transactional B-Tree database.
@@ -47,16 +58,84 @@ struct Data<File: ManagedFile> {
     thread_pool: ThreadPool<File>,
     path: PathBuf,
     tree_states: Mutex<HashMap<String, Box<dyn AnyTreeState>>>,
+    /// Held exclusively while a transaction publishes its trees' states, which
+    /// allows snapshots to capture the states of multiple trees consistently.
+    publish_lock: RwLock<()>,
+    clock: Arc<dyn Clock>,
+    operation_log: Option<OperationLog>,
+    file_extension: String,
+    repairs: Vec<TreeRepair>,
+    min_free_space: Option<u64>,
+    compaction_policy: CompactionPolicy,
+    auto_compactions: Mutex<AutoCompactions>,
+    read_only: bool,
+    commit_subscribers: Mutex<Vec<Sender<CommitEvent>>>,
+}
+
+/// Tracks the trees compacted by a [`CompactionPolicy`].
+#[derive(Debug, Default)]
+struct AutoCompactions {
+    /// The trees that have a compaction scheduled.
+    scheduled: HashSet<String>,
+    /// The length of each tree's file after it was last compacted.
+    compacted_lengths: HashMap<String, u64>,
+    /// Sends compactions to the thread that runs them, which is spawned when
+    /// the first compaction is scheduled. Compactions run one at a time on
+    /// this thread rather than on the thread pool, so that they don't delay
+    /// commits. The thread exits once the database is dropped.
+    worker: Option<flume::Sender<Box<dyn FnOnce() + Send>>>,
 }
 
 impl<File: ManagedFile> Roots<File> {
-    fn open<P: Into<PathBuf> + Send>(
-        path: P,
-        context: Context<File::Manager>,
-        thread_pool: ThreadPool<File>,
-    ) -> Result<Self, Error> {
-        let path = path.into();
+    fn open(config: Config<File::Manager>) -> Result<Self, Error> {
+        let Config {
+            path,
+            vault,
+            cache,
+            file_manager,
+            thread_pool,
+            clock,
+            operation_log,
+            file_extension,
+            thread_spawner,
+            repair_policy,
+            min_free_space,
+            recovery_handler,
+            page_size,
+            write_buffer_size,
+            key_compression,
+            compaction_policy,
+            read_only,
+            synchronize_transaction_log,
+        } = config;
+        let file_extension = file_extension.unwrap_or_else(|| String::from("nebari"));
+        if file_extension.is_empty() || file_extension.contains(std::path::is_separator) {
+            return Err(Error::from(format!(
+                "invalid file extension: {:?}",
+                file_extension
+            )));
+        }
+        let mut thread_pool = thread_pool.unwrap_or_default();
+        if let Some(spawner) = thread_spawner {
+            thread_pool.spawner = Some(spawner);
+        }
+        let context = Context {
+            file_manager: file_manager.unwrap_or_default(),
+            vault,
+            cache,
+            recovery_handler,
+            page_size,
+            write_buffer_size,
+            key_compression,
+        };
+
         if !path.exists() {
+            if read_only {
+                return Err(Error::from(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("'{:?}' does not exist", path),
+                )));
+            }
             fs::create_dir_all(&path)?;
         } else if !path.is_dir() {
             return Err(Error::from(format!(
@@ -65,7 +144,55 @@ impl<File: ManagedFile> Roots<File> {
             )));
         }
 
-        let transactions = TransactionManager::spawn(&path, context.clone())?;
+        if min_free_space.is_some() && context.file_manager.available_space(&path)?.is_none() {
+            return Err(Error::from(
+                "min_free_space is set, but the file manager can't determine the available space",
+            ));
+        }
+
+        let new_database = match context
+            .file_manager
+            .file_length(TransactionManager::<File::Manager>::log_path(&path))
+        {
+            Ok(length) => length == 0,
+            Err(err) if err.kind.is_file_not_found() => true,
+            Err(err) => return Err(err),
+        };
+        vault::verify_vault(&path, &context, new_database, read_only)?;
+
+        if read_only {
+            if context.file_manager.exists(path.join(SWAP_INTENT_FILE))? {
+                return Err(Error::from(
+                    "a swap of two trees was interrupted and must be recovered by opening the database for writing",
+                ));
+            }
+        } else {
+            recover_tree_swap(&context.file_manager, &path, &file_extension)?;
+        }
+
+        let transactions = if read_only {
+            TransactionManager::open_read_only(&path, context.clone())?
+        } else if synchronize_transaction_log {
+            TransactionManager::spawn(&path, context.clone())?
+        } else {
+            TransactionManager::spawn_unsynchronized(&path, context.clone())?
+        };
+
+        let mut repairs = Vec::new();
+        if repair_policy == RepairPolicy::TruncateToLastValidRoot && !read_only {
+            for name in tree_names(&context.file_manager, &path, &file_extension)? {
+                let tree_path = path.join(format!("{}.{}", name, file_extension));
+                if let Some(bytes_discarded) =
+                    tree::repair_tree::<File>(&tree_path, &context, &transactions)?
+                {
+                    repairs.push(TreeRepair {
+                        tree: name,
+                        bytes_discarded,
+                    });
+                }
+            }
+        }
+
         Ok(Self {
             data: Arc::new(Data {
                 context,
@@ -73,6 +200,16 @@ impl<File: ManagedFile> Roots<File> {
                 transactions,
                 thread_pool,
                 tree_states: Mutex::default(),
+                publish_lock: RwLock::default(),
+                clock: clock.unwrap_or_else(|| Arc::new(SystemClock)),
+                operation_log: operation_log.map(OperationLog::new),
+                file_extension,
+                repairs,
+                min_free_space,
+                compaction_policy,
+                auto_compactions: Mutex::default(),
+                read_only,
+                commit_subscribers: Mutex::default(),
             }),
         })
     }
@@ -88,64 +225,491 @@ impl<File: ManagedFile> Roots<File> {
         &self.data.context
     }
 
+    /// Returns the clock configured using [`Config::clock()`].
+    #[must_use]
+    pub fn clock(&self) -> &dyn Clock {
+        self.data.clock.as_ref()
+    }
+
+    /// Returns the operations recorded for `key` in the tree named `tree`,
+    /// oldest first. Always empty unless the operation log was enabled using
+    /// [`Config::operation_log()`].
+    #[must_use]
+    pub fn recent_operations(&self, tree: &str, key: &[u8]) -> Vec<(TransactionId, OperationKind)> {
+        self.data
+            .operation_log
+            .as_ref()
+            .map(|log| log.recent_operations(tree, key))
+            .unwrap_or_default()
+    }
+
+    /// Returns a receiver of a [`CommitEvent`] for each transaction committed
+    /// after this function returns. Events are sent once the committed trees'
+    /// states are visible to readers, in the order the transactions were
+    /// committed for any given tree.
+    ///
+    /// Each receiver receives every event. Dropping the receiver unsubscribes
+    /// it.
+    #[must_use]
+    pub fn subscribe(&self) -> flume::Receiver<CommitEvent> {
+        let (sender, receiver) = flume::unbounded();
+        self.data.commit_subscribers.lock().push(sender);
+        receiver
+    }
+
+    fn notify_subscribers(&self, transaction_id: TransactionId, trees: &[String]) {
+        let mut subscribers = self.data.commit_subscribers.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+        let event = CommitEvent {
+            transaction_id,
+            trees: trees.to_vec(),
+        };
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
     /// Returns the transaction manager for this database.
     #[must_use]
     pub fn transactions(&self) -> &TransactionManager<File::Manager> {
         &self.data.transactions
     }
 
-    /// Opens a tree named `name`.
+    /// Opens a tree named `name`. If this database was opened using
+    /// [`Config::read_only()`], the tree is opened using
+    /// [`tree_read_only()`](Self::tree_read_only).
     ///
     /// ## Errors
     ///
     /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): The name contained an
     ///   invalid character. For a full list of valid characters, see the
     ///   documentation on [`InvalidTreeName`](ErrorKind::InvalidTreeName).
+    /// - [`Io`](ErrorKind::Io): This database is read-only and no tree named
+    ///   `name` exists, in which case the error's kind is
+    ///   [`NotFound`](std::io::ErrorKind::NotFound).
     pub fn tree<Root: tree::Root>(
         &self,
         root: TreeRoot<Root, File>,
     ) -> Result<Tree<Root, File>, Error> {
+        if self.data.read_only {
+            return self.tree_read_only(root);
+        }
         check_name(&root.name)?;
         let path = self.tree_path(&root.name);
         if !path.exists() {
             self.context().file_manager.append(&path)?;
         }
         let state = self.tree_state(root.clone());
+        if root.max_order.is_some() {
+            state.set_max_order(root.max_order);
+        }
+        Ok(Tree {
+            roots: self.clone(),
+            vault_generation: state.vault_generation(),
+            state,
+            vault: root.vault,
+            reducer: root.reducer,
+            max_order: root.max_order,
+            name: root.name,
+            read_only: false,
+        })
+    }
+
+    /// Opens an existing tree named `name` without the ability to modify it.
+    /// Unlike [`tree()`](Self::tree), the tree's file is never created, which
+    /// allows inspecting trees stored on read-only storage.
+    ///
+    /// Every function that modifies the returned tree returns
+    /// [`ReadOnly`](ErrorKind::ReadOnly).
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): The name contained an
+    ///   invalid character.
+    /// - [`Io`](ErrorKind::Io): No tree named `name` exists, in which case the
+    ///   error's kind is [`NotFound`](std::io::ErrorKind::NotFound).
+    pub fn tree_read_only<Root: tree::Root>(
+        &self,
+        root: TreeRoot<Root, File>,
+    ) -> Result<Tree<Root, File>, Error> {
+        if !self.tree_exists(&root.name)? {
+            return Err(Error::from(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("tree {:?} does not exist", root.name),
+            )));
+        }
+        let state = self.tree_state(root.clone());
         Ok(Tree {
             roots: self.clone(),
+            vault_generation: state.vault_generation(),
             state,
             vault: root.vault,
             reducer: root.reducer,
+            max_order: root.max_order,
             name: root.name,
+            read_only: true,
         })
     }
 
     fn tree_path(&self, name: &str) -> PathBuf {
-        self.path().join(format!("{}.nebari", name))
+        self.path()
+            .join(format!("{}.{}", name, self.data.file_extension))
+    }
+
+    /// Returns true if this database was opened using
+    /// [`Config::read_only()`].
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.data.read_only
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.data.read_only {
+            Err(Error::from(ErrorKind::ReadOnly))
+        } else {
+            Ok(())
+        }
     }
 
     /// Removes a tree. Returns true if a tree was deleted.
     pub fn delete_tree(&self, name: impl Into<Cow<'static, str>>) -> Result<bool, Error> {
+        self.check_writable()?;
         let name = name.into();
         let mut tree_states = self.data.tree_states.lock();
+        let tree_path = self.tree_path(name.as_ref());
         self.context()
             .file_manager
-            .delete(self.tree_path(name.as_ref()))?;
+            .delete(tree::manifest_path(&tree_path))?;
+        self.context().file_manager.delete(tree_path)?;
         Ok(tree_states.remove(name.as_ref()).is_some())
     }
 
+    /// Returns true if a tree named `name` exists. Unlike
+    /// [`tree()`](Self::tree), this doesn't create the tree if it doesn't
+    /// exist.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): The name contained an
+    ///   invalid character.
+    pub fn tree_exists(&self, name: &str) -> Result<bool, Error> {
+        check_name(name)?;
+        self.context().file_manager.exists(self.tree_path(name))
+    }
+
+    /// Renames the tree named `from` to `to`. The tree's file is renamed
+    /// rather than copied, so this is fast regardless of the tree's size.
+    ///
+    /// [`Tree`]s obtained before the rename continue to refer to the tree
+    /// named `from`.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): `from` or `to`
+    ///   contained an invalid character.
+    /// - [`Io`](ErrorKind::Io): No tree named `from` exists, in which case the
+    ///   error's kind is [`NotFound`](std::io::ErrorKind::NotFound), or a tree
+    ///   named `to` already exists, in which case the error's kind is
+    ///   [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+    /// - [`Message`](ErrorKind::Message): A transaction is currently using
+    ///   `from` or `to`.
+    pub fn rename_tree(&self, from: &str, to: &str) -> Result<(), Error> {
+        self.check_writable()?;
+        check_name(from)?;
+        check_name(to)?;
+        let file_manager = &self.context().file_manager;
+        let from_path = self.tree_path(from);
+        let to_path = self.tree_path(to);
+        if !file_manager.exists(&from_path)? {
+            return Err(Error::from(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("tree {:?} does not exist", from),
+            )));
+        } else if file_manager.exists(&to_path)? {
+            return Err(Error::from(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("tree {:?} already exists", to),
+            )));
+        }
+
+        // Holding both trees' locks prevents transactions from accessing
+        // either tree while its files are renamed.
+        let _locks = self
+            .transactions()
+            .try_lock_trees([from.as_bytes(), to.as_bytes()])
+            .ok_or_else(|| {
+                Error::from(format!(
+                    "tree {:?} or {:?} is in use by a transaction",
+                    from, to
+                ))
+            })?;
+        let mut tree_states = self.data.tree_states.lock();
+        file_manager.rename(&from_path, &to_path)?;
+        let from_manifest = tree::manifest_path(&from_path);
+        let to_manifest = tree::manifest_path(&to_path);
+        file_manager.delete(&to_manifest)?;
+        if file_manager.exists(&from_manifest)? {
+            file_manager.rename(&from_manifest, &to_manifest)?;
+        }
+        match tree_states.remove(from) {
+            Some(state) => {
+                tree_states.insert(to.to_string(), state);
+            }
+            None => {
+                tree_states.remove(to);
+            }
+        }
+        Ok(())
+    }
+
+    /// Exchanges the contents of the trees named `a` and `b`. The trees' files
+    /// are swapped using rename operations, so this is fast regardless of the
+    /// trees' sizes.
+    ///
+    /// [`Tree`]s obtained before the swap continue to refer to the tree by
+    /// name, which means a [`Tree`] for `a` will see the contents previously
+    /// stored in `b` once this function returns. Readers never observe a
+    /// partially swapped tree, but a reader that opens a tree's file while
+    /// the files are being renamed may fail to find the file.
+    ///
+    /// Trees that haven't been opened yet are read using this instance's
+    /// vault, if one is configured.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): `a` or `b`
+    ///   contained an invalid character.
+    /// - [`Io`](ErrorKind::Io): No tree named `a` or `b` exists, in which case
+    ///   the error's kind is [`NotFound`](std::io::ErrorKind::NotFound).
+    /// - [`Message`](ErrorKind::Message): A transaction is currently using
+    ///   `a` or `b`, or either tree was opened with a different
+    ///   [`Root`](tree::Root) type.
+    pub fn swap_trees<Root: tree::Root>(&self, a: &str, b: &str) -> Result<(), Error>
+    where
+        Root::Reducer: Default,
+    {
+        self.check_writable()?;
+        check_name(a)?;
+        check_name(b)?;
+        if a == b {
+            return Ok(());
+        }
+        let file_manager = &self.context().file_manager;
+        let a_path = self.tree_path(a);
+        let b_path = self.tree_path(b);
+        for (name, path) in [(a, &a_path), (b, &b_path)] {
+            if !file_manager.exists(path)? {
+                return Err(Error::from(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("tree {:?} does not exist", name),
+                )));
+            }
+        }
+
+        let mut states = Vec::with_capacity(2);
+        for (name, state) in [a, b]
+            .into_iter()
+            .zip(self.tree_states(&[Root::tree(a.to_string()), Root::tree(b.to_string())]))
+        {
+            let state = state
+                .as_ref()
+                .as_any()
+                .downcast_ref::<State<Root>>()
+                .cloned()
+                .ok_or_else(|| {
+                    Error::from(format!(
+                        "tree {:?} was opened with a different root type",
+                        name
+                    ))
+                })?;
+            states.push(state);
+        }
+        let b_state = states.pop().unwrap();
+        let a_state = states.pop().unwrap();
+
+        // Compactions rename the trees' files, so they must be prevented while
+        // the files are swapped. Locking in a consistent order prevents two
+        // concurrent swaps of the same trees from deadlocking.
+        let (_first_compaction, _second_compaction) = if a < b {
+            let first = a_state.lock_compaction();
+            (first, b_state.lock_compaction())
+        } else {
+            let first = b_state.lock_compaction();
+            (first, a_state.lock_compaction())
+        };
+        // Holding both trees' locks prevents transactions from accessing
+        // either tree while its files are renamed.
+        let _locks = self
+            .transactions()
+            .try_lock_trees([a.as_bytes(), b.as_bytes()])
+            .ok_or_else(|| {
+                Error::from(format!(
+                    "tree {:?} or {:?} is in use by a transaction",
+                    a, b
+                ))
+            })?;
+        // Both states must reflect the files' contents before they can be
+        // exchanged.
+        for (state, path) in [(&a_state, &a_path), (&b_state, &b_path)] {
+            if !state.read().initialized() {
+                TreeFile::<Root, File>::read(
+                    path,
+                    state.clone(),
+                    self.context(),
+                    Some(self.transactions()),
+                )?;
+            }
+        }
+
+        let _tree_states = self.data.tree_states.lock();
+        TreeFileSwap::new(file_manager, self.path(), &a_path, &b_path).run(a, b)?;
+        // File ids are preserved by renames, so each state remains consistent
+        // with the file now stored at its tree's path.
+        a_state.swap(&b_state);
+        Ok(())
+    }
+
+    /// Copies every entry of `from` into a new tree, `to`, and returns the new
+    /// tree. `from` is left unchanged. Changes made to `from` while it is
+    /// being copied may not be included in the copy.
+    ///
+    /// The entries are written within a single transaction. If copying fails,
+    /// the new tree is removed. Values are read using the vault of `from` and
+    /// written using the vault of `to`, and each value is re-indexed using the
+    /// reducer of `to`.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): The name of `to`
+    ///   contained an invalid character.
+    /// - [`Io`](ErrorKind::Io): A tree named `to` already exists, in which
+    ///   case the error's kind is
+    ///   [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+    pub fn copy_tree<Root: tree::Root>(
+        &self,
+        from: &Tree<Root, File>,
+        to: TreeRoot<Root, File>,
+    ) -> Result<Tree<Root, File>, Error> {
+        self.check_writable()?;
+        if self.tree_exists(&to.name)? {
+            return Err(Error::from(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("tree {:?} already exists", to.name),
+            )));
+        }
+
+        if let Err(err) = self.copy_into_tree(from, &to) {
+            // The copy's error is more useful than any error encountered while
+            // removing the incomplete tree.
+            drop(self.delete_tree(to.name.clone()));
+            return Err(err);
+        }
+        self.tree(to)
+    }
+
+    fn copy_into_tree<Root: tree::Root>(
+        &self,
+        from: &Tree<Root, File>,
+        to: &TreeRoot<Root, File>,
+    ) -> Result<(), Error> {
+        const BATCH_SIZE: usize = 1_000;
+
+        fn write_batch<Root: tree::Root, File: ManagedFile>(
+            tree: &mut TransactionTree<Root, File>,
+            batch: &mut Vec<(ArcBytes<'static>, ArcBytes<'static>)>,
+        ) -> Result<(), Error> {
+            if !batch.is_empty() {
+                // Values aren't guaranteed to be read in the order they were
+                // scanned, and a scan that is retried after a compaction reads
+                // entries again.
+                batch.sort_by(|a, b| a.0.cmp(&b.0));
+                batch.dedup_by(|a, b| a.0 == b.0);
+                let (keys, values) = batch.drain(..).unzip();
+                tree.modify(keys, Operation::SetEach(values))?;
+            }
+            Ok(())
+        }
+
+        let transaction = self.transaction(&[to.clone()])?;
+        {
+            let mut destination = transaction.tree::<Root>(0).unwrap();
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            from.scan::<Infallible, _, _, _, _>(
+                &(..),
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |_, _| ScanEvaluation::ReadData,
+                |key, _, value| {
+                    batch.push((key, value));
+                    if batch.len() == BATCH_SIZE {
+                        write_batch(&mut destination, &mut batch)?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(AbortError::infallible)?;
+            write_batch(&mut destination, &mut batch)?;
+        }
+        transaction.commit()
+    }
+
     /// Returns a list of all the names of trees contained in this database.
     pub fn tree_names(&self) -> Result<Vec<String>, Error> {
-        let mut names = Vec::new();
-        for entry in std::fs::read_dir(self.path())? {
-            let entry = entry?;
-            if let Some(name) = entry.file_name().to_str() {
-                if let Some(without_extension) = name.strip_suffix(".nebari") {
-                    names.push(without_extension.to_string());
+        tree_names(
+            &self.context().file_manager,
+            self.path(),
+            &self.data.file_extension,
+        )
+    }
+
+    /// Returns the trees that were repaired when this database was opened.
+    /// Trees are only repaired if enabled using
+    /// [`Config::repair_on_open()`].
+    #[must_use]
+    pub fn repairs(&self) -> &[TreeRepair] {
+        &self.data.repairs
+    }
+
+    /// Rewrites the transaction log, removing all entries older than the
+    /// oldest transaction that a tree in this database still depends on. The
+    /// rewritten log atomically replaces the existing log file.
+    ///
+    /// The trees considered are those returned by
+    /// [`tree_names()`](Self::tree_names) and any trees that have been opened
+    /// through this instance. Each tree's most recent committed root is
+    /// located to determine which transaction it depends on.
+    ///
+    /// If a tree uses a vault other than the one configured on this
+    /// database, it must be opened through this instance before calling this
+    /// function, and the function will return an error if the tree's roots
+    /// cannot be decrypted.
+    pub fn defragment_transaction_log(&self) -> Result<(), Error> {
+        self.check_writable()?;
+        let mut names = self.tree_names()?;
+        {
+            let tree_states = self.data.tree_states.lock();
+            for name in tree_states.keys() {
+                if !names.contains(name) {
+                    names.push(name.clone());
                 }
             }
         }
-        Ok(names)
+
+        let mut minimum_id = self
+            .transactions()
+            .current_transaction_id()
+            .unwrap_or_default();
+        for name in names {
+            if let Some(transaction_id) = tree::committed_transaction_id::<File>(
+                &self.tree_path(&name),
+                self.context(),
+                self.transactions(),
+            )? {
+                minimum_id = minimum_id.min(transaction_id);
+            }
+        }
+
+        self.transactions().defragment(minimum_id)
     }
 
     fn tree_state<Root: tree::Root>(&self, root: TreeRoot<Root, File>) -> State<Root> {
@@ -180,6 +744,10 @@ impl<File: ManagedFile> Roots<File> {
     /// accessible by the transaction. Dropping the executing transaction will
     /// roll the transaction back.
     ///
+    /// Trees of different [`Root`](tree::Root) types can be included in the
+    /// same transaction by passing a slice of `Box<dyn AnyTreeRoot<File>>`, or
+    /// more conveniently by using [`Self::transaction_builder()`].
+    ///
     /// ## Errors
     ///
     /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): A tree name contained
@@ -189,6 +757,7 @@ impl<File: ManagedFile> Roots<File> {
         &self,
         trees: &[R],
     ) -> Result<ExecutingTransaction<File>, Error> {
+        self.check_writable()?;
         for tree in trees {
             check_name(tree.borrow().name()).map(|_| tree.borrow().name().as_bytes())?;
         }
@@ -197,27 +766,330 @@ impl<File: ManagedFile> Roots<File> {
             .transactions
             .new_transaction(trees.iter().map(|t| t.borrow().name().as_bytes()));
         let states = self.tree_states(trees);
+        let tree_names = trees
+            .iter()
+            .map(|tree| tree.borrow().name().to_string())
+            .collect();
         let trees = trees
             .iter()
             .zip(states.into_iter())
             .map(|(tree, state)| {
-                tree.borrow()
-                    .begin_transaction(
-                        transaction.id,
-                        &self.tree_path(tree.borrow().name()),
-                        state.as_ref(),
-                        self.context(),
-                        Some(&self.data.transactions),
-                    )
-                    .map(UnlockedTransactionTree::new)
+                let path = self.tree_path(tree.borrow().name());
+                let mut tree = tree.borrow().begin_transaction(
+                    transaction.id,
+                    &path,
+                    state.as_ref(),
+                    self.context(),
+                    Some(&self.data.transactions),
+                )?;
+                if self.data.operation_log.is_some() {
+                    tree.record_operations();
+                }
+                Ok(UnlockedTransactionTree::new(
+                    tree,
+                    path,
+                    self.context().file_manager.clone(),
+                ))
             })
             .collect::<Result<Vec<_>, Error>>()?;
         Ok(ExecutingTransaction {
             roots: self.clone(),
             transaction: Some(transaction),
             trees,
+            tree_names,
+        })
+    }
+
+    /// Returns a builder for a transaction over trees that may have different
+    /// [`Root`](tree::Root) types.
+    ///
+    /// ```rust
+    /// # use nebari::{Config, tree::{Root, Versioned, Unversioned}};
+    /// # let tempdir = tempfile::tempdir().unwrap();
+    /// let roots = Config::new(tempdir.path()).open().unwrap();
+    /// let transaction = roots
+    ///     .transaction_builder()
+    ///     .add::<Versioned>("a")
+    ///     .add::<Unversioned>("b")
+    ///     .begin()
+    ///     .unwrap();
+    /// transaction
+    ///     .tree::<Versioned>(0)
+    ///     .unwrap()
+    ///     .set(b"key", b"value")
+    ///     .unwrap();
+    /// transaction
+    ///     .tree::<Unversioned>(1)
+    ///     .unwrap()
+    ///     .set(b"key", b"value")
+    ///     .unwrap();
+    /// transaction.commit().unwrap();
+    /// ```
+    pub fn transaction_builder(&self) -> TransactionBuilder<'_, File> {
+        TransactionBuilder {
+            roots: self,
+            trees: Vec::new(),
+        }
+    }
+
+    /// Captures the most recently committed state of each tree in `trees`.
+    /// Unlike [`transaction()`](Self::transaction), writers aren't blocked
+    /// while the returned [`Snapshot`] is in use. Reads through the snapshot
+    /// continue to see the data as it was when the snapshot was taken, and
+    /// each transaction's changes are either visible in every tree of the
+    /// snapshot or in none of them.
+    ///
+    /// ```rust
+    /// # use nebari::{Config, tree::{Root, Versioned}};
+    /// # let tempdir = tempfile::tempdir().unwrap();
+    /// let roots = Config::new(tempdir.path()).open().unwrap();
+    /// let tree = roots.tree(Versioned::tree("a")).unwrap();
+    /// tree.set(b"key", b"old").unwrap();
+    ///
+    /// let snapshot = roots.snapshot(&[Versioned::tree("a")]).unwrap();
+    /// tree.set(b"key", b"new").unwrap();
+    /// let snapshot_tree = snapshot.tree::<Versioned>(0).unwrap();
+    /// assert_eq!(snapshot_tree.get(b"key").unwrap().unwrap(), b"old");
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): A tree name contained
+    ///   an invalid character.
+    /// - [`Io`](ErrorKind::Io): A tree doesn't exist, in which case the error's
+    ///   kind is [`NotFound`](std::io::ErrorKind::NotFound).
+    pub fn snapshot<R: Borrow<T>, T: AnyTreeRoot<File> + ?Sized>(
+        &self,
+        trees: &[R],
+    ) -> Result<Snapshot<File>, Error> {
+        for tree in trees {
+            check_name(tree.borrow().name())?;
+        }
+        let states = self.tree_states(trees);
+        for (tree, state) in trees.iter().zip(&states) {
+            let tree = tree.borrow();
+            tree.initialize_state(
+                &self.tree_path(tree.name()),
+                state.as_ref(),
+                self.context(),
+                Some(&self.data.transactions),
+            )?;
+        }
+
+        let publishing = self.data.publish_lock.read();
+        let states = states
+            .iter()
+            .map(|state| state.snapshot())
+            .collect::<Vec<_>>();
+        drop(publishing);
+
+        let trees = trees
+            .iter()
+            .zip(states)
+            .map(|(tree, state)| SnapshotEntry {
+                name: tree.borrow().name().to_string(),
+                state,
+                vault: tree.borrow().vault().cloned(),
+            })
+            .collect();
+        Ok(Snapshot {
+            roots: self.clone(),
+            trees,
         })
     }
+
+    /// Sets each `(tree_name, key, value)` entry in `entries` within a single
+    /// transaction. All trees referenced are opened as `Root` trees with their
+    /// default reducer. If the same key is specified more than once for a
+    /// tree, the last value provided is stored.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): A tree name contained
+    ///   an invalid character. For a full list of valid characters, see the
+    ///   documentation on [`InvalidTreeName`](ErrorKind::InvalidTreeName).
+    #[allow(clippy::missing_panics_doc)]
+    pub fn multi_set<Root: tree::Root>(&self, entries: &[(&str, &[u8], &[u8])]) -> Result<(), Error>
+    where
+        Root::Reducer: Default,
+    {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut trees = Vec::<TreeRoot<Root, File>>::new();
+        let mut tree_entries = Vec::<BTreeMap<&[u8], &[u8]>>::new();
+        for (name, key, value) in entries {
+            let index = if let Some(index) = trees.iter().position(|tree| tree.name == *name) {
+                index
+            } else {
+                trees.push(Root::tree((*name).to_string()));
+                tree_entries.push(BTreeMap::new());
+                trees.len() - 1
+            };
+            tree_entries[index].insert(key, value);
+        }
+
+        let transaction = self.transaction(&trees)?;
+        for (index, entries) in tree_entries.into_iter().enumerate() {
+            let (keys, values) = entries
+                .into_iter()
+                .map(|(key, value)| (ArcBytes::from(key), ArcBytes::from(value.to_vec())))
+                .unzip();
+            transaction
+                .tree::<Root>(index)
+                .unwrap()
+                .modify(keys, Operation::SetEach(values))?;
+        }
+        transaction.commit()
+    }
+
+    /// Reads each entry of `read_tree` within `range` in ascending order and
+    /// invokes `apply` with the entry and a transaction over `write_trees`.
+    ///
+    /// Entries are read in batches of at most `batch_size` entries. Each batch
+    /// is read before its transaction begins, and the transaction is committed
+    /// once `apply` has been invoked for every entry in the batch. If `apply`
+    /// returns an error, the current batch's transaction is rolled back and the
+    /// error is returned; batches that were already committed remain committed.
+    /// Because each batch is read separately, changes made to `read_tree` by
+    /// other writers between batches may be observed.
+    ///
+    /// ## Errors
+    ///
+    /// - [`InvalidTreeName`](ErrorKind::InvalidTreeName): `write_trees`
+    ///   contains `read_tree`, or a tree name contained an invalid character.
+    pub fn scan_and_apply<'keys, ReadRoot, KeyRangeBounds, R, T, CallerError, Apply>(
+        &self,
+        read_tree: &Tree<ReadRoot, File>,
+        range: &'keys KeyRangeBounds,
+        write_trees: &[R],
+        batch_size: usize,
+        mut apply: Apply,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        ReadRoot: tree::Root,
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + ?Sized,
+        R: Borrow<T>,
+        T: AnyTreeRoot<File> + ?Sized,
+        CallerError: Display + Debug,
+        Apply: FnMut(
+            (ArcBytes<'static>, ArcBytes<'static>),
+            &ExecutingTransaction<File>,
+        ) -> Result<(), AbortError<CallerError>>,
+    {
+        if write_trees
+            .iter()
+            .any(|tree| tree.borrow().name() == read_tree.name())
+        {
+            return Err(AbortError::Nebari(Error::from(ErrorKind::InvalidTreeName)));
+        }
+
+        let batch_size = batch_size.max(1);
+        let end = range.end_bound().cloned();
+        let mut last_key: Option<ArcBytes<'static>> = None;
+        loop {
+            let start = match &last_key {
+                Some(key) => Bound::Excluded(key.as_slice()),
+                None => range.start_bound().cloned(),
+            };
+            let mut keys_read = 0;
+            let mut batch = Vec::with_capacity(batch_size);
+            read_tree.scan::<CallerError, _, _, _, _>(
+                &(start, end),
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |_, _| {
+                    if keys_read < batch_size {
+                        keys_read += 1;
+                        ScanEvaluation::ReadData
+                    } else {
+                        ScanEvaluation::Stop
+                    }
+                },
+                |key, _, value| {
+                    batch.push((key, value));
+                    Ok(())
+                },
+            )?;
+            if batch.is_empty() {
+                return Ok(());
+            }
+            // Values aren't guaranteed to be read in the order they were
+            // scanned.
+            batch.sort_by(|a, b| a.0.cmp(&b.0));
+            let next_key = batch.last().map(|(key, _)| key.clone());
+
+            let transaction = self.transaction(write_trees)?;
+            for entry in batch {
+                apply(entry, &transaction)?;
+            }
+            transaction.commit()?;
+
+            if keys_read < batch_size {
+                return Ok(());
+            }
+            last_key = next_key;
+        }
+    }
+
+    /// Schedules `tree` to be compacted on the database's compaction thread if
+    /// the bytes used by its live values have fallen below `ratio` of its
+    /// file's length.
+    fn auto_compact(&self, name: String, tree: Box<dyn AnyTransactionTree<File>>, ratio: f32) {
+        let file_length = match self
+            .context()
+            .file_manager
+            .file_length(self.tree_path(&name))
+        {
+            Ok(length) => length,
+            Err(_) => return,
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let needs_compaction = (tree.live_bytes() as f64) < f64::from(ratio) * file_length as f64;
+        if !needs_compaction {
+            return;
+        }
+
+        let mut auto_compactions = self.data.auto_compactions.lock();
+        let recently_compacted = auto_compactions
+            .compacted_lengths
+            .get(&name)
+            .map_or(false, |&compacted| {
+                file_length < compacted.saturating_mul(2)
+            });
+        if recently_compacted || !auto_compactions.scheduled.insert(name.clone()) {
+            return;
+        }
+
+        let roots = self.clone();
+        let job_name = name.clone();
+        let thread_pool = &self.data.thread_pool;
+        let worker = auto_compactions
+            .worker
+            .get_or_insert_with(|| thread_pool.spawn_worker("roots-compactor"));
+        let scheduled = worker.send(Box::new(move || {
+            let name = job_name;
+            let compacted =
+                tree.try_compact(&roots.context().file_manager, roots.transactions(), &name);
+            let mut auto_compactions = roots.data.auto_compactions.lock();
+            if matches!(compacted, Ok(true)) {
+                if let Ok(length) = roots
+                    .context()
+                    .file_manager
+                    .file_length(roots.tree_path(&name))
+                {
+                    auto_compactions
+                        .compacted_lengths
+                        .insert(name.clone(), length);
+                }
+            }
+            auto_compactions.scheduled.remove(&name);
+        }));
+        if scheduled.is_err() {
+            auto_compactions.scheduled.remove(&name);
+        }
+    }
 }
 
 fn check_name(name: &str) -> Result<(), Error> {
@@ -247,35 +1119,77 @@ pub struct ExecutingTransaction<File: ManagedFile> {
     roots: Roots<File>,
     trees: Vec<UnlockedTransactionTree<File>>,
     transaction: Option<ManagedTransaction<File::Manager>>,
+    tree_names: Vec<String>,
 }
 
 /// A tree that belongs to an [`ExecutingTransaction`].
 #[must_use]
-pub struct UnlockedTransactionTree<File: ManagedFile>(Mutex<Box<dyn AnyTransactionTree<File>>>);
+pub struct UnlockedTransactionTree<File: ManagedFile> {
+    tree: RwLock<Box<dyn AnyTransactionTree<File>>>,
+    path: PathBuf,
+    file_manager: File::Manager,
+}
 
 impl<File: ManagedFile> UnlockedTransactionTree<File> {
-    fn new(file: Box<dyn AnyTransactionTree<File>>) -> Self {
-        Self(Mutex::new(file))
+    fn new(
+        tree: Box<dyn AnyTransactionTree<File>>,
+        path: PathBuf,
+        file_manager: File::Manager,
+    ) -> Self {
+        Self {
+            tree: RwLock::new(tree),
+            path,
+            file_manager,
+        }
     }
 
-    /// Locks this tree so that operations can be performed against it.
+    /// Locks this tree so that operations can be performed against it. Waits
+    /// for all [`SharedTransactionTree`]s of this tree to be dropped.
     ///
     /// # Panics
     ///
     /// This function panics if `Root` does not match the type specified when
     /// starting the transaction.
     pub fn lock<Root: tree::Root>(&self) -> LockedTransactionTree<'_, Root, File> {
-        LockedTransactionTree(MutexGuard::map(self.0.lock(), |tree| {
+        LockedTransactionTree(RwLockWriteGuard::map(self.tree.write(), |tree| {
             tree.as_mut().as_any_mut().downcast_mut().unwrap()
         }))
     }
-}
+
+    /// Acquires shared read access to this tree. Any number of
+    /// [`SharedTransactionTree`]s can exist at once, and each can be read
+    /// from concurrently. Waits for any [`LockedTransactionTree`] of this tree
+    /// to be dropped.
+    ///
+    /// The returned tree reads the changes made within this transaction as
+    /// they were when this function was called. Because the tree can't be
+    /// modified until the returned tree is dropped, its contents don't change.
+    /// Calling [`lock()`](Self::lock) on the same thread while the returned
+    /// tree exists deadlocks.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `Root` does not match the type specified when
+    /// starting the transaction.
+    pub fn read<Root: tree::Root>(&self) -> SharedTransactionTree<'_, Root, File> {
+        let tree = RwLockReadGuard::map(self.tree.read(), |tree| {
+            tree.as_ref().as_any().downcast_ref().unwrap()
+        });
+        let state = tree.tree.state.detached_uncommitted();
+        SharedTransactionTree {
+            tree,
+            state,
+            path: &self.path,
+            file_manager: &self.file_manager,
+        }
+    }
+}
 
 /// A locked transaction tree. This transactional tree is exclusively available
 /// for writing and reading to the thread that locks it.
 #[must_use]
 pub struct LockedTransactionTree<'transaction, Root: tree::Root, File: ManagedFile>(
-    MappedMutexGuard<'transaction, TransactionTree<Root, File>>,
+    MappedRwLockWriteGuard<'transaction, TransactionTree<Root, File>>,
 );
 
 impl<'transaction, Root: tree::Root, File: ManagedFile> Deref
@@ -296,6 +1210,80 @@ impl<'transaction, Root: tree::Root, File: ManagedFile> DerefMut
     }
 }
 
+/// A transaction tree that is shared for reading. Returned from
+/// [`UnlockedTransactionTree::read()`].
+///
+/// Each read opens its own handle to the tree's file, which allows reads to
+/// be performed concurrently from multiple threads. Reads reflect the changes
+/// made within the transaction before this tree was acquired.
+#[must_use]
+pub struct SharedTransactionTree<'transaction, Root: tree::Root, File: ManagedFile> {
+    tree: MappedRwLockReadGuard<'transaction, TransactionTree<Root, File>>,
+    state: State<Root>,
+    path: &'transaction Path,
+    file_manager: &'transaction File::Manager,
+}
+
+impl<'transaction, Root: tree::Root, File: ManagedFile>
+    SharedTransactionTree<'transaction, Root, File>
+{
+    fn open_for_read(&self) -> Result<TreeFile<Root, File>, Error> {
+        self.tree
+            .tree
+            .open_reader(self.file_manager, self.path, self.state.clone())
+    }
+
+    /// Returns the id of the transaction this tree belongs to.
+    #[must_use]
+    pub fn transaction_id(&self) -> TransactionId {
+        self.tree.transaction_id
+    }
+
+    /// Retrieves the value of `key`, if present.
+    pub fn get(&self, key: &[u8]) -> Result<Option<ArcBytes<'static>>, Error> {
+        self.open_for_read()?.get(key, false)
+    }
+
+    /// Returns true if `key` has a stored value.
+    pub fn contains_key(&self, key: &[u8]) -> Result<bool, Error> {
+        self.open_for_read()?.contains_key(key, false)
+    }
+
+    /// Retrieves the values of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Keys are required to be pre-sorted.
+    pub fn get_multiple<'keys, KeysIntoIter, KeysIter>(
+        &self,
+        keys: KeysIntoIter,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        KeysIntoIter: IntoIterator<Item = &'keys [u8], IntoIter = KeysIter>,
+        KeysIter: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
+    {
+        self.open_for_read()?.get_multiple(keys, false)
+    }
+
+    /// Retrieves all of the values of keys within `range`.
+    pub fn get_range<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        self.open_for_read()?.get_range(range, false)
+    }
+
+    /// Returns the first key and value of the tree.
+    pub fn first(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.open_for_read()?.first(false)
+    }
+
+    /// Returns the last key and value of the tree.
+    pub fn last(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.open_for_read()?.last(false)
+    }
+}
+
 impl<File: ManagedFile> ExecutingTransaction<File> {
     /// Returns the [`LogEntry`] for this transaction.
     #[must_use]
@@ -322,22 +1310,60 @@ impl<File: ManagedFile> ExecutingTransaction<File> {
     /// well as impervious to sudden failures such as a power outage.
     #[allow(clippy::missing_panics_doc)]
     pub fn commit(mut self) -> Result<(), Error> {
+        if let Some(min_free_space) = self.roots.data.min_free_space {
+            let available = self
+                .roots
+                .context()
+                .file_manager
+                .available_space(self.roots.path())?;
+            if available.map_or(false, |available| available < min_free_space) {
+                return Err(Error::from(ErrorKind::OutOfSpace));
+            }
+        }
+
         let trees = std::mem::take(&mut self.trees);
+        // Gather the operations to log before the trees are committed, as the
+        // trees may be committed out of order.
+        let logged_operations = self
+            .tree_names
+            .iter()
+            .zip(&trees)
+            .map(|(name, tree)| (name, tree.tree.write().take_recorded_operations()))
+            .collect::<Vec<_>>();
         // Write the trees to disk
+        let transaction_id = self.transaction.as_ref().unwrap().id;
         let trees = self.roots.data.thread_pool.commit_trees(trees)?;
 
         // Push the transaction to the log.
         let transaction = self.transaction.take().unwrap();
         let tree_locks = transaction.commit()?;
 
+        if let Some(operation_log) = &self.roots.data.operation_log {
+            for (name, operations) in logged_operations {
+                operation_log.record(transaction_id, name, operations);
+            }
+        }
+
         // Publish the tree states, now that the transaction has been fully recorded
-        for tree in trees {
+        let publishing = self.roots.data.publish_lock.write();
+        for tree in &trees {
             tree.state().publish();
         }
+        drop(publishing);
+
+        self.roots
+            .notify_subscribers(transaction_id, &self.tree_names);
 
         // Release the locks for the trees, allowing a new transaction to begin.
         drop(tree_locks);
 
+        if let CompactionPolicy::Ratio(ratio) = self.roots.data.compaction_policy {
+            let names = std::mem::take(&mut self.tree_names);
+            for (name, tree) in names.into_iter().zip(trees) {
+                self.roots.auto_compact(name, tree, ratio);
+            }
+        }
+
         Ok(())
     }
 
@@ -365,7 +1391,7 @@ impl<File: ManagedFile> ExecutingTransaction<File> {
 
     fn rollback_tree_states(&mut self) {
         for tree in self.trees.drain(..) {
-            let tree = tree.0.lock();
+            let tree = tree.tree.write();
             tree.rollback();
         }
     }
@@ -395,6 +1421,17 @@ pub trait AnyTransactionTree<File: ManagedFile>: Any + Send + Sync {
 
     fn commit(&mut self) -> Result<(), Error>;
     fn rollback(&self);
+
+    fn record_operations(&mut self);
+    fn take_recorded_operations(&mut self) -> Vec<(ArcBytes<'static>, OperationKind)>;
+
+    fn live_bytes(&self) -> u64;
+    fn try_compact(
+        self: Box<Self>,
+        file_manager: &File::Manager,
+        transactions: &TransactionManager<File::Manager>,
+        name: &str,
+    ) -> Result<bool, Error>;
 }
 
 impl<Root: tree::Root, File: ManagedFile> AnyTransactionTree<File> for TransactionTree<Root, File> {
@@ -417,6 +1454,28 @@ impl<Root: tree::Root, File: ManagedFile> AnyTransactionTree<File> for Transacti
         let mut state = self.tree.state.lock();
         state.rollback(&self.tree.state);
     }
+
+    fn record_operations(&mut self) {
+        self.tree.record_operations();
+    }
+
+    fn take_recorded_operations(&mut self) -> Vec<(ArcBytes<'static>, OperationKind)> {
+        self.tree.take_recorded_operations()
+    }
+
+    fn live_bytes(&self) -> u64 {
+        self.tree.state.read().root.live_bytes()
+    }
+
+    fn try_compact(
+        self: Box<Self>,
+        file_manager: &File::Manager,
+        transactions: &TransactionManager<File::Manager>,
+        name: &str,
+    ) -> Result<bool, Error> {
+        let compacted = self.tree.try_compact(file_manager, transactions, name)?;
+        Ok(compacted.is_some())
+    }
 }
 
 impl<File: ManagedFile, Index> TransactionTree<VersionedTreeRoot<Index>, File>
@@ -430,6 +1489,12 @@ where
         state.root.sequence
     }
 
+    /// Removes every key from the tree and resets its sequence. See
+    /// [`TreeFile::clear_and_reset_sequence()`] for more information.
+    pub fn clear_and_reset_sequence(&mut self) -> Result<(), Error> {
+        self.tree.clear_and_reset_sequence(self.transaction_id)
+    }
+
     /// Scans the tree for keys that are contained within `range`. If `forwards`
     /// is true, scanning starts at the lowest sort-order key and scans forward.
     /// Otherwise, scanning starts at the highest sort-order key and scans
@@ -455,6 +1520,44 @@ where
             .scan_sequences(range, forwards, true, key_evaluator, data_callback)
     }
 
+    /// Scans the sequences recorded after `sequence` in ascending order,
+    /// excluding `sequence` itself. See [`TreeFile::changes_after()`] for more
+    /// information. This will reflect changes made within this transaction.
+    pub fn changes_after<CallerError, KeyEvaluator, DataCallback>(
+        &mut self,
+        sequence: SequenceId,
+        key_evaluator: &mut KeyEvaluator,
+        data_callback: &mut DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyEvaluator: FnMut(KeySequence<Index>) -> ScanEvaluation,
+        DataCallback:
+            FnMut(KeySequence<Index>, ArcBytes<'static>) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.tree
+            .changes_after(sequence, true, key_evaluator, data_callback)
+    }
+
+    /// Invokes `callback` for each change recorded after `sequence`, in
+    /// ascending order, including removals. See
+    /// [`TreeFile::export_changes_after()`] for more information. This will
+    /// reflect changes made within this transaction.
+    pub fn export_changes_after<CallerError, Callback>(
+        &mut self,
+        sequence: SequenceId,
+        callback: &mut Callback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        Callback: FnMut(
+            KeySequence<Index>,
+            Option<ArcBytes<'static>>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.tree.export_changes_after(sequence, true, callback)
+    }
+
     /// Retrieves the keys and values associated with one or more `sequences`.
     /// The value retrieved is the value of the key at the given [`SequenceId`].
     /// If a sequence is not found, it will not appear in the result map. If
@@ -496,9 +1599,155 @@ where
         self.tree
             .get_multiple_with_indexes_by_sequence(sequences, true)
     }
+
+    /// Compares the [`SequenceId`] of the latest version of `key` against
+    /// `expected`, where `None` indicates that the key must not have a value.
+    /// If the sequences match, key will be set to the new value if `new` is
+    /// `Some` or removed if `new` is `None`.
+    pub fn compare_and_swap_sequence(
+        &mut self,
+        key: &[u8],
+        expected: Option<SequenceId>,
+        new: Option<ArcBytes<'_>>,
+    ) -> Result<(), CompareAndSwapSequenceError> {
+        self.tree
+            .compare_and_swap_sequence(key, expected, new, self.transaction_id)
+    }
+
+    /// Returns the current value of `key` along with the [`SequenceId`] of
+    /// its latest version. This will return updated information if it has
+    /// been previously updated within this transaction.
+    pub fn get_with_sequence(
+        &mut self,
+        key: &[u8],
+    ) -> Result<Option<(ArcBytes<'static>, SequenceId)>, Error> {
+        self.tree.get_with_sequence(key, true)
+    }
+}
+
+impl<Root, File, EmbeddedStats> TransactionTree<Root, File>
+where
+    Root: tree::Root<ReducedIndex = ByIdStats<EmbeddedStats>>,
+    File: ManagedFile,
+{
+    /// Returns an estimate of the number of keys contained within `range`.
+    ///
+    /// Only the nodes along the boundaries of `range` are visited, making this
+    /// much cheaper than counting the keys. Partially contained leaf nodes are
+    /// estimated by interpolating within their key space, which bounds the
+    /// error of the estimate to twice the tree's order (at most 2,000 keys by
+    /// default). See [`TreeFile::estimate_range_size()`] for more information.
+    /// This will reflect changes made within this transaction.
+    pub fn estimate_range_size<'keys, KeyRangeBounds>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        self.tree.estimate_range_size(range, true)
+    }
+
+    /// Returns the number of keys contained within `range` without reading
+    /// any values. See [`TreeFile::count_range()`] for more information. This
+    /// will reflect changes made within this transaction.
+    pub fn count_range<'keys, KeyRangeBounds>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        self.tree.count_range(range, true)
+    }
+
+    /// Counts the keys contained within `range`, grouped by the value
+    /// `group_fn` returns for each key. `group_fn` must map keys to groups
+    /// that are contiguous in key order, such as a key's prefix. See
+    /// [`TreeFile::scan_keys_reduce()`] for more information. This will
+    /// reflect changes made within this transaction.
+    pub fn scan_keys_reduce<'keys, KeyRangeBounds, G, GroupFn>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        group_fn: GroupFn,
+    ) -> Result<HashMap<G, u64>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        G: Eq + Hash,
+        GroupFn: Fn(&[u8]) -> G,
+    {
+        self.tree.scan_keys_reduce(range, group_fn, true)
+    }
+
+    /// Scans the tree across all nodes that might contain nodes within
+    /// `range`, reporting the approximate progress of the scan to `progress`.
+    /// See [`TreeFile::scan_with_progress()`] for more information. This will
+    /// reflect changes made within this transaction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_with_progress<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        KeyEvaluator,
+        DataCallback,
+        Progress,
+    >(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+        mut progress: Progress,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+        Progress: FnMut(ScanProgress),
+    {
+        self.tree.scan_with_progress(
+            range,
+            forwards,
+            true,
+            &mut node_evaluator,
+            &mut key_evaluator,
+            &mut callback,
+            &mut progress,
+        )
+    }
+
+    /// Retrieves all of the values of keys within `range`, reporting the
+    /// approximate progress of the scan to `progress`. See
+    /// [`TreeFile::scan_with_progress()`] for more information.
+    pub fn get_range_with_progress<'keys, KeyRangeBounds, Progress>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        progress: Progress,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        Progress: FnMut(ScanProgress),
+    {
+        self.tree.get_range_with_progress(range, true, progress)
+    }
 }
 
 impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
+    /// Returns true if the tree contains no keys, not including deleted keys.
+    /// This will reflect changes made within this transaction.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty(true)
+    }
+
     /// Sets `key` to `value`. Returns the newly created index for this key.
     pub fn set(
         &mut self,
@@ -525,6 +1774,21 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         })
     }
 
+    /// Executes a modification. Returns a list of all changed keys along with
+    /// their values and indexes from before the modification. See
+    /// [`TreeFile::modify_with_previous()`] for more information.
+    pub fn modify_with_previous<'a>(
+        &mut self,
+        keys: Vec<ArcBytes<'a>>,
+        operation: Operation<'a, ArcBytes<'static>, Root::Index>,
+    ) -> Result<Vec<ModificationResultWithPrevious<Root::Index>>, Error> {
+        self.tree.modify_with_previous(Modification {
+            keys,
+            persistence_mode: PersistenceMode::Transactional(self.transaction_id),
+            operation,
+        })
+    }
+
     /// Sets `key` to `value`. Returns a tuple containing two elements:
     ///
     /// - The previously stored value, if a value was already present.
@@ -537,6 +1801,43 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.replace(key, value, self.transaction_id)
     }
 
+    /// Returns the value stored for `key`. If `key` has no value, `f` is
+    /// invoked and the value it returns is stored. See
+    /// [`TreeFile::get_or_insert_with()`] for more information.
+    ///
+    /// Returns the stored value and its index.
+    pub fn get_or_insert_with<F: FnOnce() -> ArcBytes<'static>>(
+        &mut self,
+        key: impl Into<ArcBytes<'static>>,
+        f: F,
+    ) -> Result<(ArcBytes<'static>, Root::Index), Error> {
+        self.tree.get_or_insert_with(key, self.transaction_id, f)
+    }
+
+    /// Adds `delta` to the counter stored in `key`, returning the new total.
+    /// See [`TreeFile::increment()`] for more information. Incrementing
+    /// several counters within one transaction updates them atomically.
+    pub fn increment(
+        &mut self,
+        key: impl Into<ArcBytes<'static>>,
+        delta: i64,
+    ) -> Result<i64, Error> {
+        self.tree.increment(key, delta, self.transaction_id)
+    }
+
+    /// Sets `key` to `value`. Returns a tuple containing two elements:
+    ///
+    /// - The previously stored index, if a value was already present.
+    /// - The new/updated index for this key.
+    pub fn set_returning_previous_index(
+        &mut self,
+        key: impl Into<ArcBytes<'static>>,
+        value: impl Into<ArcBytes<'static>>,
+    ) -> Result<(Option<Root::Index>, Root::Index), Error> {
+        self.tree
+            .set_returning_previous_index(key, value, self.transaction_id)
+    }
+
     /// Returns the current value of `key`. This will return updated information
     /// if it has been previously updated within this transaction.
     pub fn get(&mut self, key: &[u8]) -> Result<Option<ArcBytes<'static>>, Error> {
@@ -549,6 +1850,12 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.get_index(key, true)
     }
 
+    /// Returns true if a value is stored for `key`. This will return updated
+    /// information if it has been previously updated within this transaction.
+    pub fn contains_key(&mut self, key: &[u8]) -> Result<bool, Error> {
+        self.tree.contains_key(key, true)
+    }
+
     /// Returns the current value and index of `key`. This will return updated
     /// information if it has been previously updated within this transaction.
     pub fn get_with_index(
@@ -558,6 +1865,37 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.get_with_index(key, true)
     }
 
+    /// Returns the user-defined metadata stored alongside this tree, if set.
+    /// This will return updated information if it has been previously updated
+    /// within this transaction.
+    #[must_use]
+    pub fn metadata(&self) -> Option<ArcBytes<'static>> {
+        self.tree.metadata(true)
+    }
+
+    /// Replaces the user-defined metadata stored alongside this tree. Metadata
+    /// is stored separately from the tree's keys, and can be at most 65,535
+    /// bytes long. Passing `None` removes any stored metadata.
+    pub fn set_metadata(&mut self, metadata: Option<ArcBytes<'_>>) -> Result<(), Error> {
+        self.tree.set_metadata(metadata, self.transaction_id)
+    }
+
+    /// Removes every key from the tree. See [`TreeFile::clear()`] for more
+    /// information.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.tree.clear(self.transaction_id)
+    }
+
+    /// Applies all operations in `batch`. If any compare-and-swap condition in
+    /// the batch isn't met, no changes are made. Returns the updated indexes of
+    /// the modified keys.
+    pub fn apply_batch(
+        &mut self,
+        batch: Batch<'_>,
+    ) -> Result<Vec<ModificationResult<Root::Index>>, CompareAndSwapError> {
+        self.tree.apply_batch(batch, self.transaction_id)
+    }
+
     /// Removes `key` and returns the existing value amd index, if present.
     pub fn remove(
         &mut self,
@@ -566,6 +1904,29 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.remove(key, self.transaction_id)
     }
 
+    /// Removes `keys`, which must be sorted in ascending order without
+    /// duplicates. Runs of consecutive keys are removed together, making this
+    /// faster than removing keys individually. See
+    /// [`TreeFile::bulk_remove_sorted()`] for more information.
+    pub fn bulk_remove_sorted(
+        &mut self,
+        keys: Vec<ArcBytes<'_>>,
+    ) -> Result<Vec<ModificationResult<Root::Index>>, Error> {
+        self.tree.bulk_remove_sorted(keys, self.transaction_id)
+    }
+
+    /// Removes every key contained by `range`, returning the number of keys
+    /// removed. See [`TreeFile::remove_range()`] for more information.
+    pub fn remove_range<'keys, KeyRangeBounds>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        self.tree.remove_range(range, self.transaction_id)
+    }
+
     /// Compares the value of `key` against `old`. If the values match, key will
     /// be set to the new value if `new` is `Some` or removed if `new` is
     /// `None`.
@@ -580,7 +1941,9 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
     }
 
     /// Retrieves the values of `keys`. If any keys are not found, they will be
-    /// omitted from the results. Keys are required to be pre-sorted.
+    /// omitted from the results. Keys are required to be pre-sorted. Use
+    /// [`Self::get_multiple_unsorted()`] to retrieve the values in the same
+    /// order as `keys`.
     pub fn get_multiple<'keys, KeysIntoIter, KeysIter>(
         &mut self,
         keys: KeysIntoIter,
@@ -592,6 +1955,47 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.get_multiple(keys, true)
     }
 
+    /// Retrieves the values of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Unlike
+    /// [`get_multiple()`](Self::get_multiple), keys do not need to be sorted.
+    /// See [`TreeFile::get_set()`] for more information.
+    pub fn get_set<'keys, Keys>(
+        &mut self,
+        keys: Keys,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        Keys: IntoIterator<Item = &'keys [u8]>,
+    {
+        self.tree.get_set(keys, true)
+    }
+
+    /// Retrieves the values of `keys`, which do not need to be sorted or
+    /// unique. Returns one entry for each key in `keys`, in the same order,
+    /// containing the key's value or `None` if the key wasn't found.
+    pub fn get_multiple_unsorted<'keys, Keys>(
+        &mut self,
+        keys: Keys,
+    ) -> Result<Vec<Option<ArcBytes<'static>>>, Error>
+    where
+        Keys: IntoIterator<Item = &'keys [u8]>,
+    {
+        self.tree.get_multiple_unsorted(keys, true)
+    }
+
+    /// Retrieves the values of `keys`, which do not need to be sorted or
+    /// unique. Returns one key/value pair for each key in `keys`, in the same
+    /// order, using `default` as the value of each key that wasn't found.
+    pub fn get_multiple_or<'keys, Keys>(
+        &mut self,
+        keys: Keys,
+        default: &ArcBytes<'static>,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        Keys: IntoIterator<Item = &'keys [u8]>,
+    {
+        self.tree.get_multiple_or(keys, default, true)
+    }
+
     /// Retrieves the indexes of `keys`. If any keys are not found, they will be
     /// omitted from the results. Keys are required to be pre-sorted.
     pub fn get_multiple_indexes<'keys, KeysIntoIter, KeysIter>(
@@ -630,11 +2034,47 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.get_range(range, true)
     }
 
-    /// Retrieves all of the indexes of keys within `range`.
-    pub fn get_range_indexes<'keys, KeyRangeBounds>(
+    /// Retrieves a page of up to `limit` keys and values within `range`,
+    /// starting after `after` if provided. See [`TreeFile::get_page()`] for
+    /// more information. This will reflect changes made within this
+    /// transaction.
+    pub fn get_page<'keys, KeyRangeBounds>(
         &mut self,
         range: &'keys KeyRangeBounds,
-    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
+        after: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<
+        (
+            Vec<(ArcBytes<'static>, ArcBytes<'static>)>,
+            Option<ScanCursor>,
+        ),
+        Error,
+    >
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        self.tree.get_page(range, after, limit, true)
+    }
+
+    /// Returns an iterator over the keys and values within `range`. See
+    /// [`TreeFile::range_iter()`] for more information. This will reflect
+    /// changes made within this transaction.
+    pub fn range_iter<'keys, KeyRangeBounds>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+    ) -> RangeIter<Root, File, &mut TreeFile<Root, File>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + ?Sized,
+    {
+        self.tree.range_iter(range, forwards, true)
+    }
+
+    /// Retrieves all of the indexes of keys within `range`.
+    pub fn get_range_indexes<'keys, KeyRangeBounds>(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
     where
         KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
     {
@@ -673,6 +2113,11 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
     /// within `range`. For all [`ScanEvaluation::ReadData`] results returned,
     /// `callback` will be invoked with the key and values. `callback` may not
     /// be invoked in the same order as the keys are scanned.
+    ///
+    /// Cloning a key in `key_evaluator` only increments a reference count.
+    /// Unless the key's leaf node was written with [key
+    /// compression](Config::key_compression), the key also shares the buffer
+    /// the node was loaded from rather than being copied.
     #[cfg_attr(
         feature = "tracing",
         tracing::instrument(skip(self, node_evaluator, key_evaluator, callback))
@@ -706,6 +2151,118 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         )
     }
 
+    /// Scans the tree for keys that start with `prefix`. See
+    /// [`TreeFile::scan_prefix()`] for more information. This will reflect
+    /// changes made within this transaction.
+    pub fn scan_prefix<CallerError, NodeEvaluator, KeyEvaluator, DataCallback>(
+        &mut self,
+        prefix: &[u8],
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.tree.scan_prefix(
+            prefix,
+            forwards,
+            true,
+            &mut node_evaluator,
+            &mut key_evaluator,
+            &mut callback,
+        )
+    }
+
+    /// Scans `range`, reading the values of only the keys whose index matches
+    /// `predicate`. See [`TreeFile::scan_index_filter()`] for more
+    /// information. This will reflect changes made within this transaction.
+    pub fn scan_index_filter<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        Predicate,
+        DataCallback,
+    >(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut predicate: Predicate,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        Predicate: FnMut(&Root::Index) -> bool,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.tree.scan_index_filter(
+            range,
+            forwards,
+            true,
+            &mut node_evaluator,
+            &mut predicate,
+            &mut callback,
+        )
+    }
+
+    /// Scans the tree like [`Self::scan()`], stopping with
+    /// [`ErrorKind::Cancelled`] once `cancellation` is cancelled. See
+    /// [`TreeFile::scan_cancellable()`] for more information.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_cancellable<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        KeyEvaluator,
+        DataCallback,
+    >(
+        &mut self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        cancellation: &CancellationToken,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.tree.scan_cancellable(
+            range,
+            forwards,
+            true,
+            cancellation,
+            &mut node_evaluator,
+            &mut key_evaluator,
+            &mut callback,
+        )
+    }
+
     /// Returns the reduced index over the provided range. This is an
     /// aggregation function that builds atop the `scan()` operation which calls
     /// [`Reducer::reduce()`](crate::tree::Reducer::reduce) and
@@ -727,6 +2284,20 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
         self.tree.reduce(range, true)
     }
 
+    /// Returns the reduced index over each range in `ranges`, scanning the
+    /// tree once. `ranges` must be sorted and must not overlap. See
+    /// [`TreeFile::reduce_multiple()`] for more information.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn reduce_multiple<'keys, KeyRangeBounds>(
+        &mut self,
+        ranges: &'keys [KeyRangeBounds],
+    ) -> Result<Vec<Option<Root::ReducedIndex>>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug,
+    {
+        self.tree.reduce_multiple(ranges, true)
+    }
+
     /// Returns the first key of the tree.
     #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub fn first_key(&mut self) -> Result<Option<ArcBytes<'static>>, Error> {
@@ -750,6 +2321,26 @@ impl<Root: tree::Root, File: ManagedFile> TransactionTree<Root, File> {
     pub fn last(&mut self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
         self.tree.last(true)
     }
+
+    /// Returns up to `n` of the tree's first keys and values, in ascending
+    /// order.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn first_n(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.tree.first_n(n, true)
+    }
+
+    /// Returns up to `n` of the tree's last keys and values, in descending
+    /// order.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn last_n(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.tree.last_n(n, true)
+    }
 }
 
 /// An error returned from `compare_and_swap()`.
@@ -763,6 +2354,60 @@ pub enum CompareAndSwapError {
     Error(#[from] Error),
 }
 
+/// An error returned from `compare_and_swap_sequence()`.
+#[derive(Debug, thiserror::Error)]
+pub enum CompareAndSwapSequenceError {
+    /// The sequence of the stored value did not match the expected sequence.
+    #[error("sequence did not match. existing sequence: {0:?}")]
+    Conflict(Option<SequenceId>),
+    /// Another error occurred while executing the operation.
+    #[error("error during compare_and_swap_sequence: {0}")]
+    Error(#[from] Error),
+}
+
+/// Executes `func`, retrying it while it returns
+/// [`CompareAndSwapError::Conflict`]. `func` is executed at most
+/// `max_attempts` times, and is always executed at least once. If `backoff` is
+/// provided, the current thread sleeps for the duration before each retry, and
+/// the duration is doubled after each retry.
+///
+/// [`CompareAndSwapError::Error`] is returned immediately without retrying. If
+/// every attempt conflicts, the last conflict is returned.
+///
+/// ```rust
+/// # use nebari::{retry_on_conflict, tree::{Root, Unversioned}, ArcBytes, Config};
+/// # let tempdir = tempfile::tempdir().unwrap();
+/// let roots = Config::new(tempdir.path()).open().unwrap();
+/// let tree = roots.tree(Unversioned::tree("counters")).unwrap();
+/// retry_on_conflict(10, None, || {
+///     let current = tree.get(b"counter")?;
+///     let next = current.as_ref().map_or(0, |value| value[0]) + 1;
+///     let next = ArcBytes::from(vec![next]);
+///     tree.compare_and_swap(b"counter", current.as_deref(), Some(next))
+/// })
+/// .unwrap();
+/// assert_eq!(tree.get(b"counter").unwrap().unwrap(), b"\x01");
+/// ```
+pub fn retry_on_conflict<R, F: FnMut() -> Result<R, CompareAndSwapError>>(
+    max_attempts: usize,
+    mut backoff: Option<Duration>,
+    mut func: F,
+) -> Result<R, CompareAndSwapError> {
+    let mut attempts = 1;
+    loop {
+        match func() {
+            Err(CompareAndSwapError::Conflict(_)) if attempts < max_attempts => {
+                attempts += 1;
+                if let Some(delay) = &mut backoff {
+                    std::thread::sleep(*delay);
+                    *delay = delay.saturating_mul(2);
+                }
+            }
+            other => return other,
+        }
+    }
+}
+
 /// A database configuration used to open a database.
 #[derive(Debug)]
 #[must_use]
@@ -772,6 +2417,19 @@ pub struct Config<M: FileManager = StdFileManager> {
     cache: Option<ChunkCache>,
     file_manager: Option<M>,
     thread_pool: Option<ThreadPool<M::File>>,
+    clock: Option<Arc<dyn Clock>>,
+    operation_log: Option<usize>,
+    file_extension: Option<String>,
+    thread_spawner: Option<ThreadSpawner>,
+    repair_policy: RepairPolicy,
+    min_free_space: Option<u64>,
+    recovery_handler: Option<RecoveryHandler>,
+    page_size: Option<usize>,
+    write_buffer_size: Option<usize>,
+    key_compression: bool,
+    compaction_policy: CompactionPolicy,
+    read_only: bool,
+    synchronize_transaction_log: bool,
 }
 
 impl<M: FileManager> Clone for Config<M> {
@@ -782,6 +2440,19 @@ impl<M: FileManager> Clone for Config<M> {
             cache: self.cache.clone(),
             file_manager: self.file_manager.clone(),
             thread_pool: self.thread_pool.clone(),
+            clock: self.clock.clone(),
+            operation_log: self.operation_log,
+            file_extension: self.file_extension.clone(),
+            thread_spawner: self.thread_spawner.clone(),
+            repair_policy: self.repair_policy,
+            min_free_space: self.min_free_space,
+            recovery_handler: self.recovery_handler.clone(),
+            page_size: self.page_size,
+            write_buffer_size: self.write_buffer_size,
+            key_compression: self.key_compression,
+            compaction_policy: self.compaction_policy,
+            read_only: self.read_only,
+            synchronize_transaction_log: self.synchronize_transaction_log,
         }
     }
 }
@@ -795,6 +2466,19 @@ impl Config<StdFileManager> {
             cache: None,
             thread_pool: None,
             file_manager: None,
+            clock: None,
+            operation_log: None,
+            file_extension: None,
+            thread_spawner: None,
+            repair_policy: RepairPolicy::default(),
+            min_free_space: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
+            compaction_policy: CompactionPolicy::default(),
+            read_only: false,
+            synchronize_transaction_log: true,
         }
     }
 
@@ -806,6 +2490,19 @@ impl Config<StdFileManager> {
             cache: Some(ChunkCache::new(2000, 65536)),
             thread_pool: Some(ThreadPool::default()),
             file_manager: None,
+            clock: None,
+            operation_log: None,
+            file_extension: None,
+            thread_spawner: None,
+            repair_policy: RepairPolicy::default(),
+            min_free_space: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
+            compaction_policy: CompactionPolicy::default(),
+            read_only: false,
+            synchronize_transaction_log: true,
         }
     }
 
@@ -822,6 +2519,19 @@ impl Config<StdFileManager> {
             cache: self.cache,
             file_manager: Some(file_manager),
             thread_pool: None,
+            clock: self.clock,
+            operation_log: self.operation_log,
+            file_extension: self.file_extension,
+            thread_spawner: self.thread_spawner,
+            repair_policy: self.repair_policy,
+            min_free_space: self.min_free_space,
+            recovery_handler: self.recovery_handler,
+            page_size: self.page_size,
+            write_buffer_size: self.write_buffer_size,
+            key_compression: self.key_compression,
+            compaction_policy: self.compaction_policy,
+            read_only: self.read_only,
+            synchronize_transaction_log: self.synchronize_transaction_log,
         }
     }
 }
@@ -847,272 +2557,524 @@ impl<M: FileManager> Config<M> {
         self
     }
 
-    /// Opens the database, or creates one if the target path doesn't exist.
-    pub fn open(self) -> Result<Roots<M::File>, Error> {
-        Roots::open(
-            self.path,
-            Context {
-                file_manager: self.file_manager.unwrap_or_default(),
-                vault: self.vault,
-                cache: self.cache,
-            },
-            self.thread_pool.unwrap_or_default(),
-        )
+    /// Spawns the threads used to commit transactions using `spawner`. See
+    /// [`ThreadPool::with_spawner()`] for more information.
+    ///
+    /// If a [shared thread pool](Self::shared_thread_pool) is used, threads
+    /// spawned while committing this database's transactions use `spawner`.
+    pub fn thread_pool_spawn_hook<Spawner>(mut self, spawner: Spawner) -> Self
+    where
+        Spawner: Fn(Box<dyn FnOnce() + Send>) + Send + Sync + 'static,
+    {
+        self.thread_spawner = Some(ThreadSpawner(Arc::new(spawner)));
+        self
     }
-}
 
-/// A named collection of keys and values.
-pub struct Tree<Root: tree::Root, File: ManagedFile> {
-    roots: Roots<File>,
-    state: State<Root>,
-    reducer: Arc<dyn AnyReducer>,
-    vault: Option<Arc<dyn AnyVault>>,
-    name: Cow<'static, str>,
-}
+    /// Sets the clock returned by [`Roots::clock()`]. Nebari doesn't read the
+    /// time itself, but code built on the database can read it through this
+    /// clock, allowing tests to control the passage of time. Defaults to
+    /// [`SystemClock`].
+    pub fn clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
 
-impl<Root: tree::Root, File: ManagedFile> Clone for Tree<Root, File> {
-    fn clone(&self) -> Self {
-        Self {
-            roots: self.roots.clone(),
-            state: self.state.clone(),
-            vault: self.vault.clone(),
-            reducer: self.reducer.clone(),
-            name: self.name.clone(),
-        }
+    /// Enables logging the keys changed by each committed transaction and the
+    /// kind of operation performed on them. Values are not logged. The log
+    /// retains the most recent `capacity` operations across all trees, and can
+    /// be queried using [`Tree::recent_operations()`] or
+    /// [`Roots::recent_operations()`].
+    ///
+    /// This is intended for debugging, and is disabled by default.
+    pub fn operation_log(mut self, capacity: usize) -> Self {
+        self.operation_log = Some(capacity);
+        self
     }
-}
 
-impl<Root: tree::Root, File: ManagedFile> Tree<Root, File> {
-    /// Returns the name of the tree.
-    #[must_use]
-    pub fn name(&self) -> &str {
-        &self.name
+    /// Sets the extension used for tree files, without the leading `.`.
+    /// Defaults to `nebari`.
+    ///
+    /// Changing the extension of an existing database will cause its existing
+    /// trees to no longer be found.
+    pub fn file_extension(mut self, extension: impl Into<String>) -> Self {
+        self.file_extension = Some(extension.into());
+        self
     }
 
-    /// Returns the path to the file for this tree.
-    #[must_use]
-    pub fn path(&self) -> PathBuf {
-        self.roots.tree_path(self.name())
+    /// Sets whether corrupt trees are repaired when the database is opened.
+    /// Repairs that were performed can be retrieved using
+    /// [`Roots::repairs()`].
+    ///
+    /// Repairing a tree may discard data, so this defaults to
+    /// [`RepairPolicy::Never`].
+    pub fn repair_on_open(mut self, policy: RepairPolicy) -> Self {
+        self.repair_policy = policy;
+        self
     }
 
-    /// Returns the number of keys stored in the tree. Does not include deleted keys.
-    #[must_use]
-    pub fn count(&self) -> u64 {
-        let state = self.state.lock();
-        state.root.count()
+    /// Sets the minimum number of bytes that must be available before a
+    /// transaction is committed. Committing a transaction when less space is
+    /// available fails with [`ErrorKind::OutOfSpace`] before the transaction's
+    /// trees or log entry are written, and the transaction is rolled back.
+    ///
+    /// The available space is queried using
+    /// [`FileManager::available_space()`]. If the file manager can't determine
+    /// the available space, opening the database fails. This is the case for
+    /// a [`MemoryFileManager`](crate::io::memory::MemoryFileManager) created
+    /// without a capacity, and for [`StdFileManager`] unless the
+    /// `available-space` feature is enabled. Writes that fail
+    /// because the storage device is full return [`ErrorKind::OutOfSpace`]
+    /// regardless of this setting.
+    pub fn min_free_space(mut self, bytes: u64) -> Self {
+        self.min_free_space = Some(bytes);
+        self
     }
 
-    /// Sets `key` to `value`. This is executed within its own transaction.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn set(
-        &self,
-        key: impl Into<ArcBytes<'static>>,
-        value: impl Into<ArcBytes<'static>>,
-    ) -> Result<(), Error> {
-        let transaction = self.begin_transaction()?;
-        transaction.tree::<Root>(0).unwrap().set(key, value)?;
-        transaction.commit()
+    /// Sets the handler deciding how to recover trees that weren't written
+    /// completely, such as after a crash during a commit. The handler is
+    /// invoked each time loading a tree requires a recovery decision, and the
+    /// returned [`RecoveryAction`] is taken. Without a handler, each
+    /// situation's [default action](RecoverySituation::default_action) is
+    /// taken.
+    ///
+    /// Returning [`RecoveryAction::Abort`] causes the operation that loaded
+    /// the tree to fail with [`ErrorKind::RecoveryAborted`].
+    pub fn recovery_handler<Handler>(mut self, handler: Handler) -> Self
+    where
+        Handler: for<'a> Fn(RecoverySituation<'a>) -> RecoveryAction + Send + Sync + 'static,
+    {
+        self.recovery_handler = Some(RecoveryHandler(Arc::new(handler)));
+        self
     }
 
-    fn begin_transaction(&self) -> Result<ExecutingTransaction<File>, Error> {
-        let reducer = self
-            .reducer
-            .as_ref()
-            .as_any()
-            .downcast_ref::<Root::Reducer>()
-            .unwrap()
-            .clone();
-        let mut root = Root::tree_with_reducer(self.name.clone(), reducer);
-        if let Some(vault) = &self.vault {
-            root.vault = Some(vault.clone());
-        }
-        self.roots.transaction(&[root])
+    /// Sets the number of bytes in each page of the database's tree files.
+    /// Defaults to [`PAGE_SIZE`](crate::tree::PAGE_SIZE).
+    ///
+    /// Roots are aligned to page boundaries, so a larger page size wastes
+    /// more space each time a tree is saved, while a smaller page size
+    /// requires checking more offsets when searching for a tree's most recent
+    /// root. The page size is recorded in each tree file, and opening a tree
+    /// created with a different page size fails with
+    /// [`ErrorKind::DataIntegrity`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `page_size` isn't a power of two between 16 and 2^31 bytes.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        tree::assert_valid_page_size(page_size);
+        self.page_size = Some(page_size);
+        self
     }
 
-    fn open_for_read(&self) -> Result<TreeFile<Root, File>, Error> {
-        let context = self.vault.as_ref().map_or_else(
-            || Cow::Borrowed(self.roots.context()),
-            |vault| Cow::Owned(self.roots.context().clone().with_any_vault(vault.clone())),
-        );
+    /// Sets the number of bytes buffered in memory while writing to the
+    /// database's tree files. Defaults to
+    /// [`WRITE_BUFFER_SIZE`](crate::tree::WRITE_BUFFER_SIZE).
+    ///
+    /// A larger buffer reduces the number of writes issued when large amounts
+    /// of data are saved at once, at the cost of allocating the buffer each
+    /// time a tree is written to.
+    pub fn write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = Some(write_buffer_size);
+        self
+    }
 
-        TreeFile::<Root, File>::read(
-            self.path(),
-            self.state.clone(),
-            &context,
-            Some(self.roots.transactions()),
-        )
+    /// Sets whether keys within leaf nodes are written using prefix
+    /// compression. Defaults to false.
+    ///
+    /// Sorted keys often share long prefixes, such as keys built from a
+    /// common namespace. When enabled, each key in a leaf node only stores the
+    /// bytes that differ from the key before it. Existing nodes remain
+    /// readable regardless of this setting, but files written with
+    /// compression enabled can't be opened by versions of this crate that
+    /// predate this option.
+    pub fn key_compression(mut self, key_compression: bool) -> Self {
+        self.key_compression = key_compression;
+        self
     }
 
-    /// Retrieves the current value of `key`, if present. Does not reflect any
-    /// changes in pending transactions.
-    pub fn get(&self, key: &[u8]) -> Result<Option<ArcBytes<'static>>, Error> {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(None),
-                Err(err) => return Err(err),
-            };
+    /// Sets the policy used to compact trees automatically. After a
+    /// transaction is committed, each tree it modified is checked against the
+    /// policy, and trees that need to be compacted are compacted one at a time
+    /// on a thread dedicated to this database's compactions. Compactions don't
+    /// occupy the [thread pool](Self::shared_thread_pool), so they don't delay
+    /// commits.
+    ///
+    /// Defaults to [`CompactionPolicy::Never`], which requires trees to be
+    /// compacted using [`Tree::compact()`].
+    pub fn auto_compact(mut self, policy: CompactionPolicy) -> Self {
+        self.compaction_policy = policy;
+        self
+    }
 
-            tree.get(key, false)
-        })
+    /// Sets whether the database is opened without the ability to modify it.
+    /// Defaults to false.
+    ///
+    /// A read-only database is never created, and opening one that doesn't
+    /// exist fails. The transaction log is read without spawning the thread
+    /// that writes to it, trees are opened as if by
+    /// [`Roots::tree_read_only()`], and [repairs](Self::repair_on_open) are
+    /// not performed. Every function that modifies the database returns
+    /// [`ErrorKind::ReadOnly`].
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
     }
 
-    /// Retrieves the current index of `key`, if present. Does not reflect any
-    /// changes in pending transactions.
-    pub fn get_index(&self, key: &[u8]) -> Result<Option<Root::Index>, Error> {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(None),
-                Err(err) => return Err(err),
-            };
+    /// Sets whether the transaction log is synchronized to disk each time a
+    /// transaction is committed. Defaults to true.
+    ///
+    /// Synchronizing the log is often the most expensive part of committing a
+    /// transaction. Disabling it can significantly speed up bulk imports that
+    /// don't need crash recovery guarantees. Transactions are still written to
+    /// the log, and the log is synchronized when the last handle to the
+    /// database, including its [`Tree`]s, is dropped. Dropping that handle
+    /// blocks until the log has been synchronized. An error synchronizing the
+    /// log at that point can't be returned, and is printed to stderr instead.
+    /// Trees are still synchronized according to their
+    /// [`PersistenceMode`](crate::tree::PersistenceMode).
+    ///
+    /// **Committed transactions are not durable while this is disabled.** If
+    /// the process or machine crashes, the most recently committed
+    /// transactions can be lost. When the database is reopened, each tree is
+    /// restored to the last transaction that reached the log, so the trees
+    /// remain consistent with each other.
+    pub fn synchronize_transaction_log(mut self, synchronize: bool) -> Self {
+        self.synchronize_transaction_log = synchronize;
+        self
+    }
 
-            tree.get_index(key, false)
-        })
+    /// Opens the database, or creates one if the target path doesn't exist.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if the configured [file
+    /// extension](Self::file_extension) is empty or contains a path separator.
+    pub fn open(self) -> Result<Roots<M::File>, Error> {
+        Roots::open(self)
     }
+}
 
-    /// Retrieves the current value and index of `key`, if present. Does not reflect any
-    /// changes in pending transactions.
-    pub fn get_with_index(
-        &self,
-        key: &[u8],
-    ) -> Result<Option<(ArcBytes<'static>, Root::Index)>, Error> {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(None),
-                Err(err) => return Err(err),
-            };
+/// Controls when trees are compacted automatically. Set using
+/// [`Config::auto_compact()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompactionPolicy {
+    /// Trees are only compacted when [`Tree::compact()`] is called. This is
+    /// the default.
+    Never,
+    /// Trees are compacted once the bytes stored for their live values make
+    /// up less than this fraction of their file's length. For example,
+    /// `Ratio(0.5)` compacts a tree once more than half of its file is
+    /// occupied by nodes, old versions, and deleted values.
+    ///
+    /// A tree whose nodes make up most of its file could fall below the ratio
+    /// again immediately after being compacted. To prevent compacting such a
+    /// tree after every transaction, a tree isn't compacted again until its
+    /// file has doubled in length since its last automatic compaction.
+    ///
+    /// A tree isn't compacted if it is already being compacted or a
+    /// transaction is using it. It is checked again after the next
+    /// transaction that modifies it.
+    Ratio(f32),
+}
 
-            tree.get_with_index(key, false)
-        })
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self::Never
     }
+}
 
-    /// Sets `key` to `value`. Returns a tuple containing two elements:
+/// Controls whether corrupt trees are repaired when opening [`Roots`]. Set
+/// using [`Config::repair_on_open()`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RepairPolicy {
+    /// Corrupt trees are left untouched, and accessing them returns an error.
+    /// This is the default.
+    Never,
+    /// Corrupt trees are truncated after their most recent valid root. Any
+    /// data written after that root is discarded.
     ///
-    /// - The previously stored value, if a value was already present.
-    /// - The new/updated index for this key.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn replace(
-        &mut self,
-        key: impl Into<ArcBytes<'static>>,
-        value: impl Into<ArcBytes<'static>>,
-    ) -> Result<(Option<ArcBytes<'static>>, Root::Index), Error> {
-        let transaction = self.begin_transaction()?;
-        let existing_value = transaction.tree::<Root>(0).unwrap().replace(key, value)?;
-        transaction.commit()?;
-        Ok(existing_value)
+    /// A root is valid if its chunk passes its checksum and the transaction
+    /// that wrote it was committed. Trees that use a different vault than the
+    /// database are not repaired.
+    TruncateToLastValidRoot,
+}
+
+impl Default for RepairPolicy {
+    fn default() -> Self {
+        Self::Never
     }
+}
 
-    /// Executes a modification. Returns a list of all changed keys.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn modify<'a>(
-        &mut self,
-        keys: Vec<ArcBytes<'a>>,
-        operation: Operation<'a, ArcBytes<'static>, Root::Index>,
-    ) -> Result<Vec<ModificationResult<Root::Index>>, Error> {
-        let transaction = self.begin_transaction()?;
-        let results = transaction
-            .tree::<Root>(0)
-            .unwrap()
-            .modify(keys, operation)?;
-        transaction.commit()?;
-        Ok(results)
+/// A tree that was repaired when opening [`Roots`]. Returned from
+/// [`Roots::repairs()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TreeRepair {
+    /// The name of the repaired tree.
+    pub tree: String,
+    /// The number of bytes truncated from the end of the tree's file.
+    pub bytes_discarded: u64,
+}
+
+/// A transaction that was committed. Received from the channel returned by
+/// [`Roots::subscribe()`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CommitEvent {
+    /// The id of the committed transaction.
+    pub transaction_id: TransactionId,
+    /// The names of the trees modified by the transaction.
+    pub trees: Vec<String>,
+}
+
+/// Builds a transaction over trees that may have different
+/// [`Root`](tree::Root) types. Returned from [`Roots::transaction_builder()`].
+///
+/// Trees are accessible within the transaction by the index in which they were
+/// added.
+#[must_use]
+pub struct TransactionBuilder<'a, File: ManagedFile> {
+    roots: &'a Roots<File>,
+    trees: Vec<Box<dyn AnyTreeRoot<File>>>,
+}
+
+impl<File: ManagedFile> TransactionBuilder<'_, File> {
+    /// Adds the tree named `name` using the default reducer of `Root`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<Root: tree::Root>(self, name: impl Into<Cow<'static, str>>) -> Self
+    where
+        Root::Reducer: Default,
+    {
+        self.add_tree(Root::tree(name))
     }
 
-    /// Removes `key` and returns the existing value and index, if present. This
-    /// is executed within its own transaction.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn remove(&self, key: &[u8]) -> Result<Option<(ArcBytes<'static>, Root::Index)>, Error> {
-        let transaction = self.begin_transaction()?;
-        let existing_value = transaction.tree::<Root>(0).unwrap().remove(key)?;
-        transaction.commit()?;
-        Ok(existing_value)
+    /// Adds `root` to the transaction.
+    pub fn add_tree<Root: tree::Root>(mut self, root: TreeRoot<Root, File>) -> Self {
+        self.trees.push(Box::new(root));
+        self
     }
 
-    /// Compares the value of `key` against `old`. If the values match, key will
-    /// be set to the new value if `new` is `Some` or removed if `new` is
-    /// `None`. This is executed within its own transaction.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn compare_and_swap(
-        &self,
-        key: &[u8],
-        old: Option<&[u8]>,
-        new: Option<ArcBytes<'_>>,
-    ) -> Result<(), CompareAndSwapError> {
-        let transaction = self.begin_transaction()?;
-        transaction
-            .tree::<Root>(0)
-            .unwrap()
-            .compare_and_swap(key, old, new)?;
-        transaction.commit()?;
-        Ok(())
+    /// Begins the transaction. See [`Roots::transaction()`] for more
+    /// information.
+    pub fn begin(self) -> Result<ExecutingTransaction<File>, Error> {
+        self.roots
+            .transaction::<_, dyn AnyTreeRoot<File>>(&self.trees)
     }
+}
 
-    /// Retrieves the values of `keys`. If any keys are not found, they will be
-    /// omitted from the results. Keys are required to be pre-sorted.
-    #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple<'keys, Keys>(
-        &self,
-        keys: Keys,
-    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
-    where
-        Keys: Iterator<Item = &'keys [u8]> + ExactSizeIterator + Clone,
-    {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
-                Err(err) => return Err(err),
-            };
+/// A point-in-time view of one or more trees, created by
+/// [`Roots::snapshot()`].
+#[derive(Debug)]
+pub struct Snapshot<File: ManagedFile> {
+    roots: Roots<File>,
+    trees: Vec<SnapshotEntry>,
+}
 
-            tree.get_multiple(keys.clone(), false)
+#[derive(Debug)]
+struct SnapshotEntry {
+    name: String,
+    state: Box<dyn AnyTreeState>,
+    vault: Option<Arc<dyn AnyVault>>,
+}
+
+impl<File: ManagedFile> Snapshot<File> {
+    /// Accesses the tree at `index` in the list of trees the snapshot was
+    /// created with.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `Root` isn't the root type the tree was captured with.
+    #[must_use]
+    pub fn tree<Root: tree::Root>(&self, index: usize) -> Option<SnapshotTree<Root, File>> {
+        self.trees.get(index).map(|entry| SnapshotTree {
+            roots: self.roots.clone(),
+            state: entry
+                .state
+                .as_ref()
+                .as_any()
+                .downcast_ref::<State<Root>>()
+                .unwrap()
+                .clone(),
+            vault: entry.vault.clone(),
+            name: entry.name.clone(),
         })
     }
 
-    /// Retrieves the indexes of `keys`. If any keys are not found, they will be
-    /// omitted from the results. Keys are required to be pre-sorted.
-    #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple_indexes<'keys, KeysIntoIter, KeysIter>(
-        &self,
-        keys: KeysIntoIter,
-    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
-    where
-        KeysIntoIter: IntoIterator<Item = &'keys [u8], IntoIter = KeysIter> + Clone,
-        KeysIter: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
-    {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
-                Err(err) => return Err(err),
-            };
+    /// Returns the number of trees in the snapshot.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
 
-            tree.get_multiple_indexes(keys.clone(), false)
-        })
+    /// Returns true if the snapshot contains no trees.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
     }
 
-    /// Retrieves the values and indexes of `keys`. If any keys are not found,
-    /// they will be omitted from the results. Keys are required to be
-    /// pre-sorted.
-    #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple_with_indexes<'keys, KeysIntoIter, KeysIter>(
+    /// Writes a database containing each tree in this snapshot, as it was
+    /// when the snapshot was taken, to the directory `path`. The exported
+    /// database can be opened using [`Config`], and uses the same file
+    /// extension and vault as this database.
+    ///
+    /// Each tree's file is copied up to the end of the root captured by the
+    /// snapshot, and the transaction log is copied so that the captured roots
+    /// are recognized as committed. Writers aren't blocked while exporting.
+    ///
+    /// ## Errors
+    ///
+    /// - [`Io`](ErrorKind::Io): `path` already exists, in which case the
+    ///   error's kind is [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+    /// - [`TreeCompacted`](ErrorKind::TreeCompacted): A tree was compacted
+    ///   after the snapshot was taken.
+    ///
+    /// If an error occurs, the partially written directory is removed.
+    pub fn export_to(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(Error::from(std::io::Error::from(
+                std::io::ErrorKind::AlreadyExists,
+            )));
+        }
+        fs::create_dir_all(path)?;
+        let result = self.export_files(path);
+        if result.is_err() {
+            let file_manager = &self.roots.context().file_manager;
+            file_manager.delete_directory(path)?;
+            if path.exists() {
+                fs::remove_dir_all(path)?;
+            }
+        }
+        result
+    }
+
+    fn export_files(&self, path: &Path) -> Result<(), Error> {
+        let file_manager = &self.roots.context().file_manager;
+        for entry in &self.trees {
+            let (file_id, length) = entry.state.published_position();
+            let mut tree = file_manager.read(self.roots.tree_path(&entry.name))?;
+            tree.execute(SnapshotExporter {
+                manager: file_manager,
+                file_id,
+                length,
+                destination: &path
+                    .join(format!("{}.{}", entry.name, self.roots.data.file_extension)),
+            })?;
+        }
+
+        // Every committed transaction the exported roots belong to is in the
+        // log. Transactions committed after the snapshot was taken don't
+        // refer to any data in the exported trees.
+        let database = self.roots.path();
+        for (source, destination) in [
+            (
+                TransactionManager::<File::Manager>::log_path(database),
+                TransactionManager::<File::Manager>::log_path(path),
+            ),
+            (
+                vault::vault_check_path(database),
+                vault::vault_check_path(path),
+            ),
+        ] {
+            if !file_manager.exists(&source)? {
+                continue;
+            }
+            let length = file_manager.file_length(&source)?;
+            let mut file = file_manager.open_for_read(&source, None)?;
+            copy_file_prefix(file_manager, &mut file, length, &destination)?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the first `length` bytes of a tree file, as captured by a
+/// [`Snapshot`], to `destination`.
+struct SnapshotExporter<'a, Manager: FileManager> {
+    manager: &'a Manager,
+    file_id: Option<u64>,
+    length: u64,
+    destination: &'a Path,
+}
+
+impl<'a, Manager: FileManager> FileOp<Result<(), Error>> for SnapshotExporter<'a, Manager> {
+    fn execute(self, file: &mut dyn io::File) -> Result<(), Error> {
+        if file.id() != self.file_id {
+            return Err(Error::from(ErrorKind::TreeCompacted));
+        }
+        copy_file_prefix(self.manager, file, self.length, self.destination)
+    }
+}
+
+/// Copies the first `length` bytes of `source` to a new file at
+/// `destination`, synchronizing it before returning.
+fn copy_file_prefix<Manager: FileManager>(
+    manager: &Manager,
+    source: &mut dyn io::File,
+    length: u64,
+    destination: &Path,
+) -> Result<(), Error> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    source.seek(SeekFrom::Start(0))?;
+    let mut copy = manager.open_for_append(destination, None)?;
+    std::io::copy(&mut source.take(length), &mut copy)?;
+    copy.synchronize()?;
+    copy.close()
+}
+
+/// A read-only view of a tree as it was when a [`Snapshot`] was taken.
+///
+/// Compacting a tree removes the data its snapshots refer to. Once the tree
+/// has been compacted, reads through the snapshot return
+/// [`TreeCompacted`](ErrorKind::TreeCompacted).
+pub struct SnapshotTree<Root: tree::Root, File: ManagedFile> {
+    roots: Roots<File>,
+    state: State<Root>,
+    vault: Option<Arc<dyn AnyVault>>,
+    name: String,
+}
+
+impl<Root: tree::Root, File: ManagedFile> SnapshotTree<Root, File> {
+    /// Returns the name of the tree.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of keys stored in the tree when the snapshot was
+    /// taken. Does not include deleted keys.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.state.read().root.count()
+    }
+
+    fn open_for_read(&self) -> Result<TreeFile<Root, File>, Error> {
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(self.roots.context()),
+            |vault| Cow::Owned(self.roots.context().clone().with_any_vault(vault.clone())),
+        );
+
+        TreeFile::<Root, File>::read(
+            self.roots.tree_path(&self.name),
+            self.state.clone(),
+            &context,
+            Some(self.roots.transactions()),
+        )
+    }
+
+    /// Retrieves the value of `key`, if present.
+    pub fn get(&self, key: &[u8]) -> Result<Option<ArcBytes<'static>>, Error> {
+        self.open_for_read()?.get(key, false)
+    }
+
+    /// Retrieves the values of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Keys are required to be pre-sorted.
+    pub fn get_multiple<'keys, Keys>(
         &self,
-        keys: KeysIntoIter,
-    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>, Root::Index)>, Error>
+        keys: Keys,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
     where
-        KeysIntoIter: IntoIterator<Item = &'keys [u8], IntoIter = KeysIter> + Clone,
-        KeysIter: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
+        Keys: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
     {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
-                Err(err) => return Err(err),
-            };
-
-            tree.get_multiple_with_indexes(keys.clone(), false)
-        })
+        self.open_for_read()?.get_multiple(keys, false)
     }
 
     /// Retrieves all of the values of keys within `range`.
@@ -1121,150 +3083,228 @@ impl<Root: tree::Root, File: ManagedFile> Tree<Root, File> {
         range: &'keys KeyRangeBounds,
     ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
     where
-        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
     {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
-                Err(err) => return Err(err),
-            };
-
-            tree.get_range(range, false)
-        })
+        self.open_for_read()?.get_range(range, false)
     }
 
-    /// Retrieves all of the indexes of keys within `range`.
-    pub fn get_range_indexes<'keys, KeyRangeBounds>(
+    /// Calculates the reduced index over `range`.
+    pub fn reduce<'keys, KeyRangeBounds>(
         &self,
         range: &'keys KeyRangeBounds,
-    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
+    ) -> Result<Option<Root::ReducedIndex>, Error>
     where
-        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
     {
-        catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
-                Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
-                Err(err) => return Err(err),
-            };
+        self.open_for_read()?.reduce(range, false)
+    }
 
-            tree.get_range_indexes(range, false)
-        })
+    /// Returns the first key and value of the tree.
+    pub fn first(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.open_for_read()?.first(false)
     }
 
-    /// Retrieves all of the values and indexes of keys within `range`.
-    pub fn get_range_with_indexes<'keys, KeyRangeBounds>(
-        &self,
-        range: &'keys KeyRangeBounds,
-    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>, Root::Index)>, Error>
-    where
-        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
-    {
+    /// Returns the last key and value of the tree.
+    pub fn last(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        self.open_for_read()?.last(false)
+    }
+}
+
+/// A named collection of keys and values.
+pub struct Tree<Root: tree::Root, File: ManagedFile> {
+    roots: Roots<File>,
+    state: State<Root>,
+    reducer: Arc<dyn AnyReducer>,
+    vault: Option<Arc<dyn AnyVault>>,
+    /// The vault generation of `state` when this tree was opened.
+    vault_generation: u64,
+    max_order: Option<usize>,
+    name: Cow<'static, str>,
+    read_only: bool,
+}
+
+impl<Root: tree::Root, File: ManagedFile> Clone for Tree<Root, File> {
+    fn clone(&self) -> Self {
+        Self {
+            roots: self.roots.clone(),
+            state: self.state.clone(),
+            vault: self.vault.clone(),
+            vault_generation: self.vault_generation,
+            reducer: self.reducer.clone(),
+            max_order: self.max_order,
+            name: self.name.clone(),
+            read_only: self.read_only,
+        }
+    }
+}
+
+impl<Root: tree::Root, File: ManagedFile> Tree<Root, File> {
+    /// Returns the name of the tree.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if this tree was opened using
+    /// [`Roots::tree_read_only()`] or from a database opened using
+    /// [`Config::read_only()`].
+    #[must_use]
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the operations recorded for `key` by committed transactions,
+    /// oldest first. Always empty unless the operation log was enabled using
+    /// [`Config::operation_log()`].
+    #[must_use]
+    pub fn recent_operations(&self, key: &[u8]) -> Vec<(TransactionId, OperationKind)> {
+        self.roots.recent_operations(&self.name, key)
+    }
+
+    /// Returns the path to the file for this tree.
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        self.roots.tree_path(self.name())
+    }
+
+    /// Returns true if the file for this tree exists. The file is created
+    /// when the tree is obtained from [`Roots::tree()`], but it may have since
+    /// been deleted or renamed.
+    pub fn exists(&self) -> Result<bool, Error> {
+        self.roots.context().file_manager.exists(self.path())
+    }
+
+    /// Returns the number of keys stored in the tree. Does not include deleted keys.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        let state = self.state.lock();
+        state.root.count()
+    }
+
+    /// Returns the number of keys stored in the tree's most recently
+    /// committed state. Does not include deleted keys.
+    ///
+    /// Unlike [`Self::count()`], this doesn't wait for the lock held by
+    /// transactions writing to the tree. The returned value reflects the last
+    /// published root, so it may lag behind writes that are being committed.
+    /// This makes it suitable for frequent polling, such as for monitoring.
+    #[must_use]
+    pub fn approximate_count(&self) -> u64 {
+        self.state.read().root.count()
+    }
+
+    /// Returns true if the tree contains no keys, not including deleted keys.
+    /// Only the tree's most recently committed state is checked.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.state.read().root.count() == 0
+    }
+
+    /// Returns statistics describing the structure of the tree. See
+    /// [`TreeFile::statistics()`] for more information. Does not reflect any
+    /// changes in pending transactions.
+    pub fn statistics(&self) -> Result<TreeStatistics, Error> {
         catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) if err.kind.is_file_not_found() => return Ok(TreeStatistics::default()),
                 Err(err) => return Err(err),
             };
 
-            tree.get_range_with_indexes(range, false)
+            tree.statistics()
         })
     }
 
-    /// Scans the tree across all nodes that might contain nodes within `range`.
-    ///
-    /// If `forwards` is true, the tree is scanned in ascending order.
-    /// Otherwise, the tree is scanned in descending order.
-    ///
-    /// `node_evaluator` is invoked for each [`Interior`](crate::tree::Interior) node to determine if
-    /// the node should be traversed. The parameters to the callback are:
-    ///
-    /// - `&ArcBytes<'static>`: The maximum key stored within the all children
-    ///   nodes.
-    /// - `&Root::ReducedIndex`: The reduced index value stored within the node.
-    /// - `usize`: The depth of the node. The root nodes are depth 0.
-    ///
-    /// The result of the callback is a [`ScanEvaluation`]. To read children
-    /// nodes, return [`ScanEvaluation::ReadData`].
-    ///
-    /// `key_evaluator` is invoked for each key encountered that is contained
-    /// within `range`. For all [`ScanEvaluation::ReadData`] results returned,
-    /// `callback` will be invoked with the key and values. `callback` may not
-    /// be invoked in the same order as the keys are scanned.
-    #[cfg_attr(
-        feature = "tracing",
-        tracing::instrument(skip(self, node_evaluator, key_evaluator, callback))
-    )]
-    pub fn scan<'keys, CallerError, KeyRangeBounds, NodeEvaluator, KeyEvaluator, DataCallback>(
+    /// Sets `key` to `value`. This is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn set(
         &self,
-        range: &'keys KeyRangeBounds,
-        forwards: bool,
-        mut node_evaluator: NodeEvaluator,
-        mut key_evaluator: KeyEvaluator,
-        mut callback: DataCallback,
-    ) -> Result<(), AbortError<CallerError>>
-    where
-        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
-        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
-        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
-        DataCallback: FnMut(
-            ArcBytes<'static>,
-            &Root::Index,
-            ArcBytes<'static>,
-        ) -> Result<(), AbortError<CallerError>>,
-        CallerError: Display + Debug,
-    {
-        catch_compaction_and_retry_abortable(move || {
+        key: impl Into<ArcBytes<'static>>,
+        value: impl Into<ArcBytes<'static>>,
+    ) -> Result<(), Error> {
+        let transaction = self.begin_transaction()?;
+        transaction.tree::<Root>(0).unwrap().set(key, value)?;
+        transaction.commit()
+    }
+
+    /// Synchronizes all data written to the tree's file so far to stable
+    /// storage. See [`TreeFile::synchronize_now()`] for more information.
+    pub fn flush(&self) -> Result<(), Error> {
+        catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
                 Err(err) if err.kind.is_file_not_found() => return Ok(()),
-                Err(err) => return Err(AbortError::from(err)),
+                Err(err) => return Err(err),
             };
 
-            tree.scan(
-                range,
-                forwards,
-                false,
-                &mut node_evaluator,
-                &mut key_evaluator,
-                &mut callback,
-            )
+            tree.synchronize_now()
         })
     }
 
-    /// Returns the reduced index over the provided range. This is an
-    /// aggregation function that builds atop the `scan()` operation which calls
-    /// [`Reducer::reduce()`](crate::tree::Reducer::reduce) and
-    /// [`Reducer::rereduce()`](crate::tree::Reducer::rereduce) on all matching
-    /// indexes stored within the nodes of this tree, producing a single
-    /// aggregated [`Root::ReducedIndex`](tree::Root::ReducedIndex) value.
-    ///
-    /// If no keys match, the returned result is what
-    /// [`Reducer::rereduce()`](crate::tree::Reducer::rereduce) returns when an
-    /// empty slice is provided.
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
-    pub fn reduce<'keys, KeyRangeBounds>(
-        &self,
-        range: &'keys KeyRangeBounds,
-    ) -> Result<Option<Root::ReducedIndex>, Error>
-    where
-        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
-    {
-        catch_compaction_and_retry(move || {
+    fn begin_transaction(&self) -> Result<ExecutingTransaction<File>, Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
+        let reducer = self
+            .reducer
+            .as_ref()
+            .as_any()
+            .downcast_ref::<Root::Reducer>()
+            .unwrap()
+            .clone();
+        let mut root = Root::tree_with_reducer(self.name.clone(), reducer);
+        if let Some(vault) = &self.vault {
+            root.vault = Some(vault.clone());
+        }
+        let transaction = self.roots.transaction(&[root])?;
+        // The vault can't be rotated while the transaction holds the tree's
+        // lock, so this check can't race with a rotation.
+        self.check_vault_generation()?;
+        Ok(transaction)
+    }
+
+    /// Returns [`VaultMismatch`](ErrorKind::VaultMismatch) if the tree's vault
+    /// was rotated after this handle was opened.
+    fn check_vault_generation(&self) -> Result<(), Error> {
+        if self.state.vault_generation() == self.vault_generation {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::VaultMismatch))
+        }
+    }
+
+    fn open_for_read(&self) -> Result<TreeFile<Root, File>, Error> {
+        self.check_vault_generation()?;
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(self.roots.context()),
+            |vault| Cow::Owned(self.roots.context().clone().with_any_vault(vault.clone())),
+        );
+
+        TreeFile::<Root, File>::read(
+            self.path(),
+            self.state.clone(),
+            &context,
+            Some(self.roots.transactions()),
+        )
+    }
+
+    /// Retrieves the current value of `key`, if present. Does not reflect any
+    /// changes in pending transactions.
+    pub fn get(&self, key: &[u8]) -> Result<Option<ArcBytes<'static>>, Error> {
+        catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
                 Err(err) if err.kind.is_file_not_found() => return Ok(None),
                 Err(err) => return Err(err),
             };
 
-            tree.reduce(range, false)
+            tree.get(key, false)
         })
     }
 
-    /// Returns the first key of the tree.
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
-    pub fn first_key(&self) -> Result<Option<ArcBytes<'static>>, Error> {
+    /// Retrieves the current index of `key`, if present. Does not reflect any
+    /// changes in pending transactions.
+    pub fn get_index(&self, key: &[u8]) -> Result<Option<Root::Index>, Error> {
         catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
@@ -1272,27 +3312,30 @@ impl<Root: tree::Root, File: ManagedFile> Tree<Root, File> {
                 Err(err) => return Err(err),
             };
 
-            tree.first_key(false)
+            tree.get_index(key, false)
         })
     }
 
-    /// Returns the first key and value of the tree.
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
-    pub fn first(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+    /// Returns true if a value is stored for `key`. Does not reflect any
+    /// changes in pending transactions.
+    pub fn contains_key(&self, key: &[u8]) -> Result<bool, Error> {
         catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
-                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) if err.kind.is_file_not_found() => return Ok(false),
                 Err(err) => return Err(err),
             };
 
-            tree.first(false)
+            tree.contains_key(key, false)
         })
     }
 
-    /// Returns the last key of the tree.
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
-    pub fn last_key(&self) -> Result<Option<ArcBytes<'static>>, Error> {
+    /// Retrieves the current value and index of `key`, if present. Does not reflect any
+    /// changes in pending transactions.
+    pub fn get_with_index(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<(ArcBytes<'static>, Root::Index)>, Error> {
         catch_compaction_and_retry(|| {
             let mut tree = match self.open_for_read() {
                 Ok(tree) => tree,
@@ -1300,597 +3343,5348 @@ impl<Root: tree::Root, File: ManagedFile> Tree<Root, File> {
                 Err(err) => return Err(err),
             };
 
-            tree.last_key(false)
+            tree.get_with_index(key, false)
         })
     }
 
-    /// Returns the last key and value of the tree.
-    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
-    pub fn last(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+    /// Retrieves the user-defined metadata stored alongside this tree, if set.
+    /// Does not reflect any changes in pending transactions.
+    pub fn metadata(&self) -> Result<Option<ArcBytes<'static>>, Error> {
         catch_compaction_and_retry(|| {
-            let mut tree = match self.open_for_read() {
+            let tree = match self.open_for_read() {
                 Ok(tree) => tree,
                 Err(err) if err.kind.is_file_not_found() => return Ok(None),
                 Err(err) => return Err(err),
             };
 
-            tree.last(false)
+            Ok(tree.metadata(false))
         })
     }
 
-    /// Rewrites the database to remove data that is no longer current. Because
-    /// Nebari uses an append-only format, this is helpful in reducing disk
-    /// usage.
-    ///
-    /// See [`TreeFile::compact()`](crate::tree::TreeFile::compact) for more
-    /// information.
-    pub fn compact(&self) -> Result<(), Error> {
-        let tree = match self.open_for_read() {
-            Ok(tree) => tree,
-            Err(err) if err.kind.is_file_not_found() => return Ok(()),
-            Err(err) => return Err(err),
-        };
-        tree.compact(
-            &self.roots.context().file_manager,
-            Some(TransactableCompaction {
-                name: self.name.as_ref(),
-                manager: self.roots.transactions(),
-            }),
-        )?;
+    /// Replaces the user-defined metadata stored alongside this tree. Metadata
+    /// is stored separately from the tree's keys, and can be at most 65,535
+    /// bytes long. Passing `None` removes any stored metadata. This is
+    /// executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn set_metadata(&self, metadata: Option<ArcBytes<'_>>) -> Result<(), Error> {
+        let transaction = self.begin_transaction()?;
+        transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .set_metadata(metadata)?;
+        transaction.commit()?;
         Ok(())
     }
-}
 
-impl<Root: tree::Root, File: ManagedFile> AnyTreeRoot<File> for Tree<Root, File> {
-    fn name(&self) -> &str {
-        &self.name
+    /// Removes every key from the tree. Readers see an empty tree once the
+    /// change is committed. See [`TreeFile::clear()`] for more information.
+    /// This is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn clear(&self) -> Result<(), Error> {
+        let transaction = self.begin_transaction()?;
+        transaction.tree::<Root>(0).unwrap().clear()?;
+        transaction.commit()
     }
 
-    fn default_state(&self) -> Box<dyn AnyTreeState> {
-        Box::new(State::<Root>::new(
-            None,
-            None,
-            Root::default_with(
-                self.reducer
-                    .as_ref()
-                    .as_any()
-                    .downcast_ref::<Root::Reducer>()
-                    .unwrap()
-                    .clone(),
-            ),
-        ))
+    /// Sets `key` to `value`. Returns a tuple containing two elements:
+    ///
+    /// - The previously stored value, if a value was already present.
+    /// - The new/updated index for this key.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn replace(
+        &mut self,
+        key: impl Into<ArcBytes<'static>>,
+        value: impl Into<ArcBytes<'static>>,
+    ) -> Result<(Option<ArcBytes<'static>>, Root::Index), Error> {
+        let transaction = self.begin_transaction()?;
+        let existing_value = transaction.tree::<Root>(0).unwrap().replace(key, value)?;
+        transaction.commit()?;
+        Ok(existing_value)
     }
 
-    fn begin_transaction(
+    /// Sets `key` to `value`. Returns a tuple containing two elements:
+    ///
+    /// - The previously stored index, if a value was already present.
+    /// - The new/updated index for this key.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn set_returning_previous_index(
+        &mut self,
+        key: impl Into<ArcBytes<'static>>,
+        value: impl Into<ArcBytes<'static>>,
+    ) -> Result<(Option<Root::Index>, Root::Index), Error> {
+        let transaction = self.begin_transaction()?;
+        let indexes = transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .set_returning_previous_index(key, value)?;
+        transaction.commit()?;
+        Ok(indexes)
+    }
+
+    /// Executes a modification. Returns a list of all changed keys.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn modify<'a>(
+        &mut self,
+        keys: Vec<ArcBytes<'a>>,
+        operation: Operation<'a, ArcBytes<'static>, Root::Index>,
+    ) -> Result<Vec<ModificationResult<Root::Index>>, Error> {
+        let transaction = self.begin_transaction()?;
+        let results = transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .modify(keys, operation)?;
+        transaction.commit()?;
+        Ok(results)
+    }
+
+    /// Adds `delta` to the counter stored in `key`, returning the new total.
+    /// See [`TreeFile::increment()`] for more information. This is executed
+    /// within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn increment(&self, key: impl Into<ArcBytes<'static>>, delta: i64) -> Result<i64, Error> {
+        let transaction = self.begin_transaction()?;
+        let total = transaction.tree::<Root>(0).unwrap().increment(key, delta)?;
+        transaction.commit()?;
+        Ok(total)
+    }
+
+    /// Executes a modification. Returns a list of all changed keys along with
+    /// their values and indexes from before the modification. See
+    /// [`TreeFile::modify_with_previous()`] for more information. This is
+    /// executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn modify_with_previous<'a>(
         &self,
-        transaction_id: TransactionId,
-        file_path: &Path,
-        state: &dyn AnyTreeState,
-        context: &Context<File::Manager>,
-        transactions: Option<&TransactionManager<File::Manager>>,
-    ) -> Result<Box<dyn AnyTransactionTree<File>>, Error> {
-        let context = self.vault.as_ref().map_or_else(
-            || Cow::Borrowed(context),
-            |vault| Cow::Owned(context.clone().with_any_vault(vault.clone())),
-        );
-        let tree = TreeFile::write(
-            file_path,
-            state
-                .as_any()
-                .downcast_ref::<State<Root>>()
-                .unwrap()
-                .clone(),
-            &context,
-            transactions,
-        )?;
+        keys: Vec<ArcBytes<'a>>,
+        operation: Operation<'a, ArcBytes<'static>, Root::Index>,
+    ) -> Result<Vec<ModificationResultWithPrevious<Root::Index>>, Error> {
+        let transaction = self.begin_transaction()?;
+        let results = transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .modify_with_previous(keys, operation)?;
+        transaction.commit()?;
+        Ok(results)
+    }
 
-        Ok(Box::new(TransactionTree {
-            transaction_id,
-            tree,
-        }))
+    /// Removes `key` and returns the existing value and index, if present. This
+    /// is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn remove(&self, key: &[u8]) -> Result<Option<(ArcBytes<'static>, Root::Index)>, Error> {
+        let transaction = self.begin_transaction()?;
+        let existing_value = transaction.tree::<Root>(0).unwrap().remove(key)?;
+        transaction.commit()?;
+        Ok(existing_value)
     }
-}
 
-impl<File: ManagedFile, Index> Tree<VersionedTreeRoot<Index>, File>
-where
-    Index: EmbeddedIndex + Clone + Debug + 'static,
-{
-    /// Returns the latest sequence id.
-    #[must_use]
-    pub fn current_sequence_id(&self) -> SequenceId {
-        let state = self.state.lock();
-        state.root.sequence
+    /// Removes `keys`, which must be sorted in ascending order without
+    /// duplicates. Runs of consecutive keys are removed together, making this
+    /// faster than removing keys individually. See
+    /// [`TreeFile::bulk_remove_sorted()`] for more information. This is
+    /// executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn bulk_remove_sorted(
+        &self,
+        keys: Vec<ArcBytes<'_>>,
+    ) -> Result<Vec<ModificationResult<Root::Index>>, Error> {
+        let transaction = self.begin_transaction()?;
+        let results = transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .bulk_remove_sorted(keys)?;
+        transaction.commit()?;
+        Ok(results)
     }
 
-    /// Scans the tree for keys that are contained within `range`. If `forwards`
-    /// is true, scanning starts at the lowest sort-order key and scans forward.
-    /// Otherwise, scanning starts at the highest sort-order key and scans
-    /// backwards. `key_evaluator` is invoked for each key as it is encountered.
-    /// For all [`ScanEvaluation::ReadData`] results returned, `callback` will be
-    /// invoked with the key and values. The callback may not be invoked in the
-    /// same order as the keys are scanned.
-    pub fn scan_sequences<CallerError, Range, KeyEvaluator, DataCallback>(
+    /// Removes every key contained by `range`, returning the number of keys
+    /// removed. See [`TreeFile::remove_range()`] for more information. This
+    /// is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn remove_range<'keys, KeyRangeBounds>(
         &self,
-        range: Range,
-        forwards: bool,
-        key_evaluator: &mut KeyEvaluator,
-        data_callback: &mut DataCallback,
-    ) -> Result<(), AbortError<CallerError>>
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
     where
-        Range: Clone + RangeBounds<SequenceId> + Debug + 'static,
-        KeyEvaluator: FnMut(KeySequence<Index>) -> ScanEvaluation,
-        DataCallback:
-            FnMut(KeySequence<Index>, ArcBytes<'static>) -> Result<(), AbortError<CallerError>>,
-        CallerError: Display + Debug,
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
     {
-        catch_compaction_and_retry_abortable(|| {
-            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
-                self.path(),
-                self.state.clone(),
-                self.roots.context(),
-                Some(self.roots.transactions()),
-            )?;
+        let transaction = self.begin_transaction()?;
+        let removed = transaction.tree::<Root>(0).unwrap().remove_range(range)?;
+        transaction.commit()?;
+        Ok(removed)
+    }
 
-            tree.scan_sequences(range.clone(), forwards, false, key_evaluator, data_callback)
-        })
+    /// Compares the value of `key` against `old`. If the values match, key will
+    /// be set to the new value if `new` is `Some` or removed if `new` is
+    /// `None`. This is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn compare_and_swap(
+        &self,
+        key: &[u8],
+        old: Option<&[u8]>,
+        new: Option<ArcBytes<'_>>,
+    ) -> Result<(), CompareAndSwapError> {
+        let transaction = self.begin_transaction()?;
+        transaction
+            .tree::<Root>(0)
+            .unwrap()
+            .compare_and_swap(key, old, new)?;
+        transaction.commit()?;
+        Ok(())
     }
 
-    /// Retrieves the keys and values associated with one or more `sequences`.
-    /// The value retrieved is the value of the key at the given [`SequenceId`].
-    /// If a sequence is not found, it will not appear in the result map. If
-    /// the value was removed, None is returned for the value.
+    /// Applies all operations in `batch`. If any compare-and-swap condition in
+    /// the batch isn't met, no changes are made. Returns the updated indexes of
+    /// the modified keys. This is executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn apply_batch(
+        &self,
+        batch: Batch<'_>,
+    ) -> Result<Vec<ModificationResult<Root::Index>>, CompareAndSwapError> {
+        let transaction = self.begin_transaction()?;
+        let results = transaction.tree::<Root>(0).unwrap().apply_batch(batch)?;
+        transaction.commit()?;
+        Ok(results)
+    }
+
+    /// Retrieves the values of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Keys are required to be pre-sorted. Use
+    /// [`Self::get_multiple_unsorted()`] to retrieve the values in the same
+    /// order as `keys`.
     #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple_by_sequence<Sequences>(
-        &mut self,
-        sequences: Sequences,
-    ) -> Result<HashMap<SequenceId, (ArcBytes<'static>, Option<ArcBytes<'static>>)>, Error>
+    pub fn get_multiple<'keys, Keys>(
+        &self,
+        keys: Keys,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
     where
-        Sequences: Iterator<Item = SequenceId> + Clone,
+        Keys: Iterator<Item = &'keys [u8]> + ExactSizeIterator + Clone,
     {
         catch_compaction_and_retry(|| {
-            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
-                self.path(),
-                self.state.clone(),
-                self.roots.context(),
-                Some(self.roots.transactions()),
-            )?;
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
 
-            tree.get_multiple_by_sequence(sequences.clone(), false)
+            tree.get_multiple(keys.clone(), false)
         })
     }
 
-    /// Retrieves the keys and indexes associated with one or more `sequences`.
-    /// The value retrieved is the value of the key at the given [`SequenceId`].
-    /// If a sequence is not found, it will not appear in the result list.
+    /// Retrieves the values of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Unlike
+    /// [`get_multiple()`](Self::get_multiple), keys do not need to be sorted.
+    /// See [`TreeFile::get_set()`] for more information.
     #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple_indexes_by_sequence<Sequences>(
-        &mut self,
-        sequences: Sequences,
-    ) -> Result<Vec<SequenceIndex<Index>>, Error>
+    pub fn get_set<'keys, Keys>(
+        &self,
+        keys: Keys,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
     where
-        Sequences: Iterator<Item = SequenceId> + Clone,
+        Keys: IntoIterator<Item = &'keys [u8]> + Clone,
     {
         catch_compaction_and_retry(|| {
-            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
-                self.path(),
-                self.state.clone(),
-                self.roots.context(),
-                Some(self.roots.transactions()),
-            )?;
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
 
-            tree.get_multiple_indexes_by_sequence(sequences.clone(), false)
+            tree.get_set(keys.clone(), false)
         })
     }
 
-    /// Retrieves the keys, values, and indexes associated with one or more
-    /// `sequences`. The value retrieved is the value of the key at the given
-    /// [`SequenceId`]. If a sequence is not found, it will not appear in the
-    /// result list.
+    /// Retrieves the values of `keys`, which do not need to be sorted or
+    /// unique. Returns one entry for each key in `keys`, in the same order,
+    /// containing the key's value or `None` if the key wasn't found.
     #[allow(clippy::needless_pass_by_value)]
-    pub fn get_multiple_with_indexes_by_sequence<Sequences>(
-        &mut self,
-        sequences: Sequences,
-    ) -> Result<HashMap<SequenceId, SequenceEntry<Index>>, Error>
+    pub fn get_multiple_unsorted<'keys, Keys>(
+        &self,
+        keys: Keys,
+    ) -> Result<Vec<Option<ArcBytes<'static>>>, Error>
     where
-        Sequences: Iterator<Item = SequenceId> + Clone,
+        Keys: IntoIterator<Item = &'keys [u8]> + Clone,
     {
         catch_compaction_and_retry(|| {
-            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
-                self.path(),
-                self.state.clone(),
-                self.roots.context(),
-                Some(self.roots.transactions()),
-            )?;
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => {
+                    return Ok(keys.clone().into_iter().map(|_| None).collect())
+                }
+                Err(err) => return Err(err),
+            };
 
-            tree.get_multiple_with_indexes_by_sequence(sequences.clone(), false)
+            tree.get_multiple_unsorted(keys.clone(), false)
         })
     }
-}
 
-/// An error that could come from user code or Nebari.
-#[derive(thiserror::Error, Debug)]
-pub enum AbortError<CallerError: Display + Debug = Infallible> {
-    /// An error unrelated to Nebari occurred.
-    #[error("other error: {0}")]
-    Other(CallerError),
-    /// An error from Roots occurred.
-    #[error("database error: {0}")]
-    Nebari(#[from] Error),
-}
+    /// Retrieves the values of `keys`, which do not need to be sorted or
+    /// unique. Returns one key/value pair for each key in `keys`, in the same
+    /// order, using `default` as the value of each key that wasn't found. See
+    /// [`TreeFile::get_multiple_or()`] for more information.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_or<'keys, Keys>(
+        &self,
+        keys: Keys,
+        default: &ArcBytes<'static>,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        Keys: IntoIterator<Item = &'keys [u8]> + Clone,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => {
+                    return Ok(keys
+                        .clone()
+                        .into_iter()
+                        .map(|key| (ArcBytes::from(key.to_vec()), default.clone()))
+                        .collect())
+                }
+                Err(err) => return Err(err),
+            };
+
+            tree.get_multiple_or(keys.clone(), default, false)
+        })
+    }
+
+    /// Retrieves the indexes of `keys`. If any keys are not found, they will be
+    /// omitted from the results. Keys are required to be pre-sorted.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_indexes<'keys, KeysIntoIter, KeysIter>(
+        &self,
+        keys: KeysIntoIter,
+    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
+    where
+        KeysIntoIter: IntoIterator<Item = &'keys [u8], IntoIter = KeysIter> + Clone,
+        KeysIter: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_multiple_indexes(keys.clone(), false)
+        })
+    }
+
+    /// Retrieves the values and indexes of `keys`. If any keys are not found,
+    /// they will be omitted from the results. Keys are required to be
+    /// pre-sorted.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_with_indexes<'keys, KeysIntoIter, KeysIter>(
+        &self,
+        keys: KeysIntoIter,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>, Root::Index)>, Error>
+    where
+        KeysIntoIter: IntoIterator<Item = &'keys [u8], IntoIter = KeysIter> + Clone,
+        KeysIter: Iterator<Item = &'keys [u8]> + ExactSizeIterator,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_multiple_with_indexes(keys.clone(), false)
+        })
+    }
+
+    /// Retrieves all of the values of keys within `range`.
+    pub fn get_range<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_range(range, false)
+        })
+    }
+
+    /// Retrieves a page of up to `limit` keys and values within `range`,
+    /// starting after `after` if provided. The returned cursor can be passed
+    /// to a later call to retrieve the next page, and is `None` once the end
+    /// of `range` has been reached. See [`TreeFile::get_page()`] for more
+    /// information. Does not reflect any changes in pending transactions.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `limit` is 0.
+    pub fn get_page<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        after: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<
+        (
+            Vec<(ArcBytes<'static>, ArcBytes<'static>)>,
+            Option<ScanCursor>,
+        ),
+        Error,
+    >
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok((Vec::new(), None)),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_page(range, after, limit, false)
+        })
+    }
+
+    /// Retrieves up to `limit` keys and values within `range`, and whether
+    /// `range` contains more keys than were returned. See
+    /// [`TreeFile::get_range_limited()`] for more information. Does not
+    /// reflect any changes in pending transactions.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `limit` is 0.
+    pub fn get_range_limited<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        limit: usize,
+    ) -> Result<(Vec<(ArcBytes<'static>, ArcBytes<'static>)>, bool), Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok((Vec::new(), false)),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_range_limited(range, limit, false)
+        })
+    }
+
+    /// Returns an iterator over the keys and values within `range`. See
+    /// [`TreeFile::range_iter()`] for more information. Does not reflect any
+    /// changes in pending transactions.
+    ///
+    /// The iterator continues reading from the compacted file if the tree is
+    /// compacted while iterating.
+    pub fn range_iter<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+    ) -> Result<RangeIter<Root, File, TreeFile<Root, File>>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + ?Sized,
+    {
+        self.check_vault_generation()?;
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(self.roots.context()),
+            |vault| Cow::Owned(self.roots.context().clone().with_any_vault(vault.clone())),
+        );
+        let tree = match TreeFile::<Root, File>::read_following_compaction(
+            self.path(),
+            self.state.clone(),
+            &context,
+            Some(self.roots.transactions()),
+        ) {
+            Ok(tree) => Some(tree),
+            Err(err) if err.kind.is_file_not_found() => None,
+            Err(err) => return Err(err),
+        };
+        Ok(RangeIter::new(tree, range, forwards, false))
+    }
+
+    /// Returns an iterator over all keys and values in the tree, in ascending
+    /// order.
+    ///
+    /// ```rust
+    /// # use nebari::{Config, tree::{Root, Versioned}};
+    /// # let tempdir = tempfile::tempdir().unwrap();
+    /// let roots = Config::new(tempdir.path()).open().unwrap();
+    /// let tree = roots.tree(Versioned::tree("a")).unwrap();
+    /// tree.set(b"a", b"1").unwrap();
+    /// tree.set(b"b", b"2").unwrap();
+    ///
+    /// for entry in tree.iter().unwrap() {
+    ///     let (key, value) = entry.unwrap();
+    ///     println!("{:?} = {:?}", key, value);
+    /// }
+    /// ```
+    ///
+    /// See [`Self::iter_range()`] for more information.
+    pub fn iter(&self) -> Result<TreeIter<Root, File>, Error> {
+        self.iter_range(&(..))
+    }
+
+    /// Returns an iterator over the keys and values within `range`, in
+    /// ascending order. Does not reflect any changes in pending transactions.
+    ///
+    /// The iterator reads from the tree as it was when this function was
+    /// called. Changes made while iterating aren't observed. Entries are read
+    /// lazily in small batches, so ranges of any size can be iterated using a
+    /// bounded amount of memory. If the tree is compacted while iterating, the
+    /// iterator returns [`TreeCompacted`](ErrorKind::TreeCompacted).
+    pub fn iter_range<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<TreeIter<Root, File>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + ?Sized,
+    {
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(self.roots.context()),
+            |vault| Cow::Owned(self.roots.context().clone().with_any_vault(vault.clone())),
+        );
+        // Initialize the shared state before copying it.
+        let tree = match self.open_for_read() {
+            Ok(tree) => tree,
+            Err(err) if err.kind.is_file_not_found() => {
+                return Ok(RangeIter::new(None, range, true, false))
+            }
+            Err(err) => return Err(err),
+        };
+        drop(tree);
+        let tree = TreeFile::<Root, File>::read(
+            self.path(),
+            self.state.detached(),
+            &context,
+            Some(self.roots.transactions()),
+        )?;
+        Ok(RangeIter::new(Some(tree), range, true, false))
+    }
+
+    /// Retrieves all of the indexes of keys within `range`.
+    pub fn get_range_indexes<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Vec<(ArcBytes<'static>, Root::Index)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_range_indexes(range, false)
+        })
+    }
+
+    /// Retrieves all of the values and indexes of keys within `range`.
+    pub fn get_range_with_indexes<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>, Root::Index)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_range_with_indexes(range, false)
+        })
+    }
+
+    /// Scans the tree across all nodes that might contain nodes within `range`.
+    ///
+    /// If `forwards` is true, the tree is scanned in ascending order.
+    /// Otherwise, the tree is scanned in descending order.
+    ///
+    /// `node_evaluator` is invoked for each [`Interior`](crate::tree::Interior) node to determine if
+    /// the node should be traversed. The parameters to the callback are:
+    ///
+    /// - `&ArcBytes<'static>`: The maximum key stored within the all children
+    ///   nodes.
+    /// - `&Root::ReducedIndex`: The reduced index value stored within the node.
+    /// - `usize`: The depth of the node. The root nodes are depth 0.
+    ///
+    /// The result of the callback is a [`ScanEvaluation`]. To read children
+    /// nodes, return [`ScanEvaluation::ReadData`].
+    ///
+    /// `key_evaluator` is invoked for each key encountered that is contained
+    /// within `range`. For all [`ScanEvaluation::ReadData`] results returned,
+    /// `callback` will be invoked with the key and values. `callback` may not
+    /// be invoked in the same order as the keys are scanned.
+    ///
+    /// Cloning a key in `key_evaluator` only increments a reference count.
+    /// Unless the key's leaf node was written with [key
+    /// compression](Config::key_compression), the key also shares the buffer
+    /// the node was loaded from rather than being copied.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self, node_evaluator, key_evaluator, callback))
+    )]
+    pub fn scan<'keys, CallerError, KeyRangeBounds, NodeEvaluator, KeyEvaluator, DataCallback>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        catch_compaction_and_retry_abortable(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(()),
+                Err(err) => return Err(AbortError::from(err)),
+            };
+
+            tree.scan(
+                range,
+                forwards,
+                false,
+                &mut node_evaluator,
+                &mut key_evaluator,
+                &mut callback,
+            )
+        })
+    }
+
+    /// Scans the tree for keys that start with `prefix`. See
+    /// [`TreeFile::scan_prefix()`] for more information. Does not reflect any
+    /// changes in pending transactions.
+    pub fn scan_prefix<CallerError, NodeEvaluator, KeyEvaluator, DataCallback>(
+        &self,
+        prefix: &[u8],
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        catch_compaction_and_retry_abortable(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(()),
+                Err(err) => return Err(AbortError::from(err)),
+            };
+
+            tree.scan_prefix(
+                prefix,
+                forwards,
+                false,
+                &mut node_evaluator,
+                &mut key_evaluator,
+                &mut callback,
+            )
+        })
+    }
+
+    /// Scans `range`, reading the values of only the keys whose index matches
+    /// `predicate`. See [`TreeFile::scan_index_filter()`] for more
+    /// information. Does not reflect any changes in pending transactions.
+    pub fn scan_index_filter<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        Predicate,
+        DataCallback,
+    >(
+        &self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        node_evaluator: NodeEvaluator,
+        mut predicate: Predicate,
+        callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        Predicate: FnMut(&Root::Index) -> bool,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        self.scan(
+            range,
+            forwards,
+            node_evaluator,
+            |_, index| {
+                if predicate(index) {
+                    ScanEvaluation::ReadData
+                } else {
+                    ScanEvaluation::Skip
+                }
+            },
+            callback,
+        )
+    }
+
+    /// Returns a stream of the key-value pairs within `range`, in ascending
+    /// order. Does not reflect any changes in pending transactions.
+    ///
+    /// The range is scanned on a background thread, which reads at most
+    /// `capacity` entries ahead of the consumer of the stream. Dropping the
+    /// stream stops the scan.
+    #[cfg(feature = "async")]
+    pub fn stream_range<Range>(&self, range: Range, capacity: usize) -> crate::RangeStream
+    where
+        Range: for<'a> tree::BorrowByteRange<'a> + Send + 'static,
+    {
+        let (sender, receiver) = flume::bounded(capacity);
+        let tree = self.clone();
+        std::thread::spawn(move || {
+            let result = tree.scan::<&'static str, _, _, _, _>(
+                &range.borrow_as_bytes(),
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |_, _| {
+                    if sender.is_disconnected() {
+                        ScanEvaluation::Stop
+                    } else {
+                        ScanEvaluation::ReadData
+                    }
+                },
+                |key, _index, value| {
+                    sender
+                        .send(Ok((key, value)))
+                        .map_err(|_| AbortError::Other("stream dropped"))
+                },
+            );
+            if let Err(AbortError::Nebari(err)) = result {
+                drop(sender.send(Err(err)));
+            }
+        });
+        crate::RangeStream::new(receiver)
+    }
+
+    /// Scans the tree like [`Self::scan()`], stopping with
+    /// [`ErrorKind::Cancelled`] once `cancellation` is cancelled. See
+    /// [`TreeFile::scan_cancellable()`] for more information.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_cancellable<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        KeyEvaluator,
+        DataCallback,
+    >(
+        &self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        cancellation: &CancellationToken,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        catch_compaction_and_retry_abortable(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(()),
+                Err(err) => return Err(AbortError::from(err)),
+            };
+
+            tree.scan_cancellable(
+                range,
+                forwards,
+                false,
+                cancellation,
+                &mut node_evaluator,
+                &mut key_evaluator,
+                &mut callback,
+            )
+        })
+    }
+
+    /// Scans `range` in parallel, splitting it into up to `parallelism`
+    /// sub-ranges that are each scanned on the database's [`ThreadPool`]. If
+    /// `parallelism` is `None`, the pool's maximum number of threads is used.
+    ///
+    /// The sub-ranges are split at the keys separating the children of the
+    /// tree's root node, so small trees may be scanned using fewer threads
+    /// than requested. Every key in `range` belongs to exactly one sub-range.
+    /// `key_evaluator` and `callback` are called concurrently from multiple
+    /// threads, and keys are visited in ascending order only within each
+    /// sub-range. Returning [`ScanEvaluation::Stop`] stops scanning the
+    /// current sub-range.
+    ///
+    /// Each sub-range is scanned independently, so changes committed while the
+    /// scan is in progress may be visible in some sub-ranges but not others.
+    /// If a sub-range's scan fails, the remaining sub-ranges stop scanning and
+    /// the first error is returned.
+    pub fn par_scan<'keys, CallerError, KeyRangeBounds, KeyEvaluator, DataCallback>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        parallelism: Option<usize>,
+        key_evaluator: KeyEvaluator,
+        callback: DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        KeyEvaluator:
+            Fn(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation + Send + Sync + 'static,
+        DataCallback: Fn(
+                ArcBytes<'static>,
+                &Root::Index,
+                ArcBytes<'static>,
+            ) -> Result<(), AbortError<CallerError>>
+            + Send
+            + Sync
+            + 'static,
+        CallerError: Display + Debug + Send + 'static,
+    {
+        let thread_pool = &self.roots.data.thread_pool;
+        let parallelism = parallelism.unwrap_or(thread_pool.maximum_threads).max(1);
+        let sub_ranges = self.split_range(
+            tree::owned_bound(range.start_bound()),
+            tree::owned_bound(range.end_bound()),
+            parallelism,
+        )?;
+
+        let key_evaluator = Arc::new(key_evaluator);
+        let callback = Arc::new(callback);
+        let failed = Arc::new(AtomicBool::new(false));
+        let (result_sender, result_receiver) = flume::unbounded();
+        let jobs = sub_ranges
+            .into_iter()
+            .map(|(start, end)| {
+                let tree = self.clone();
+                let key_evaluator = key_evaluator.clone();
+                let callback = callback.clone();
+                let failed = failed.clone();
+                let result_sender = result_sender.clone();
+                Box::new(move || {
+                    let result = tree.scan(
+                        &BorrowedRange {
+                            start: tree::borrowed_bound(&start),
+                            end: tree::borrowed_bound(&end),
+                        },
+                        true,
+                        |_, _, _| ScanEvaluation::ReadData,
+                        |key, index| {
+                            if failed.load(Ordering::Relaxed) {
+                                ScanEvaluation::Stop
+                            } else {
+                                key_evaluator(key, index)
+                            }
+                        },
+                        |key, index, value| callback(key, index, value),
+                    );
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    drop(result_sender.send(result));
+                }) as Box<dyn FnOnce() + Send>
+            })
+            .collect::<Vec<_>>();
+        drop(result_sender);
+        thread_pool.spawn_jobs(jobs)?;
+
+        let mut result = Ok(());
+        for sub_range_result in result_receiver.iter() {
+            if result.is_ok() {
+                result = sub_range_result;
+            }
+        }
+        result
+    }
+
+    /// Splits `start..end` into up to `parts` non-overlapping sub-ranges
+    /// using the maximum keys of the root node's children.
+    #[allow(clippy::type_complexity)]
+    fn split_range(
+        &self,
+        start: Bound<ArcBytes<'static>>,
+        end: Bound<ArcBytes<'static>>,
+        parts: usize,
+    ) -> Result<Vec<(Bound<ArcBytes<'static>>, Bound<ArcBytes<'static>>)>, Error> {
+        let mut separators = Vec::new();
+        if parts > 1 {
+            let range = BorrowedRange {
+                start: tree::borrowed_bound(&start),
+                end: tree::borrowed_bound(&end),
+            };
+            self.scan::<Infallible, _, _, _, _>(
+                &range,
+                true,
+                |max_key, _, depth| {
+                    if depth == 0 && range.contains(&max_key.as_slice()) {
+                        separators.push(max_key.clone());
+                    }
+                    ScanEvaluation::Skip
+                },
+                |_, _| ScanEvaluation::Skip,
+                |_, _, _| unreachable!(),
+            )
+            .map_err(AbortError::infallible)?;
+        }
+
+        // Pick evenly spaced separators when there are more than needed.
+        if separators.len() >= parts {
+            separators = (1..parts)
+                .map(|part| separators[part * separators.len() / parts].clone())
+                .collect();
+        }
+
+        let mut sub_ranges = Vec::with_capacity(separators.len() + 1);
+        let mut sub_range_start = start;
+        for separator in separators {
+            sub_ranges.push((sub_range_start, Bound::Included(separator.clone())));
+            sub_range_start = Bound::Excluded(separator);
+        }
+        sub_ranges.push((sub_range_start, end));
+        Ok(sub_ranges)
+    }
+
+    /// Returns the reduced index over the provided range. This is an
+    /// aggregation function that builds atop the `scan()` operation which calls
+    /// [`Reducer::reduce()`](crate::tree::Reducer::reduce) and
+    /// [`Reducer::rereduce()`](crate::tree::Reducer::rereduce) on all matching
+    /// indexes stored within the nodes of this tree, producing a single
+    /// aggregated [`Root::ReducedIndex`](tree::Root::ReducedIndex) value.
+    ///
+    /// If no keys match, the returned result is what
+    /// [`Reducer::rereduce()`](crate::tree::Reducer::rereduce) returns when an
+    /// empty slice is provided.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn reduce<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<Option<Root::ReducedIndex>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + Clone + ?Sized,
+    {
+        catch_compaction_and_retry(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.reduce(range, false)
+        })
+    }
+
+    /// Returns the reduced index over each range in `ranges`, scanning the
+    /// tree once. This is equivalent to calling [`Self::reduce()`] for each
+    /// range, but is more efficient when reducing many small ranges.
+    ///
+    /// `ranges` must be sorted and must not overlap. The result at each
+    /// position corresponds to the range at the same position of `ranges`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn reduce_multiple<'keys, KeyRangeBounds>(
+        &self,
+        ranges: &'keys [KeyRangeBounds],
+    ) -> Result<Vec<Option<Root::ReducedIndex>>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug,
+    {
+        catch_compaction_and_retry(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(vec![None; ranges.len()]),
+                Err(err) => return Err(err),
+            };
+
+            tree.reduce_multiple(ranges, false)
+        })
+    }
+
+    /// Returns the first key of the tree.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn first_key(&self) -> Result<Option<ArcBytes<'static>>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.first_key(false)
+        })
+    }
+
+    /// Returns the first key and value of the tree.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn first(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.first(false)
+        })
+    }
+
+    /// Returns the last key of the tree.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn last_key(&self) -> Result<Option<ArcBytes<'static>>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.last_key(false)
+        })
+    }
+
+    /// Returns the last key and value of the tree.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn last(&self) -> Result<Option<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.last(false)
+        })
+    }
+
+    /// Returns up to `n` of the tree's first keys and values, in ascending
+    /// order. Only the first `n` entries are read, regardless of the tree's
+    /// size.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn first_n(&self, n: usize) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.first_n(n, false)
+        })
+    }
+
+    /// Returns up to `n` of the tree's last keys and values, in descending
+    /// order. Only the last `n` entries are read, regardless of the tree's
+    /// size.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub fn last_n(&self, n: usize) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.last_n(n, false)
+        })
+    }
+
+    /// Rewrites the database to remove data that is no longer current. Because
+    /// Nebari uses an append-only format, this is helpful in reducing disk
+    /// usage.
+    ///
+    /// If another compaction of this tree is in progress, this function waits
+    /// for it to complete rather than compacting the tree again.
+    ///
+    /// See [`TreeFile::compact()`](crate::tree::TreeFile::compact) for more
+    /// information.
+    pub fn compact(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
+        if self.state.wait_for_compaction() {
+            return Ok(());
+        }
+        let tree = match self.open_for_read() {
+            Ok(tree) => tree,
+            Err(err) if err.kind.is_file_not_found() => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        tree.compact(
+            &self.roots.context().file_manager,
+            Some(TransactableCompaction {
+                name: self.name.as_ref(),
+                manager: self.roots.transactions(),
+            }),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Rewrites the database like [`Self::compact()`], decrypting its chunks
+    /// using `old` and re-encrypting them using `new`. Passing `None` for
+    /// `old` reads unencrypted chunks, and passing `None` for `new` stores the
+    /// chunks without encryption.
+    ///
+    /// After this function returns, this tree's data can only be read using
+    /// `new`. This tree, its clones, and every other handle opened before the
+    /// rotation return [`VaultMismatch`](ErrorKind::VaultMismatch) when they
+    /// are used to read or modify the tree. The tree must be opened again
+    /// using a [`TreeRoot`] configured with `new` before it is accessed.
+    ///
+    /// See [`TreeFile::rotate_vault()`](crate::tree::TreeFile::rotate_vault)
+    /// for more information.
+    pub fn rotate_vault(
+        &self,
+        old: Option<Arc<dyn AnyVault>>,
+        new: Option<Arc<dyn AnyVault>>,
+    ) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
+        let mut context = self.roots.context().clone();
+        context.vault = old;
+        let tree = match TreeFile::<Root, File>::read(
+            self.path(),
+            self.state.clone(),
+            &context,
+            Some(self.roots.transactions()),
+        ) {
+            Ok(tree) => tree,
+            Err(err) if err.kind.is_file_not_found() => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        tree.rotate_vault(
+            &context.file_manager,
+            Some(TransactableCompaction {
+                name: self.name.as_ref(),
+                manager: self.roots.transactions(),
+            }),
+            new,
+        )?;
+        Ok(())
+    }
+}
+
+impl<Root: tree::Root, File: ManagedFile> AnyTreeRoot<File> for Tree<Root, File> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn default_state(&self) -> Box<dyn AnyTreeState> {
+        Box::new(State::<Root>::new(
+            None,
+            self.max_order,
+            Root::default_with(
+                self.reducer
+                    .as_ref()
+                    .as_any()
+                    .downcast_ref::<Root::Reducer>()
+                    .unwrap()
+                    .clone(),
+            ),
+        ))
+    }
+
+    fn begin_transaction(
+        &self,
+        transaction_id: TransactionId,
+        file_path: &Path,
+        state: &dyn AnyTreeState,
+        context: &Context<File::Manager>,
+        transactions: Option<&TransactionManager<File::Manager>>,
+    ) -> Result<Box<dyn AnyTransactionTree<File>>, Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
+        self.check_vault_generation()?;
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(context),
+            |vault| Cow::Owned(context.clone().with_any_vault(vault.clone())),
+        );
+        let tree = TreeFile::write(
+            file_path,
+            state
+                .as_any()
+                .downcast_ref::<State<Root>>()
+                .unwrap()
+                .clone(),
+            &context,
+            transactions,
+        )?;
+
+        Ok(Box::new(TransactionTree {
+            transaction_id,
+            tree,
+        }))
+    }
+
+    fn initialize_state(
+        &self,
+        file_path: &Path,
+        state: &dyn AnyTreeState,
+        context: &Context<File::Manager>,
+        transactions: Option<&TransactionManager<File::Manager>>,
+    ) -> Result<(), Error> {
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(context),
+            |vault| Cow::Owned(context.clone().with_any_vault(vault.clone())),
+        );
+        TreeFile::<Root, File>::read(
+            file_path,
+            state
+                .as_any()
+                .downcast_ref::<State<Root>>()
+                .unwrap()
+                .clone(),
+            &context,
+            transactions,
+        )?;
+        Ok(())
+    }
+
+    fn vault(&self) -> Option<&Arc<dyn AnyVault>> {
+        self.vault.as_ref()
+    }
+}
+
+impl<File: ManagedFile, Index> Tree<VersionedTreeRoot<Index>, File>
+where
+    Index: EmbeddedIndex + Clone + Debug + 'static,
+{
+    /// Returns the latest sequence id.
+    #[must_use]
+    pub fn current_sequence_id(&self) -> SequenceId {
+        let state = self.state.lock();
+        state.root.sequence
+    }
+
+    /// Returns the first and last sequence ids stored in the tree, or None if
+    /// no sequences are stored. Compacting the tree removes the sequences of
+    /// old versions, which moves the first sequence forward. A follower whose
+    /// last processed sequence is older than the first sequence can no longer
+    /// catch up using [`Self::changes_after()`].
+    pub fn sequence_range(&self) -> Result<Option<(SequenceId, SequenceId)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.sequence_range(false)
+        })
+    }
+
+    /// Removes every key from the tree and resets its sequence. See
+    /// [`TreeFile::clear_and_reset_sequence()`] for more information. This is
+    /// executed within its own transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn clear_and_reset_sequence(&self) -> Result<(), Error> {
+        let transaction = self.begin_transaction()?;
+        transaction
+            .tree::<VersionedTreeRoot<Index>>(0)
+            .unwrap()
+            .clear_and_reset_sequence()?;
+        transaction.commit()
+    }
+
+    /// Scans the tree for keys that are contained within `range`. If `forwards`
+    /// is true, scanning starts at the lowest sort-order key and scans forward.
+    /// Otherwise, scanning starts at the highest sort-order key and scans
+    /// backwards. `key_evaluator` is invoked for each key as it is encountered.
+    /// For all [`ScanEvaluation::ReadData`] results returned, `callback` will be
+    /// invoked with the key and values. The callback may not be invoked in the
+    /// same order as the keys are scanned.
+    pub fn scan_sequences<CallerError, Range, KeyEvaluator, DataCallback>(
+        &self,
+        range: Range,
+        forwards: bool,
+        key_evaluator: &mut KeyEvaluator,
+        data_callback: &mut DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        Range: Clone + RangeBounds<SequenceId> + Debug + 'static,
+        KeyEvaluator: FnMut(KeySequence<Index>) -> ScanEvaluation,
+        DataCallback:
+            FnMut(KeySequence<Index>, ArcBytes<'static>) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        catch_compaction_and_retry_abortable(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.scan_sequences(range.clone(), forwards, false, key_evaluator, data_callback)
+        })
+    }
+
+    /// Scans the sequences recorded after `sequence` in ascending order,
+    /// excluding `sequence` itself. A follower of this tree's changes can pass
+    /// the last sequence it processed to resume without receiving it again.
+    /// See [`TreeFile::changes_after()`] for more information.
+    pub fn changes_after<CallerError, KeyEvaluator, DataCallback>(
+        &self,
+        sequence: SequenceId,
+        key_evaluator: &mut KeyEvaluator,
+        data_callback: &mut DataCallback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyEvaluator: FnMut(KeySequence<Index>) -> ScanEvaluation,
+        DataCallback:
+            FnMut(KeySequence<Index>, ArcBytes<'static>) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        catch_compaction_and_retry_abortable(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.changes_after(sequence, false, key_evaluator, data_callback)
+        })
+    }
+
+    /// Invokes `callback` for each change recorded after `sequence`, in
+    /// ascending order. Removals are included, with `None` passed as the
+    /// value. Replaying the changes in order brings a copy of this tree up to
+    /// date, which allows backing up or replicating only the changes made
+    /// since the last sequence processed. See
+    /// [`TreeFile::export_changes_after()`] for more information.
+    ///
+    /// If the tree is compacted while exporting, the export resumes after the
+    /// last change passed to `callback`.
+    pub fn export_changes_after<CallerError, Callback>(
+        &self,
+        sequence: SequenceId,
+        callback: &mut Callback,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        Callback: FnMut(
+            KeySequence<Index>,
+            Option<ArcBytes<'static>>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+    {
+        let mut last_exported = sequence;
+        catch_compaction_and_retry_abortable(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.export_changes_after(last_exported, false, &mut |key, value| {
+                let sequence = key.sequence;
+                callback(key, value)?;
+                last_exported = sequence;
+                Ok(())
+            })
+        })
+    }
+
+    /// Retrieves the keys and values associated with one or more `sequences`.
+    /// The value retrieved is the value of the key at the given [`SequenceId`].
+    /// If a sequence is not found, it will not appear in the result map. If
+    /// the value was removed, None is returned for the value.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_by_sequence<Sequences>(
+        &mut self,
+        sequences: Sequences,
+    ) -> Result<HashMap<SequenceId, (ArcBytes<'static>, Option<ArcBytes<'static>>)>, Error>
+    where
+        Sequences: Iterator<Item = SequenceId> + Clone,
+    {
+        catch_compaction_and_retry(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.get_multiple_by_sequence(sequences.clone(), false)
+        })
+    }
+
+    /// Retrieves the keys and indexes associated with one or more `sequences`.
+    /// The value retrieved is the value of the key at the given [`SequenceId`].
+    /// If a sequence is not found, it will not appear in the result list.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_indexes_by_sequence<Sequences>(
+        &mut self,
+        sequences: Sequences,
+    ) -> Result<Vec<SequenceIndex<Index>>, Error>
+    where
+        Sequences: Iterator<Item = SequenceId> + Clone,
+    {
+        catch_compaction_and_retry(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.get_multiple_indexes_by_sequence(sequences.clone(), false)
+        })
+    }
+
+    /// Retrieves the keys, values, and indexes associated with one or more
+    /// `sequences`. The value retrieved is the value of the key at the given
+    /// [`SequenceId`]. If a sequence is not found, it will not appear in the
+    /// result list.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_multiple_with_indexes_by_sequence<Sequences>(
+        &mut self,
+        sequences: Sequences,
+    ) -> Result<HashMap<SequenceId, SequenceEntry<Index>>, Error>
+    where
+        Sequences: Iterator<Item = SequenceId> + Clone,
+    {
+        catch_compaction_and_retry(|| {
+            self.check_vault_generation()?;
+            let mut tree = TreeFile::<VersionedTreeRoot<Index>, File>::read(
+                self.path(),
+                self.state.clone(),
+                self.roots.context(),
+                Some(self.roots.transactions()),
+            )?;
+
+            tree.get_multiple_with_indexes_by_sequence(sequences.clone(), false)
+        })
+    }
+
+    /// Compares the [`SequenceId`] of the latest version of `key` against
+    /// `expected`, where `None` indicates that the key must not have a value.
+    /// If the sequences match, key will be set to the new value if `new` is
+    /// `Some` or removed if `new` is `None`. This is executed within its own
+    /// transaction.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn compare_and_swap_sequence(
+        &self,
+        key: &[u8],
+        expected: Option<SequenceId>,
+        new: Option<ArcBytes<'_>>,
+    ) -> Result<(), CompareAndSwapSequenceError> {
+        let transaction = self.begin_transaction()?;
+        transaction
+            .tree::<VersionedTreeRoot<Index>>(0)
+            .unwrap()
+            .compare_and_swap_sequence(key, expected, new)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Retrieves the current value of `key` along with the [`SequenceId`] of
+    /// its latest version, if present. Does not reflect any changes in pending
+    /// transactions.
+    pub fn get_with_sequence(
+        &self,
+        key: &[u8],
+    ) -> Result<Option<(ArcBytes<'static>, SequenceId)>, Error> {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            tree.get_with_sequence(key, false)
+        })
+    }
+}
+
+impl<Root, File, EmbeddedStats> Tree<Root, File>
+where
+    Root: tree::Root<ReducedIndex = ByIdStats<EmbeddedStats>>,
+    File: ManagedFile,
+{
+    /// Returns an estimate of the number of keys contained within `range`.
+    ///
+    /// Only the nodes along the boundaries of `range` are visited, making this
+    /// much cheaper than counting the keys. Partially contained leaf nodes are
+    /// estimated by interpolating within their key space, which bounds the
+    /// error of the estimate to twice the tree's order (at most 2,000 keys by
+    /// default). See [`TreeFile::estimate_range_size()`] for more information.
+    /// Does not reflect any changes in pending transactions.
+    pub fn estimate_range_size<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(0),
+                Err(err) => return Err(err),
+            };
+
+            tree.estimate_range_size(range, false)
+        })
+    }
+
+    /// Returns the number of keys contained within `range` without reading
+    /// any values. See [`TreeFile::count_range()`] for more information. Does
+    /// not reflect any changes in pending transactions.
+    pub fn count_range<'keys, KeyRangeBounds>(
+        &self,
+        range: &'keys KeyRangeBounds,
+    ) -> Result<u64, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(0),
+                Err(err) => return Err(err),
+            };
+
+            tree.count_range(range, false)
+        })
+    }
+
+    /// Counts the keys contained within `range`, grouped by the value
+    /// `group_fn` returns for each key. `group_fn` must map keys to groups
+    /// that are contiguous in key order, such as a key's prefix. See
+    /// [`TreeFile::scan_keys_reduce()`] for more information. Does not reflect
+    /// any changes in pending transactions.
+    pub fn scan_keys_reduce<'keys, KeyRangeBounds, G, GroupFn>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        group_fn: GroupFn,
+    ) -> Result<HashMap<G, u64>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        G: Eq + Hash,
+        GroupFn: Fn(&[u8]) -> G,
+    {
+        catch_compaction_and_retry(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(HashMap::new()),
+                Err(err) => return Err(err),
+            };
+
+            tree.scan_keys_reduce(range, &group_fn, false)
+        })
+    }
+
+    /// Scans the tree across all nodes that might contain nodes within
+    /// `range`, reporting the approximate progress of the scan to `progress`.
+    /// See [`TreeFile::scan_with_progress()`] for more information. Does not
+    /// reflect any changes in pending transactions.
+    #[allow(clippy::too_many_arguments)]
+    pub fn scan_with_progress<
+        'keys,
+        CallerError,
+        KeyRangeBounds,
+        NodeEvaluator,
+        KeyEvaluator,
+        DataCallback,
+        Progress,
+    >(
+        &self,
+        range: &'keys KeyRangeBounds,
+        forwards: bool,
+        mut node_evaluator: NodeEvaluator,
+        mut key_evaluator: KeyEvaluator,
+        mut callback: DataCallback,
+        mut progress: Progress,
+    ) -> Result<(), AbortError<CallerError>>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        NodeEvaluator: FnMut(&ArcBytes<'static>, &Root::ReducedIndex, usize) -> ScanEvaluation,
+        KeyEvaluator: FnMut(&ArcBytes<'static>, &Root::Index) -> ScanEvaluation,
+        DataCallback: FnMut(
+            ArcBytes<'static>,
+            &Root::Index,
+            ArcBytes<'static>,
+        ) -> Result<(), AbortError<CallerError>>,
+        CallerError: Display + Debug,
+        Progress: FnMut(ScanProgress),
+    {
+        catch_compaction_and_retry_abortable(move || {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(()),
+                Err(err) => return Err(AbortError::from(err)),
+            };
+
+            tree.scan_with_progress(
+                range,
+                forwards,
+                false,
+                &mut node_evaluator,
+                &mut key_evaluator,
+                &mut callback,
+                &mut progress,
+            )
+        })
+    }
+
+    /// Retrieves all of the values of keys within `range`, reporting the
+    /// approximate progress of the scan to `progress`. See
+    /// [`TreeFile::scan_with_progress()`] for more information.
+    pub fn get_range_with_progress<'keys, KeyRangeBounds, Progress>(
+        &self,
+        range: &'keys KeyRangeBounds,
+        mut progress: Progress,
+    ) -> Result<Vec<(ArcBytes<'static>, ArcBytes<'static>)>, Error>
+    where
+        KeyRangeBounds: RangeBounds<&'keys [u8]> + Debug + ?Sized,
+        Progress: FnMut(ScanProgress),
+    {
+        catch_compaction_and_retry_abortable(|| {
+            let mut tree = match self.open_for_read() {
+                Ok(tree) => tree,
+                Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+                Err(err) => return Err(AbortError::from(err)),
+            };
+
+            tree.get_range_with_progress(range, false, &mut progress)
+                .map_err(AbortError::from)
+        })
+        .map_err(AbortError::infallible)
+    }
+}
+
+/// An error that could come from user code or Nebari.
+#[derive(thiserror::Error, Debug)]
+pub enum AbortError<CallerError: Display + Debug = Infallible> {
+    /// An error unrelated to Nebari occurred.
+    #[error("other error: {0}")]
+    Other(CallerError),
+    /// An error from Roots occurred.
+    #[error("database error: {0}")]
+    Nebari(#[from] Error),
+}
+
+impl<CallerError: Display + Debug> AbortError<CallerError> {
+    /// Returns the caller's error, if this error originated from user code.
+    #[must_use]
+    pub fn into_caller(self) -> Option<CallerError> {
+        match self {
+            Self::Other(error) => Some(error),
+            Self::Nebari(_) => None,
+        }
+    }
+
+    /// Returns a reference to the caller's error, if this error originated
+    /// from user code.
+    #[must_use]
+    pub const fn as_caller(&self) -> Option<&CallerError> {
+        match self {
+            Self::Other(error) => Some(error),
+            Self::Nebari(_) => None,
+        }
+    }
+
+    /// Returns a reference to the Nebari error, if this error originated from
+    /// Nebari.
+    #[must_use]
+    pub const fn as_nebari(&self) -> Option<&Error> {
+        match self {
+            Self::Nebari(error) => Some(error),
+            Self::Other(_) => None,
+        }
+    }
+
+    /// Returns the Nebari error, if this error originated from Nebari.
+    #[must_use]
+    pub fn into_nebari(self) -> Option<Error> {
+        match self {
+            Self::Nebari(error) => Some(error),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl AbortError<Infallible> {
+    /// Unwraps the error contained within an infallible abort error.
+    #[must_use]
+    pub fn infallible(self) -> Error {
+        match self {
+            AbortError::Other(_) => unreachable!(),
+            AbortError::Nebari(error) => error,
+        }
+    }
+}
+
+/// A function that spawns a thread executing the provided function.
+pub type SpawnFn = dyn Fn(Box<dyn FnOnce() + Send>) + Send + Sync;
+
+#[derive(Clone)]
+struct ThreadSpawner(Arc<SpawnFn>);
+
+impl Debug for ThreadSpawner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ThreadSpawner").finish()
+    }
+}
+
+/// A thread pool that commits transactions to disk in parallel.
+#[derive(Debug)]
+pub struct ThreadPool<File>
+where
+    File: ManagedFile,
+{
+    sender: flume::Sender<ThreadJob<File>>,
+    receiver: flume::Receiver<ThreadJob<File>>,
+    thread_count: Arc<AtomicU16>,
+    maximum_threads: usize,
+    spawner: Option<ThreadSpawner>,
+}
+
+impl<File: ManagedFile> ThreadPool<File> {
+    /// Returns a thread pool that will spawn up to `maximum_threads` to process
+    /// file operations.
+    #[must_use]
+    pub fn new(maximum_threads: usize) -> Self {
+        let (sender, receiver) = flume::unbounded();
+        Self {
+            sender,
+            receiver,
+            thread_count: Arc::new(AtomicU16::new(0)),
+            maximum_threads,
+            spawner: None,
+        }
+    }
+
+    /// Spawns this pool's threads using `spawner` instead of
+    /// [`std::thread::Builder`]. `spawner` must execute the function it is
+    /// given on a new thread. This allows the threads to be created by the
+    /// host application's runtime, for example to install thread-local context.
+    #[must_use]
+    pub fn with_spawner<Spawner>(mut self, spawner: Spawner) -> Self
+    where
+        Spawner: Fn(Box<dyn FnOnce() + Send>) + Send + Sync + 'static,
+    {
+        self.spawner = Some(ThreadSpawner(Arc::new(spawner)));
+        self
+    }
+
+    fn commit_trees(
+        &self,
+        trees: Vec<UnlockedTransactionTree<File>>,
+    ) -> Result<Vec<Box<dyn AnyTransactionTree<File>>>, Error> {
+        // If we only have one tree, there's no reason to split IO across
+        // threads. If we have multiple trees, we should split even with one
+        // cpu: if one thread blocks, the other can continue executing.
+        if trees.len() == 1 {
+            let mut tree = trees.into_iter().next().unwrap().tree.into_inner();
+            tree.commit()?;
+            Ok(vec![tree])
+        } else {
+            // Push the trees so that any existing threads can begin processing the queue.
+            let (completion_sender, completion_receiver) = flume::unbounded();
+            let tree_count = trees.len();
+            for (index, tree) in trees.into_iter().enumerate() {
+                self.sender.send(ThreadJob::Commit(ThreadCommit {
+                    index,
+                    tree: tree.tree.into_inner(),
+                    completion_sender: completion_sender.clone(),
+                }))?;
+            }
+
+            // Scale the queue if needed.
+            self.ensure_threads(tree_count);
+
+            // Wait for our results, restoring the order the trees were
+            // provided in.
+            let mut results = Vec::with_capacity(tree_count);
+            for _ in 0..tree_count {
+                let (index, result) = completion_receiver.recv()?;
+                results.push((index, result?));
+            }
+            results.sort_unstable_by_key(|(index, _)| *index);
+
+            Ok(results.into_iter().map(|(_, tree)| tree).collect())
+        }
+    }
+
+    /// Executes `job` on one of this pool's threads.
+    fn spawn_job<Job: FnOnce() + Send + 'static>(&self, job: Job) -> Result<(), Error> {
+        self.sender.send(ThreadJob::Run(Box::new(job)))?;
+        self.ensure_threads(1);
+        Ok(())
+    }
+
+    /// Executes each of `jobs` on this pool's threads, scaling the pool so
+    /// that the jobs can run in parallel.
+    fn spawn_jobs(&self, jobs: Vec<Box<dyn FnOnce() + Send>>) -> Result<(), Error> {
+        let job_count = jobs.len();
+        for job in jobs {
+            self.sender.send(ThreadJob::Run(job))?;
+        }
+        self.ensure_threads(job_count);
+        Ok(())
+    }
+
+    /// Spawns a thread, separate from this pool's threads, that executes each
+    /// job sent to the returned sender in order. The thread exits once every
+    /// clone of the sender has been dropped.
+    fn spawn_worker(&self, name: &str) -> flume::Sender<Box<dyn FnOnce() + Send>> {
+        let (sender, receiver) = flume::unbounded::<Box<dyn FnOnce() + Send>>();
+        let run = move || {
+            while let Ok(job) = receiver.recv() {
+                job();
+            }
+        };
+        if let Some(ThreadSpawner(spawner)) = &self.spawner {
+            spawner(Box::new(run));
+        } else {
+            std::thread::Builder::new()
+                .name(String::from(name))
+                .spawn(run)
+                .unwrap();
+        }
+        sender
+    }
+
+    fn ensure_threads(&self, desired_threads: usize) {
+        let desired_threads = desired_threads.min(self.maximum_threads);
+        loop {
+            let thread_count = self.thread_count.load(Ordering::SeqCst);
+            if (thread_count as usize) >= desired_threads {
+                break;
+            }
+
+            // Spawn a thread, but ensure that we don't spin up too many threads if another thread is committing at the same time.
+            if self
+                .thread_count
+                .compare_exchange(
+                    thread_count,
+                    thread_count + 1,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                let commit_receiver = self.receiver.clone();
+                if let Some(ThreadSpawner(spawner)) = &self.spawner {
+                    spawner(Box::new(move || transaction_commit_thread(commit_receiver)));
+                } else {
+                    std::thread::Builder::new()
+                        .name(String::from("roots-txwriter"))
+                        .spawn(move || transaction_commit_thread(commit_receiver))
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl<File: ManagedFile> Clone for ThreadPool<File> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            receiver: self.receiver.clone(),
+            thread_count: self.thread_count.clone(),
+            maximum_threads: self.maximum_threads,
+            spawner: self.spawner.clone(),
+        }
+    }
+}
+
+impl<File: ManagedFile> Default for ThreadPool<File> {
+    fn default() -> Self {
+        static CPU_COUNT: Lazy<usize> = Lazy::new(num_cpus::get);
+        Self::new(*CPU_COUNT)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn transaction_commit_thread<File: ManagedFile>(receiver: flume::Receiver<ThreadJob<File>>) {
+    while let Ok(job) = receiver.recv() {
+        match job {
+            ThreadJob::Commit(ThreadCommit {
+                index,
+                mut tree,
+                completion_sender,
+            }) => {
+                let result = tree.commit();
+                let result = result.map(move |_| tree);
+                drop(completion_sender.send((index, result)));
+            }
+            ThreadJob::Run(job) => job(),
+        }
+    }
+}
+
+enum ThreadJob<File>
+where
+    File: ManagedFile,
+{
+    Commit(ThreadCommit<File>),
+    Run(Box<dyn FnOnce() + Send>),
+}
+
+struct ThreadCommit<File>
+where
+    File: ManagedFile,
+{
+    index: usize,
+    tree: Box<dyn AnyTransactionTree<File>>,
+    completion_sender: Sender<(usize, Result<Box<dyn AnyTransactionTree<File>>, Error>)>,
+}
+
+fn tree_names<Manager: FileManager>(
+    file_manager: &Manager,
+    directory: &Path,
+    file_extension: &str,
+) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for name in file_manager.file_names(directory)? {
+        if let Some(without_extension) = name
+            .strip_suffix(file_extension)
+            .and_then(|name| name.strip_suffix('.'))
+        {
+            names.push(without_extension.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// The name of the file recording a swap of two trees' files that is in
+/// progress. It contains the names of both trees, separated by a newline.
+const SWAP_INTENT_FILE: &str = "_swap";
+/// The name of the file recording that every file of a swap has been moved
+/// aside. Once it exists, an interrupted swap is completed rather than undone.
+const SWAP_COMMIT_FILE: &str = "_swap.commit";
+
+/// Exchanges the files of two trees, including their manifests.
+///
+/// Each file is first moved to a temporary path, and then moved to its
+/// destination. Before the files are moved, the swap is recorded in
+/// [`SWAP_INTENT_FILE`], and once every file has been moved aside,
+/// [`SWAP_COMMIT_FILE`] is written. If the swap is interrupted,
+/// [`recover_tree_swap()`] uses these files to either undo or complete it, so
+/// that each tree always has all of its files.
+struct TreeFileSwap<'a, Manager: FileManager> {
+    file_manager: &'a Manager,
+    directory: &'a Path,
+    /// Each file being moved and its destination.
+    moves: [(PathBuf, PathBuf); 4],
+}
+
+impl<'a, Manager: FileManager> TreeFileSwap<'a, Manager> {
+    fn new(file_manager: &'a Manager, directory: &'a Path, a: &Path, b: &Path) -> Self {
+        Self {
+            file_manager,
+            directory,
+            moves: [
+                (a.to_path_buf(), b.to_path_buf()),
+                (tree::manifest_path(a), tree::manifest_path(b)),
+                (b.to_path_buf(), a.to_path_buf()),
+                (tree::manifest_path(b), tree::manifest_path(a)),
+            ],
+        }
+    }
+
+    fn temporary_path(path: &Path) -> PathBuf {
+        let mut temporary_path = path.as_os_str().to_os_string();
+        temporary_path.push(".swapping");
+        PathBuf::from(temporary_path)
+    }
+
+    /// Swaps the files of the trees named `a` and `b`. If the swap fails, the
+    /// files are returned to where they were.
+    fn run(&self, a: &str, b: &str) -> Result<(), Error> {
+        if let Err(err) = self.begin(a, b) {
+            // The original error is more useful than any error encountered
+            // while undoing the swap, which is retried when the database is
+            // next opened.
+            drop(self.undo(false));
+            return Err(err);
+        }
+        if let Err(err) = self.commit() {
+            drop(self.undo(true));
+            return Err(err);
+        }
+        self.finish()
+    }
+
+    /// Records the swap and moves each file to its temporary path.
+    fn begin(&self, a: &str, b: &str) -> Result<(), Error> {
+        write_swap_marker(
+            self.file_manager,
+            &self.directory.join(SWAP_INTENT_FILE),
+            format!("{}\n{}", a, b).as_bytes(),
+        )?;
+        for (source, _) in &self.moves {
+            if self.file_manager.exists(source)? {
+                self.file_manager
+                    .rename(source, Self::temporary_path(source))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records that every file has been moved aside, and moves each file to
+    /// its destination.
+    fn commit(&self) -> Result<(), Error> {
+        write_swap_marker(
+            self.file_manager,
+            &self.directory.join(SWAP_COMMIT_FILE),
+            b"",
+        )?;
+        self.move_to_destinations()
+    }
+
+    fn move_to_destinations(&self) -> Result<(), Error> {
+        for (source, destination) in &self.moves {
+            let temporary_path = Self::temporary_path(source);
+            if self.file_manager.exists(&temporary_path)? {
+                self.file_manager.rename(&temporary_path, destination)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns every file to its original path. If the swap was committed,
+    /// files that have already been moved to their destinations are moved
+    /// aside again first.
+    fn undo(&self, committed: bool) -> Result<(), Error> {
+        if committed {
+            // Every original file was moved aside before the swap was
+            // committed, so any file at a destination was moved there by the
+            // swap.
+            for (source, destination) in &self.moves {
+                let temporary_path = Self::temporary_path(source);
+                if !self.file_manager.exists(&temporary_path)?
+                    && self.file_manager.exists(destination)?
+                {
+                    self.file_manager.rename(destination, &temporary_path)?;
+                }
+            }
+            self.file_manager
+                .delete(self.directory.join(SWAP_COMMIT_FILE))?;
+        }
+        for (source, _) in &self.moves {
+            // A file that is still at its original path was never moved
+            // aside, so anything at its temporary path isn't part of the swap.
+            let temporary_path = Self::temporary_path(source);
+            if !self.file_manager.exists(source)? && self.file_manager.exists(&temporary_path)? {
+                self.file_manager.rename(&temporary_path, source)?;
+            }
+        }
+        self.file_manager
+            .delete(self.directory.join(SWAP_INTENT_FILE))?;
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), Error> {
+        self.file_manager
+            .delete(self.directory.join(SWAP_COMMIT_FILE))?;
+        self.file_manager
+            .delete(self.directory.join(SWAP_INTENT_FILE))?;
+        Ok(())
+    }
+}
+
+/// Undoes or completes a swap of two trees' files that was interrupted. See
+/// [`TreeFileSwap`].
+fn recover_tree_swap<Manager: FileManager>(
+    file_manager: &Manager,
+    directory: &Path,
+    file_extension: &str,
+) -> Result<(), Error> {
+    let intent_path = directory.join(SWAP_INTENT_FILE);
+    if !file_manager.exists(&intent_path)? {
+        return Ok(());
+    }
+    let contents = file_manager.read(&intent_path)?.execute(SwapMarkerReader)?;
+    let names = String::from_utf8(contents)
+        .map_err(|_| Error::data_integrity("invalid tree swap record"))?;
+    let (a, b) = match names.split_once('\n') {
+        Some(names) => names,
+        None => return Err(Error::data_integrity("invalid tree swap record")),
+    };
+    let a_path = directory.join(format!("{}.{}", a, file_extension));
+    let b_path = directory.join(format!("{}.{}", b, file_extension));
+    let swap = TreeFileSwap::new(file_manager, directory, &a_path, &b_path);
+    if file_manager.exists(directory.join(SWAP_COMMIT_FILE))? {
+        swap.move_to_destinations()?;
+        swap.finish()
+    } else {
+        swap.undo(false)
+    }
+}
+
+fn write_swap_marker<Manager: FileManager>(
+    file_manager: &Manager,
+    path: &Path,
+    contents: &[u8],
+) -> Result<(), Error> {
+    file_manager.delete(path)?;
+    file_manager
+        .append(path)?
+        .execute(SwapMarkerWriter { contents })
+}
+
+struct SwapMarkerWriter<'a> {
+    contents: &'a [u8],
+}
+
+impl FileOp<Result<(), Error>> for SwapMarkerWriter<'_> {
+    fn execute(self, file: &mut dyn io::File) -> Result<(), Error> {
+        use std::io::Write;
+
+        file.write_all(self.contents)?;
+        file.synchronize()
+    }
+}
+
+struct SwapMarkerReader;
+
+impl FileOp<Result<Vec<u8>, Error>> for SwapMarkerReader {
+    fn execute(self, file: &mut dyn io::File) -> Result<Vec<u8>, Error> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let length = usize::try_from(file.length()?)
+            .map_err(|_| Error::data_integrity("invalid tree swap record"))?;
+        let mut contents = vec![0; length];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+fn catch_compaction_and_retry<R, F: Fn() -> Result<R, Error>>(func: F) -> Result<R, Error> {
+    loop {
+        match func() {
+            Ok(result) => return Ok(result),
+            Err(error) => {
+                if error.kind.is_tree_compacted() {
+                    continue;
+                }
+
+                return Err(error);
+            }
+        }
+    }
+}
+
+fn catch_compaction_and_retry_abortable<
+    R,
+    E: Display + Debug,
+    F: FnMut() -> Result<R, AbortError<E>>,
+>(
+    mut func: F,
+) -> Result<R, AbortError<E>> {
+    loop {
+        match func() {
+            Ok(result) => return Ok(result),
+            Err(AbortError::Nebari(error)) => {
+                if error.kind.is_tree_compacted() {
+                    continue;
+                }
+
+                return Err(AbortError::Nebari(error));
+            }
+            Err(other) => return Err(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, ByteOrder};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        io::{
+            any::AnyFileManager,
+            fs::{StdFile, StdFileManager},
+            memory::{CapacityPolicy, MemoryFileManager},
+        },
+        testing::RotatorVault,
+        tree::{CompareSwap, KeyOperation, Root, Unversioned, Versioned},
+    };
+
+    fn basic_get_set<M: FileManager>(file_manager: M) {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path())
+            .file_manager(file_manager)
+            .open()
+            .unwrap();
+
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"test", b"value").unwrap();
+        let result = tree.get(b"test").unwrap().expect("key not found");
+
+        assert_eq!(result, b"value");
+    }
+
+    #[test]
+    fn memory_basic_get_set() {
+        basic_get_set(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn std_basic_get_set() {
+        basic_get_set(StdFileManager::default());
+    }
+
+    fn tree_exists<M: FileManager>(file_manager: M) {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path())
+            .file_manager(file_manager)
+            .open()
+            .unwrap();
+        assert!(!roots.tree_exists("test").unwrap());
+        // Checking doesn't create the tree.
+        assert!(!roots.tree_exists("test").unwrap());
+        assert!(matches!(
+            roots.tree_exists("=").unwrap_err().kind,
+            ErrorKind::InvalidTreeName
+        ));
+
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert!(roots.tree_exists("test").unwrap());
+        assert!(tree.exists().unwrap());
+
+        roots.delete_tree("test").unwrap();
+        assert!(!roots.tree_exists("test").unwrap());
+        assert!(!tree.exists().unwrap());
+    }
+
+    #[test]
+    fn memory_tree_exists() {
+        tree_exists(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn tree_read_only() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+
+        // Opening a missing tree doesn't create it.
+        let err = roots.tree_read_only(Versioned::tree("test")).unwrap_err();
+        assert!(err.kind.is_file_not_found());
+        assert!(!roots.tree_exists("test").unwrap());
+
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"key", b"value").unwrap();
+
+        let read_only = roots.tree_read_only(Versioned::tree("test")).unwrap();
+        assert!(read_only.is_read_only());
+        assert!(!tree.is_read_only());
+        assert_eq!(read_only.get(b"key").unwrap().unwrap(), b"value");
+        assert!(matches!(
+            read_only.set(b"key", b"other").unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            read_only.remove(b"key").unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            read_only.compact().unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            roots.transaction(&[read_only.clone()]).err().unwrap().kind,
+            ErrorKind::ReadOnly
+        ));
+
+        // Changes made through other handles are visible.
+        tree.set(b"key", b"other").unwrap();
+        assert_eq!(read_only.get(b"key").unwrap().unwrap(), b"other");
+    }
+
+    #[test]
+    fn read_only_database() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("db");
+
+        // Read-only databases are never created.
+        let err = Config::new(&path).read_only(true).open().unwrap_err();
+        assert!(err.kind.is_file_not_found());
+        assert!(!path.exists());
+
+        let roots = Config::new(&path).open().unwrap();
+        roots
+            .tree(Versioned::tree("test"))
+            .unwrap()
+            .set(b"key", b"value")
+            .unwrap();
+        drop(roots);
+        let files_before = std::fs::read_dir(&path).unwrap().count();
+
+        let roots = Config::new(&path).read_only(true).open().unwrap();
+        assert!(roots.is_read_only());
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert!(tree.is_read_only());
+        assert_eq!(tree.get(b"key").unwrap().unwrap(), b"value");
+        assert!(roots.transactions().current_transaction_id().is_some());
+        assert!(matches!(
+            tree.set(b"key", b"other").unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            roots
+                .transaction(&[Versioned::tree("test")])
+                .err()
+                .unwrap()
+                .kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            roots.delete_tree("test").unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        assert!(matches!(
+            roots.defragment_transaction_log().unwrap_err().kind,
+            ErrorKind::ReadOnly
+        ));
+        // Opening a missing tree doesn't create it.
+        let err = roots.tree(Versioned::tree("missing")).unwrap_err();
+        assert!(err.kind.is_file_not_found());
+        drop(roots);
+
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), files_before);
+    }
+
+    #[test]
+    fn unsynchronized_transaction_log() {
+        let tempdir = tempdir().unwrap();
+
+        let roots = Config::new(tempdir.path())
+            .synchronize_transaction_log(false)
+            .open()
+            .unwrap();
+        for value in 0_u8..10 {
+            let transaction = roots
+                .transaction(&[Unversioned::tree("a"), Unversioned::tree("b")])
+                .unwrap();
+            for index in 0..2 {
+                transaction
+                    .tree::<Unversioned>(index)
+                    .unwrap()
+                    .set(vec![value], b"value")
+                    .unwrap();
+            }
+            transaction.commit().unwrap();
+        }
+        let last_transaction_id = roots.transactions().current_transaction_id();
+        drop(roots);
+
+        // The transactions were still written to the log, so the trees'
+        // roots are accepted when the database is reopened normally.
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        assert_eq!(
+            roots.transactions().current_transaction_id(),
+            last_transaction_id
+        );
+        for name in ["a", "b"] {
+            let tree = roots.tree(Unversioned::tree(name)).unwrap();
+            for value in 0_u8..10 {
+                assert_eq!(tree.get(&[value]).unwrap().as_deref(), Some(&b"value"[..]));
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        let b = roots.tree(Unversioned::tree("b")).unwrap();
+        a.set(b"key", b"a1").unwrap();
+        b.set(b"key", b"b1").unwrap();
+
+        // Snapshots don't wait for transactions to finish.
+        let transaction = roots
+            .transaction_builder()
+            .add::<Versioned>("a")
+            .add::<Unversioned>("b")
+            .begin()
+            .unwrap();
+        let trees: [Box<dyn AnyTreeRoot<StdFile>>; 2] = [
+            Box::new(Versioned::tree("a")),
+            Box::new(Unversioned::tree("b")),
+        ];
+        let snapshot = roots
+            .snapshot::<_, dyn AnyTreeRoot<StdFile>>(&trees)
+            .unwrap();
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"key", b"a2")
+            .unwrap();
+        transaction
+            .tree::<Unversioned>(1)
+            .unwrap()
+            .set(b"other", b"b2")
+            .unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(snapshot.len(), 2);
+        let snapshot_a = snapshot.tree::<Versioned>(0).unwrap();
+        let snapshot_b = snapshot.tree::<Unversioned>(1).unwrap();
+        assert!(snapshot.tree::<Versioned>(2).is_none());
+        assert_eq!(snapshot_a.name(), "a");
+        assert_eq!(snapshot_a.get(b"key").unwrap().unwrap(), b"a1");
+        assert_eq!(snapshot_b.count(), 1);
+        assert_eq!(snapshot_b.get(b"other").unwrap(), None);
+        assert_eq!(
+            snapshot_b.get_range(&(..)).unwrap(),
+            vec![(ArcBytes::from(b"key"), ArcBytes::from(b"b1"))]
+        );
+        assert_eq!(a.get(b"key").unwrap().unwrap(), b"a2");
+        assert_eq!(b.count(), 2);
+
+        // Compaction removes the data the snapshot refers to.
+        a.compact().unwrap();
+        assert!(matches!(
+            snapshot_a.get(b"key").unwrap_err().kind,
+            ErrorKind::TreeCompacted
+        ));
+
+        let err = roots.snapshot(&[Versioned::tree("missing")]).unwrap_err();
+        assert!(err.kind.is_file_not_found());
+    }
+
+    #[test]
+    fn snapshot_export() {
+        let tempdir = tempdir().unwrap();
+        let database = tempdir.path().join("database");
+        let backup = tempdir.path().join("backup");
+        let roots = Config::new(&database).open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        a.set(b"key", b"a1").unwrap();
+        b.set(b"key", b"b1").unwrap();
+        let snapshot = roots
+            .snapshot(&[Versioned::tree("a"), Versioned::tree("b")])
+            .unwrap();
+        roots
+            .multi_set::<Versioned>(&[("a", b"key", b"a2"), ("b", b"other", b"b2")])
+            .unwrap();
+
+        snapshot.export_to(&backup).unwrap();
+        let err = snapshot.export_to(&backup).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::AlreadyExists)
+        );
+
+        let exported = Config::new(&backup).open().unwrap();
+        let exported_a = exported.tree(Versioned::tree("a")).unwrap();
+        let exported_b = exported.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(exported_a.get(b"key").unwrap().unwrap(), b"a1");
+        assert_eq!(exported_b.get(b"key").unwrap().unwrap(), b"b1");
+        assert_eq!(exported_b.get(b"other").unwrap(), None);
+        // The exported database can be written to.
+        exported_a.set(b"key", b"a3").unwrap();
+        assert_eq!(exported_a.get(b"key").unwrap().unwrap(), b"a3");
+        drop((exported_a, exported_b, exported));
+
+        // Compaction removes the data the snapshot refers to.
+        a.compact().unwrap();
+        let failed_backup = tempdir.path().join("failed-backup");
+        let err = snapshot.export_to(&failed_backup).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TreeCompacted));
+        assert!(!failed_backup.exists());
+        drop(b);
+    }
+
+    #[test]
+    fn std_tree_exists() {
+        tree_exists(StdFileManager::default());
+    }
+
+    fn rename_tree<M: FileManager>(file_manager: M) {
+        let tempdir = tempdir().unwrap();
+        let config = Config::new(tempdir.path()).file_manager(file_manager);
+        let roots = config.clone().open().unwrap();
+        roots
+            .tree(Versioned::tree("a"))
+            .unwrap()
+            .set(b"test", b"value")
+            .unwrap();
+        roots.tree(Versioned::tree("c")).unwrap();
+
+        roots.rename_tree("a", "b").unwrap();
+        let tree = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(tree.get(b"test").unwrap().as_deref(), Some(&b"value"[..]));
+        // The renamed tree can still be written to.
+        tree.set(b"other", b"value").unwrap();
+        assert!(roots.tree(Versioned::tree("a")).unwrap().is_empty());
+
+        let error = roots.rename_tree("missing", "d").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Io(ref err) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+        let error = roots.rename_tree("b", "c").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Io(ref err) if err.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+        assert!(matches!(
+            roots.rename_tree("b", "=").unwrap_err().kind,
+            ErrorKind::InvalidTreeName
+        ));
+
+        // Trees used by a transaction can't be renamed.
+        let transaction = roots.transaction(&[Versioned::tree("b")]).unwrap();
+        assert!(roots.rename_tree("b", "d").is_err());
+        drop(transaction);
+        roots.rename_tree("b", "d").unwrap();
+        drop(roots);
+
+        let roots = config.open().unwrap();
+        let tree = roots.tree(Versioned::tree("d")).unwrap();
+        assert_eq!(tree.get(b"test").unwrap().as_deref(), Some(&b"value"[..]));
+        assert_eq!(tree.get(b"other").unwrap().as_deref(), Some(&b"value"[..]));
+    }
+
+    #[test]
+    fn memory_rename_tree() {
+        rename_tree(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn std_rename_tree() {
+        rename_tree(StdFileManager::default());
+    }
+
+    fn swap_trees<M: FileManager>(file_manager: M) {
+        let tempdir = tempdir().unwrap();
+        let config = Config::new(tempdir.path()).file_manager(file_manager);
+        let roots = config.clone().open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        a.set(b"test", b"a").unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        b.set(b"test", b"b").unwrap();
+        b.set(b"other", b"b").unwrap();
+        a.compact().unwrap();
+
+        roots.swap_trees::<Versioned>("a", "b").unwrap();
+        // Existing trees see the swapped contents.
+        assert_eq!(a.get(b"test").unwrap().as_deref(), Some(&b"b"[..]));
+        assert_eq!(a.count(), 2);
+        assert_eq!(b.get(b"test").unwrap().as_deref(), Some(&b"a"[..]));
+        assert_eq!(b.count(), 1);
+        // The swapped trees can still be written to.
+        b.set(b"other", b"a").unwrap();
+        a.compact().unwrap();
+
+        // Trees used by a transaction can't be swapped.
+        let transaction = roots.transaction(&[Versioned::tree("a")]).unwrap();
+        assert!(matches!(
+            roots.swap_trees::<Versioned>("a", "b").unwrap_err().kind,
+            ErrorKind::Message(_)
+        ));
+        drop(transaction);
+        // Trees must be opened with the same root type.
+        assert!(matches!(
+            roots.swap_trees::<Unversioned>("a", "b").unwrap_err().kind,
+            ErrorKind::Message(_)
+        ));
+        let error = roots.swap_trees::<Versioned>("a", "missing").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Io(ref err) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+        drop((a, b, roots));
+
+        let roots = config.open().unwrap();
+        // Trees that haven't been opened are read before being swapped.
+        roots.swap_trees::<Versioned>("b", "a").unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        assert_eq!(a.get(b"test").unwrap().as_deref(), Some(&b"a"[..]));
+        assert_eq!(a.get(b"other").unwrap().as_deref(), Some(&b"a"[..]));
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(b.get(b"test").unwrap().as_deref(), Some(&b"b"[..]));
+        assert_eq!(b.count(), 2);
+    }
+
+    #[test]
+    fn memory_swap_trees() {
+        swap_trees(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn std_swap_trees() {
+        swap_trees(StdFileManager::default());
+    }
+
+    #[test]
+    fn swap_trees_failure() {
+        let tempdir = tempdir().unwrap();
+        let config = Config::<StdFileManager>::new(tempdir.path());
+        let roots = config.clone().open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        a.set(b"test", b"a").unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        b.set(b"test", b"b").unwrap();
+
+        // Moving `b` aside fails after `a` has already been moved aside.
+        let blocker = tempdir.path().join("b.nebari.swapping");
+        fs::create_dir(&blocker).unwrap();
+        roots.swap_trees::<Versioned>("a", "b").unwrap_err();
+        assert!(!tempdir.path().join("a.nebari.swapping").exists());
+        assert!(!tempdir.path().join(SWAP_INTENT_FILE).exists());
+        assert_eq!(a.get(b"test").unwrap().as_deref(), Some(&b"a"[..]));
+        assert_eq!(b.get(b"test").unwrap().as_deref(), Some(&b"b"[..]));
+        a.set(b"test", b"updated").unwrap();
+        drop((a, b, roots));
+
+        fs::remove_dir(&blocker).unwrap();
+        let roots = config.open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        assert_eq!(a.get(b"test").unwrap().as_deref(), Some(&b"updated"[..]));
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(b.get(b"test").unwrap().as_deref(), Some(&b"b"[..]));
+        roots.swap_trees::<Versioned>("a", "b").unwrap();
+        assert_eq!(a.get(b"test").unwrap().as_deref(), Some(&b"b"[..]));
+    }
+
+    #[test]
+    fn swap_trees_recovery() {
+        let tempdir = tempdir().unwrap();
+        let config = Config::<StdFileManager>::new(tempdir.path());
+        let roots = config.clone().open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        a.set(b"test", b"a").unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        b.set(b"test", b"b").unwrap();
+        drop((a, b, roots));
+
+        let manager = StdFileManager::default();
+        let a_path = tempdir.path().join("a.nebari");
+        let b_path = tempdir.path().join("b.nebari");
+        let swap = TreeFileSwap::new(&manager, tempdir.path(), &a_path, &b_path);
+        let get = |roots: &Roots<StdFile>, name: &str| {
+            roots
+                .tree(Versioned::tree(name.to_string()))
+                .unwrap()
+                .get(b"test")
+                .unwrap()
+        };
+
+        // A swap interrupted before it was committed is undone.
+        swap.begin("a", "b").unwrap();
+        assert!(config.clone().read_only(true).open().is_err());
+        let roots = config.clone().open().unwrap();
+        assert_eq!(get(&roots, "a").as_deref(), Some(&b"a"[..]));
+        assert_eq!(get(&roots, "b").as_deref(), Some(&b"b"[..]));
+        drop(roots);
+        assert!(!tempdir.path().join(SWAP_INTENT_FILE).exists());
+
+        // A swap interrupted after it was committed is completed.
+        swap.begin("a", "b").unwrap();
+        write_swap_marker(&manager, &tempdir.path().join(SWAP_COMMIT_FILE), b"").unwrap();
+        manager
+            .rename(
+                TreeFileSwap::<StdFileManager>::temporary_path(&a_path),
+                &b_path,
+            )
+            .unwrap();
+        let roots = config.open().unwrap();
+        assert_eq!(get(&roots, "a").as_deref(), Some(&b"b"[..]));
+        assert_eq!(get(&roots, "b").as_deref(), Some(&b"a"[..]));
+        assert!(!tempdir.path().join(SWAP_INTENT_FILE).exists());
+        assert!(!tempdir.path().join(SWAP_COMMIT_FILE).exists());
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn mmap_file_manager() {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        use crate::io::mmap::MmapFileManager;
+
+        basic_get_set(MmapFileManager::default());
+        tree_exists(MmapFileManager::default());
+        rename_tree(MmapFileManager::default());
+
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path())
+            .file_manager(MmapFileManager::default())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        // Each write grows the file past the region mapped by earlier reads.
+        for i in 0_u32..100 {
+            tree.set(i.to_be_bytes().to_vec(), b"initial").unwrap();
+            assert_eq!(tree.get(&i.to_be_bytes()).unwrap().unwrap(), b"initial");
+        }
+
+        // Compaction replaces the file, so readers must map the new file.
+        tree.compact().unwrap();
+        for i in 0_u32..100 {
+            assert_eq!(tree.get(&i.to_be_bytes()).unwrap().unwrap(), b"initial");
+            tree.set(i.to_be_bytes().to_vec(), b"updated").unwrap();
+        }
+        for i in 0_u32..100 {
+            assert_eq!(tree.get(&i.to_be_bytes()).unwrap().unwrap(), b"updated");
+        }
+
+        // Readers remap a file that has been truncated, rather than reading
+        // past the end of the file.
+        let manager = MmapFileManager::default();
+        let path = tempdir.path().join("truncated");
+        let mut file = manager.open_for_append(&path, None).unwrap();
+        file.write_all(&[1; 8192]).unwrap();
+        drop(file);
+        let mut reader = manager.open_for_read(&path, None).unwrap();
+        reader.read_exact(&mut [0; 8192]).unwrap();
+        manager.truncate(&path, 10).unwrap();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, vec![1_u8; 10]);
+    }
+
+    #[test]
+    fn clear() {
+        let tempdir = tempdir().unwrap();
+        let config = Config::<StdFileManager>::new(tempdir.path());
+        let roots = config.clone().open().unwrap();
+        let tree = roots.tree(Versioned::tree("versioned")).unwrap();
+        for id in 0_u32..100 {
+            tree.set(id.to_be_bytes(), b"value").unwrap();
+        }
+        tree.set_metadata(Some(ArcBytes::from(b"metadata")))
+            .unwrap();
+        let sequence = tree.current_sequence_id();
+
+        tree.clear().unwrap();
+        assert!(tree.is_empty());
+        assert_eq!(tree.get(&0_u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(tree.metadata().unwrap().as_deref(), Some(&b"metadata"[..]));
+        // The sequence isn't reset.
+        assert_eq!(tree.current_sequence_id(), sequence);
+        tree.set(b"new", b"value").unwrap();
+        assert!(tree.current_sequence_id() > sequence);
+
+        // After resetting the sequence, writes are assigned the same sequence
+        // ids as writes to a new tree.
+        tree.clear_and_reset_sequence().unwrap();
+        assert!(tree.is_empty());
+        tree.set(b"new", b"value").unwrap();
+        let fresh = roots.tree(Versioned::tree("fresh")).unwrap();
+        fresh.set(b"new", b"value").unwrap();
+        let first_sequence = fresh.current_sequence_id();
+        assert_eq!(tree.current_sequence_id(), first_sequence);
+
+        let unversioned = roots.tree(Unversioned::tree("unversioned")).unwrap();
+        unversioned.set(b"key", b"value").unwrap();
+        unversioned.clear().unwrap();
+        assert!(unversioned.is_empty());
+        unversioned.set(b"other", b"value").unwrap();
+        drop(roots);
+
+        // The cleared trees are empty after reopening.
+        let roots = config.open().unwrap();
+        let tree = roots.tree(Versioned::tree("versioned")).unwrap();
+        assert_eq!(tree.get(&0_u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(tree.count(), 1);
+        assert_eq!(tree.current_sequence_id(), first_sequence);
+        let unversioned = roots.tree(Unversioned::tree("unversioned")).unwrap();
+        assert_eq!(unversioned.get(b"key").unwrap(), None);
+        assert_eq!(unversioned.count(), 1);
+    }
+
+    #[test]
+    fn copy_tree() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let source = roots.tree(Versioned::tree("source")).unwrap();
+        // Enough entries to require multiple batches.
+        for id in 0_u32..2_500 {
+            source
+                .set(id.to_be_bytes(), id.to_string().into_bytes())
+                .unwrap();
+        }
+        source.remove(&0_u32.to_be_bytes()).unwrap();
+
+        let copy = roots
+            .copy_tree(
+                &source,
+                Versioned::tree("copy").with_vault(RotatorVault::new(42)),
+            )
+            .unwrap();
+        assert_eq!(copy.count(), 2_499);
+        assert_eq!(copy.get(&0_u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(
+            copy.get(&1_234_u32.to_be_bytes()).unwrap().as_deref(),
+            Some(&b"1234"[..])
+        );
+
+        // The copy is independent of the source.
+        copy.set(1_234_u32.to_be_bytes(), b"changed").unwrap();
+        assert_eq!(
+            source.get(&1_234_u32.to_be_bytes()).unwrap().as_deref(),
+            Some(&b"1234"[..])
+        );
+
+        let error = roots
+            .copy_tree(&source, Versioned::tree("copy"))
+            .unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ErrorKind::Io(ref err) if err.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+    }
+
+    #[test]
+    fn basic_transaction_isolation_test() {
+        let tempdir = tempdir().unwrap();
+
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"test", b"value").unwrap();
+
+        // Begin a transaction
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+
+        // Replace the key with a new value.
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"test", b"updated value")
+            .unwrap();
+
+        // Check that the transaction can read the new value
+        let result = transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .get(b"test")
+            .unwrap()
+            .expect("key not found");
+        assert_eq!(result, b"updated value");
+
+        // Ensure that existing read-access doesn't see the new value
+        let result = tree.get(b"test").unwrap().expect("key not found");
+        assert_eq!(result, b"value");
+
+        // Commit the transaction
+        transaction.commit().unwrap();
+
+        // Ensure that the reader now sees the new value
+        let result = tree.get(b"test").unwrap().expect("key not found");
+        assert_eq!(result, b"updated value");
+    }
+
+    #[test]
+    fn operation_log() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .operation_log(3)
+            .open()
+            .unwrap();
+        let tree = roots.tree(Versioned::tree("a")).unwrap();
+        tree.set(b"a", b"0").unwrap();
+        tree.remove(b"a").unwrap();
+        let remove_id = roots.transactions().current_transaction_id().unwrap();
+        let operations = tree.recent_operations(b"a");
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].1, OperationKind::Set);
+        assert_eq!(operations[1], (remove_id, OperationKind::Remove));
+
+        // Rolled back transactions aren't logged.
+        let transaction = roots.transaction(&[Versioned::tree("a")]).unwrap();
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"a", b"1")
+            .unwrap();
+        transaction.rollback();
+        assert_eq!(tree.recent_operations(b"a").len(), 2);
+
+        // Operations are logged per tree, and the oldest are discarded.
+        let other = roots.tree(Unversioned::tree("b")).unwrap();
+        other.set(b"a", b"0").unwrap();
+        other.set(b"b", b"0").unwrap();
+        assert_eq!(
+            tree.recent_operations(b"a"),
+            vec![(remove_id, OperationKind::Remove)]
+        );
+        assert_eq!(other.recent_operations(b"a").len(), 1);
+        assert_eq!(roots.recent_operations("b", b"b").len(), 1);
+    }
+
+    #[test]
+    fn custom_file_extension() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .file_extension("db")
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        tree.set(b"a", b"0").unwrap();
+        assert!(tempdir.path().join("test.db").exists());
+        assert!(!tempdir.path().join("test.nebari").exists());
+        std::fs::write(tempdir.path().join("other.nebari"), b"").unwrap();
+        assert_eq!(roots.tree_names().unwrap(), vec![String::from("test")]);
+
+        assert!(roots.delete_tree("test").unwrap());
+        assert!(!tempdir.path().join("test.db").exists());
+        assert!(roots.tree_names().unwrap().is_empty());
+
+        for invalid in ["", "a/b"] {
+            assert!(Config::<StdFileManager>::new(tempdir.path())
+                .file_extension(invalid)
+                .open()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn thread_pool_spawn_hook() {
+        let tempdir = tempdir().unwrap();
+        let spawned = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let hook_spawned = spawned.clone();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .thread_pool_spawn_hook(move |thread| {
+                hook_spawned.fetch_add(1, Ordering::SeqCst);
+                std::thread::spawn(thread);
+            })
+            .open()
+            .unwrap();
+
+        // Committing multiple trees uses the thread pool.
+        let transaction = roots
+            .transaction(&[Unversioned::tree("a"), Unversioned::tree("b")])
+            .unwrap();
+        for index in 0..2 {
+            transaction
+                .tree::<Unversioned>(index)
+                .unwrap()
+                .set(b"key", b"value")
+                .unwrap();
+        }
+        transaction.commit().unwrap();
+        assert!(spawned.load(Ordering::SeqCst) > 0);
+
+        let tree = roots.tree(Unversioned::tree("b")).unwrap();
+        assert_eq!(tree.get(b"key").unwrap().unwrap(), b"value");
+    }
+
+    #[test]
+    fn is_empty() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert!(tree.is_empty());
+
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        {
+            let mut tx_tree = transaction.tree::<Versioned>(0).unwrap();
+            assert!(tx_tree.is_empty());
+            tx_tree.set(b"a", b"0").unwrap();
+            assert!(!tx_tree.is_empty());
+            // Uncommitted changes are not visible outside of the transaction.
+            assert!(tree.is_empty());
+        }
+        transaction.commit().unwrap();
+        assert!(!tree.is_empty());
+
+        tree.remove(b"a").unwrap();
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn approximate_count() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"a", b"0").unwrap();
+        assert_eq!(tree.approximate_count(), 1);
+
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        {
+            let mut tx_tree = transaction.tree::<Versioned>(0).unwrap();
+            tx_tree.set(b"b", b"0").unwrap();
+            tx_tree.remove(b"a").unwrap();
+            tx_tree.set(b"c", b"0").unwrap();
+            // Uncommitted changes are not reflected.
+            assert_eq!(tree.approximate_count(), 1);
+        }
+        transaction.commit().unwrap();
+        assert_eq!(tree.approximate_count(), 2);
+        assert_eq!(tree.approximate_count(), tree.count());
+    }
+
+    #[test]
+    fn subscribe() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let first = roots.subscribe();
+        let second = roots.subscribe();
+
+        let transaction = roots
+            .transaction(&[Versioned::tree("a"), Versioned::tree("b")])
+            .unwrap();
+        let transaction_id = transaction.entry().id;
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"a", b"0")
+            .unwrap();
+        transaction
+            .tree::<Versioned>(1)
+            .unwrap()
+            .set(b"b", b"0")
+            .unwrap();
+        transaction.commit().unwrap();
+
+        let expected = CommitEvent {
+            transaction_id,
+            trees: vec![String::from("a"), String::from("b")],
+        };
+        assert_eq!(first.try_recv().unwrap(), expected);
+        assert_eq!(second.try_recv().unwrap(), expected);
+
+        // Dropping a receiver doesn't prevent committing.
+        drop(first);
+        let tree = roots.tree(Versioned::tree("a")).unwrap();
+        tree.set(b"a", b"1").unwrap();
+        let event = second.try_recv().unwrap();
+        assert!(event.transaction_id > transaction_id);
+        assert_eq!(event.trees, vec![String::from("a")]);
+        assert!(second.try_recv().is_err());
+    }
+
+    #[test]
+    fn flush() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        tree.flush().unwrap();
+        tree.set(b"a", b"0").unwrap();
+        tree.flush().unwrap();
+        assert_eq!(tree.get(b"a").unwrap().as_deref(), Some(&b"0"[..]));
+    }
+
+    #[test]
+    fn contains_key() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert!(!tree.contains_key(b"a").unwrap());
+        tree.set(b"a", b"0").unwrap();
+        assert!(tree.contains_key(b"a").unwrap());
+        assert!(!tree.contains_key(b"b").unwrap());
+
+        // Versioned trees keep deleted keys, but they have no value.
+        tree.remove(b"a").unwrap();
+        assert!(!tree.contains_key(b"a").unwrap());
+
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        let mut transaction_tree = transaction.tree::<Versioned>(0).unwrap();
+        transaction_tree.set(b"b", b"1").unwrap();
+        assert!(transaction_tree.contains_key(b"b").unwrap());
+        drop(transaction_tree);
+        transaction.rollback();
+        assert!(!tree.contains_key(b"b").unwrap());
+    }
+
+    #[test]
+    fn shared_transaction_tree() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"committed", b"0").unwrap();
+
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        {
+            let mut transaction_tree = transaction.tree::<Versioned>(0).unwrap();
+            for id in 0_u32..100 {
+                transaction_tree
+                    .set(id.to_be_bytes(), id.to_le_bytes())
+                    .unwrap();
+            }
+        }
+
+        let transaction = Arc::new(transaction);
+        // Hold a shared tree while other threads read from their own.
+        let shared = transaction.unlocked_tree(0).unwrap().read::<Versioned>();
+        let readers = (0..4)
+            .map(|_| {
+                let transaction = transaction.clone();
+                std::thread::spawn(move || {
+                    let shared = transaction.unlocked_tree(0).unwrap().read::<Versioned>();
+                    for id in 0_u32..100 {
+                        let value = shared.get(&id.to_be_bytes()).unwrap();
+                        assert_eq!(value.as_deref(), Some(&id.to_le_bytes()[..]));
+                    }
+                    assert!(shared.contains_key(b"committed").unwrap());
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shared.get_range(&(..)).unwrap().len(),
+            101,
+            "uncommitted changes are visible"
+        );
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        drop(shared);
+
+        // Other trees don't observe the transaction until it's committed.
+        assert!(tree.get(&0_u32.to_be_bytes()).unwrap().is_none());
+        let transaction = Arc::try_unwrap(transaction).ok().unwrap();
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"after", b"reading")
+            .unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(
+            tree.get(b"after").unwrap().as_deref(),
+            Some(&b"reading"[..])
+        );
+        assert!(tree.get(&0_u32.to_be_bytes()).unwrap().is_some());
+    }
+
+    #[test]
+    fn error_context() {
+        let error = Error::from(ErrorKind::TreeCompacted)
+            .with_context("while reading tree \"users\"")
+            .with_context("while loading profile");
+        assert!(error.kind.is_tree_compacted());
+        assert!(matches!(error.kind.root_kind(), ErrorKind::TreeCompacted));
+        assert_eq!(
+            error.kind.to_string(),
+            "while loading profile: while reading tree \"users\": the file has been compacted. reopen the file and try again"
+        );
+
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .with_context("while opening tree");
+        assert!(error.kind.is_file_not_found());
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn error_classification() {
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .with_context("while opening tree");
+        assert!(error.is_io());
+        assert!(error.is_file_not_found());
+        assert!(!error.is_data_integrity());
+        assert!(!error.is_tree_compacted());
+
+        let error = Error::data_integrity(ErrorKind::message("bad crc"));
+        assert!(error.is_data_integrity());
+        assert!(!error.is_io());
+
+        let error = Error::from(ErrorKind::TreeCompacted).with_context("while reading");
+        assert!(error.is_tree_compacted());
+        assert!(!error.is_file_not_found());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn stream_range() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll, Wake, Waker},
+            thread::Thread,
+        };
+
+        use futures_core::Stream;
+
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn next(
+            stream: &mut crate::RangeStream,
+        ) -> Option<Result<(ArcBytes<'static>, ArcBytes<'static>), Error>> {
+            let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+            let mut context = Context::from_waker(&waker);
+            loop {
+                match Pin::new(&mut *stream).poll_next(&mut context) {
+                    Poll::Ready(entry) => return entry,
+                    Poll::Pending => std::thread::park(),
+                }
+            }
+        }
+
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Unversioned::tree("test")).unwrap();
+        let key = |id: u32| id.to_be_bytes().to_vec();
+        tree.modify(
+            (0..100).map(|id| ArcBytes::from(key(id))).collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
+
+        let mut stream = tree.stream_range(key(10)..key(20), 2);
+        let mut keys = Vec::new();
+        while let Some(entry) = next(&mut stream) {
+            let (key, value) = entry.unwrap();
+            assert_eq!(value, b"value");
+            keys.push(key.to_vec());
+        }
+        assert_eq!(keys, (10..20).map(key).collect::<Vec<_>>());
+
+        // Dropping the stream before it is exhausted stops the scan.
+        let mut stream = tree.stream_range(key(0)..key(100), 1);
+        assert_eq!(next(&mut stream).unwrap().unwrap().0, &key(0)[..]);
+        drop(stream);
+    }
+
+    #[test]
+    fn repair_on_open() {
+        let tempdir = tempdir().unwrap();
+        {
+            let roots = Config::new(tempdir.path()).open().unwrap();
+            let a = roots.tree(Versioned::tree("a")).unwrap();
+            a.set(b"key", b"1").unwrap();
+            a.set(b"key", b"2").unwrap();
+            let b = roots.tree(Versioned::tree("b")).unwrap();
+            b.set(b"key", b"1").unwrap();
+        }
+
+        // Corrupt the most recent root of "a".
+        let path = tempdir.path().join("a.nebari");
+        let mut contents = std::fs::read(&path).unwrap();
+        let last_root = (0..contents.len())
+            .step_by(tree::PAGE_SIZE)
+            .filter(|offset| contents[*offset..].starts_with(b"Nbr"))
+            .last()
+            .unwrap();
+        contents[last_root + 12] ^= 0xFF;
+        std::fs::write(&path, &contents).unwrap();
+
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        assert!(roots.repairs().is_empty());
+        assert!(roots
+            .tree(Versioned::tree("a"))
+            .unwrap()
+            .get(b"key")
+            .is_err());
+        drop(roots);
+
+        let roots = Config::new(tempdir.path())
+            .repair_on_open(RepairPolicy::TruncateToLastValidRoot)
+            .open()
+            .unwrap();
+        assert_eq!(roots.repairs().len(), 1);
+        assert_eq!(roots.repairs()[0].tree, "a");
+        assert!(roots.repairs()[0].bytes_discarded > 0);
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        assert_eq!(a.get(b"key").unwrap().unwrap(), b"1");
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(b.get(b"key").unwrap().unwrap(), b"1");
+
+        // The repaired tree can be written to again.
+        a.set(b"key", b"3").unwrap();
+        drop((a, b, roots));
+        let roots = Config::new(tempdir.path())
+            .repair_on_open(RepairPolicy::TruncateToLastValidRoot)
+            .open()
+            .unwrap();
+        assert!(roots.repairs().is_empty());
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        assert_eq!(a.get(b"key").unwrap().unwrap(), b"3");
+    }
+
+    #[test]
+    fn repair_on_open_memory() {
+        use std::io::{Read, Write};
+
+        let tempdir = tempdir().unwrap();
+        let file_manager = MemoryFileManager::default();
+        let config = Config::new(tempdir.path())
+            .file_manager(file_manager.clone())
+            .repair_on_open(RepairPolicy::TruncateToLastValidRoot);
+        {
+            let roots = config.clone().open().unwrap();
+            let a = roots.tree(Versioned::tree("a")).unwrap();
+            a.set(b"key", b"1").unwrap();
+            a.set(b"key", b"2").unwrap();
+            assert_eq!(roots.tree_names().unwrap(), vec![String::from("a")]);
+        }
+
+        // Append a corrupted copy of the most recent root of "a".
+        let path = tempdir.path().join("a.nebari");
+        let valid_length = file_manager.file_length(&path).unwrap();
+        let mut contents = vec![0; usize::try_from(valid_length).unwrap()];
+        file_manager
+            .open_for_read(&path, None)
+            .unwrap()
+            .read_exact(&mut contents)
+            .unwrap();
+        let last_root = (0..contents.len())
+            .step_by(tree::PAGE_SIZE)
+            .filter(|offset| contents[*offset..].starts_with(b"Nbr"))
+            .last()
+            .unwrap();
+        let mut corrupted = contents[last_root..].to_vec();
+        corrupted[12] ^= 0xFF;
+        let padding = tree::PAGE_SIZE - contents.len() % tree::PAGE_SIZE;
+        let mut file = file_manager.open_for_append(&path, None).unwrap();
+        file.write_all(&vec![0; padding]).unwrap();
+        file.write_all(&corrupted).unwrap();
+        drop(file);
+
+        let roots = config.open().unwrap();
+        assert_eq!(roots.repairs().len(), 1);
+        assert_eq!(roots.repairs()[0].tree, "a");
+        assert_eq!(
+            roots.repairs()[0].bytes_discarded,
+            (padding + corrupted.len()) as u64
+        );
+        assert_eq!(file_manager.file_length(&path).unwrap(), valid_length);
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        assert_eq!(a.get(b"key").unwrap().unwrap(), b"2");
+    }
+
+    #[test]
+    fn scan_and_apply() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let source = roots.tree(Unversioned::tree("source")).unwrap();
+        for key in 0_u8..5 {
+            source.set(vec![key], vec![key * 2]).unwrap();
+        }
+
+        // Copy every entry after the first into another tree, two at a time.
+        let destination = Unversioned::tree("destination");
+        roots
+            .scan_and_apply::<_, _, _, _, Infallible, _>(
+                &source,
+                &(&[1][..]..),
+                &[destination.clone()],
+                2,
+                |(key, value), transaction| {
+                    transaction
+                        .tree::<Unversioned>(0)
+                        .unwrap()
+                        .set(key, value)?;
+                    Ok(())
+                },
+            )
+            .unwrap();
+        let destination_tree = roots.tree(destination.clone()).unwrap();
+        let copied = destination_tree.get_range(&(..)).unwrap();
+        assert_eq!(
+            copied,
+            (1_u8..5)
+                .map(|key| (ArcBytes::from(vec![key]), ArcBytes::from(vec![key * 2])))
+                .collect::<Vec<_>>()
+        );
+
+        // A failure only rolls back the batch it occurred in.
+        let failing = Unversioned::tree("failing");
+        let result = roots.scan_and_apply(
+            &source,
+            &(..),
+            &[failing.clone()],
+            2,
+            |(key, value), transaction| {
+                if key[0] == 3 {
+                    return Err(AbortError::Other("failed"));
+                }
+                transaction
+                    .tree::<Unversioned>(0)
+                    .unwrap()
+                    .set(key, value)?;
+                Ok(())
+            },
+        );
+        assert!(matches!(result, Err(AbortError::Other("failed"))));
+        let failing_tree = roots.tree(failing).unwrap();
+        assert_eq!(failing_tree.count(), 2);
+
+        // The tree being read can't be written to.
+        assert!(matches!(
+            roots.scan_and_apply::<_, _, _, _, Infallible, _>(
+                &source,
+                &(..),
+                &[Unversioned::tree("source")],
+                2,
+                |_, _| Ok(()),
+            ),
+            Err(AbortError::Nebari(Error {
+                kind: ErrorKind::InvalidTreeName,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn min_free_space() {
+        let tempdir = tempdir().unwrap();
+        let file_manager = MemoryFileManager::with_capacity(1_000_000, CapacityPolicy::Error);
+        let roots = Config::new(tempdir.path())
+            .file_manager(file_manager.clone())
+            .min_free_space(999_000)
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        tree.set(b"a", vec![0; 500]).unwrap();
+
+        // Once less than the minimum is available, commits are refused.
+        let error = tree.set(b"b", vec![0; 500]).unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::OutOfSpace));
+        assert_eq!(tree.get(b"b").unwrap(), None);
+
+        // The file system's available space is checked for files on disk.
+        #[cfg(feature = "available-space")]
+        {
+            let roots = Config::<StdFileManager>::new(tempdir.path().join("std"))
+                .min_free_space(u64::MAX)
+                .open()
+                .unwrap();
+            let error = roots
+                .tree(Unversioned::tree("test"))
+                .unwrap()
+                .set(b"a", b"a")
+                .unwrap_err();
+            assert!(matches!(error.kind, ErrorKind::OutOfSpace));
+        }
+
+        // A file manager that can't report the available space can't enforce
+        // the minimum.
+        let error = Config::new(tempdir.path().join("unbounded"))
+            .file_manager(MemoryFileManager::default())
+            .min_free_space(1)
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::Message(_)));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn out_of_space_io_error() {
+        let error = Error::from(std::io::Error::from_raw_os_error(28));
+        assert!(matches!(error.kind, ErrorKind::OutOfSpace));
+    }
+
+    #[test]
+    fn recovery_handler() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        roots
+            .tree(Unversioned::tree("test"))
+            .unwrap()
+            .set(b"a", b"committed")
+            .unwrap();
+        drop(roots);
+
+        // Write a root for a transaction that was never committed to the log.
+        let context = Context {
+            file_manager: StdFileManager::default(),
+            vault: None,
+            cache: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
+        };
+        let mut tree = TreeFile::<Unversioned, StdFile>::write(
+            tempdir.path().join("test.nebari"),
+            State::default(),
+            &context,
+            None,
+        )
+        .unwrap();
+        tree.set(
+            Some(TransactionId(u64::MAX / 2)),
+            ArcBytes::from(b"a"),
+            ArcBytes::from(b"uncommitted"),
+        )
+        .unwrap();
+        drop(tree);
+
+        let open_with = |action: RecoveryAction| {
+            let situations = Arc::new(Mutex::new(Vec::new()));
+            let roots = Config::<StdFileManager>::new(tempdir.path())
+                .recovery_handler({
+                    let situations = situations.clone();
+                    move |situation| {
+                        if let RecoverySituation::UnconfirmedTransaction {
+                            transaction_id, ..
+                        } = situation
+                        {
+                            situations.lock().push(transaction_id);
+                        }
+                        action
+                    }
+                })
+                .open()
+                .unwrap();
+            let result = roots
+                .tree(Unversioned::tree("test"))
+                .and_then(|tree| tree.get(b"a"));
+            assert_eq!(&*situations.lock(), &[TransactionId(u64::MAX / 2)]);
+            result
+        };
+
+        assert_eq!(
+            open_with(RecoveryAction::RollBack).unwrap().as_deref(),
+            Some(&b"committed"[..])
+        );
+        assert_eq!(
+            open_with(RecoveryAction::Accept).unwrap().as_deref(),
+            Some(&b"uncommitted"[..])
+        );
+        assert_eq!(open_with(RecoveryAction::Reset).unwrap(), None);
+        assert!(matches!(
+            open_with(RecoveryAction::Abort).unwrap_err().kind,
+            ErrorKind::RecoveryAborted { .. }
+        ));
+    }
+
+    #[test]
+    fn page_size() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .page_size(1024)
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        for value in 0_u8..3 {
+            tree.set(b"a", vec![value]).unwrap();
+        }
+        drop(tree);
+        drop(roots);
+
+        let tree_path = tempdir.path().join("test.nebari");
+        let contents = std::fs::read(&tree_path).unwrap();
+        assert_eq!(&contents[0..4], b"Nbrp");
+        assert_eq!(&contents[4..8], &1024_u32.to_be_bytes());
+        // Every root is aligned to the configured page size.
+        for (offset, _) in contents
+            .windows(3)
+            .enumerate()
+            .filter(|(_, bytes)| bytes == b"Nbr")
+            .skip(1)
+        {
+            assert_eq!(offset % 1024, 0);
+        }
+
+        // Reopening with the same page size sees the data.
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .page_size(1024)
+            .open()
+            .unwrap();
+        assert_eq!(
+            roots
+                .tree(Unversioned::tree("test"))
+                .unwrap()
+                .get(b"a")
+                .unwrap()
+                .as_deref(),
+            Some(&[2_u8][..])
+        );
+        drop(roots);
+
+        // Opening with a different page size is rejected.
+        let result = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .and_then(|roots| roots.tree(Unversioned::tree("test")))
+            .and_then(|tree| tree.get(b"a"));
+        assert!(matches!(
+            result.unwrap_err().kind,
+            ErrorKind::DataIntegrity(_)
+        ));
+    }
+
+    #[test]
+    fn write_buffer_size() {
+        let tempdir = tempdir().unwrap();
+        let large_value = vec![7_u8; 100_000];
+        for write_buffer_size in [1, 64, 1024 * 1024] {
+            let roots = Config::<StdFileManager>::new(tempdir.path())
+                .write_buffer_size(write_buffer_size)
+                .open()
+                .unwrap();
+            let tree = roots.tree(Unversioned::tree("test")).unwrap();
+            tree.set(b"small", b"value").unwrap();
+            tree.set(b"large", large_value.clone()).unwrap();
+            tree.compact().unwrap();
+            drop(roots);
+
+            // The data written is readable using the default buffer size.
+            let roots = Config::<StdFileManager>::new(tempdir.path())
+                .open()
+                .unwrap();
+            let tree = roots.tree(Unversioned::tree("test")).unwrap();
+            assert_eq!(tree.get(b"small").unwrap().unwrap(), b"value");
+            assert_eq!(
+                tree.get(b"large").unwrap().unwrap().as_slice(),
+                &large_value[..]
+            );
+            roots.delete_tree("test").unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_page_size() {
+        drop(Config::<StdFileManager>::new("unused").page_size(1000));
+    }
+
+    #[test]
+    fn auto_compact() {
+        const VALUE_SIZE: usize = 1024;
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .auto_compact(CompactionPolicy::Ratio(0.5))
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        let tree_path = tempdir.path().join("test.nebari");
+        for value in 0_u8..100 {
+            tree.set(b"a", vec![value; VALUE_SIZE]).unwrap();
+        }
+
+        // Compactions are skipped while a transaction holds the tree, so keep
+        // writing until a compaction has had a chance to run.
+        let mut value = 0_u8;
+        while std::fs::metadata(&tree_path).unwrap().len() > 16 * VALUE_SIZE as u64 {
+            assert!(value < u8::MAX, "tree was never compacted");
+            value += 1;
+            tree.set(b"a", vec![value; VALUE_SIZE]).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(
+            tree.get(b"a").unwrap().as_deref(),
+            Some(&vec![value; VALUE_SIZE][..])
+        );
+    }
+
+    #[test]
+    fn auto_compact_never() {
+        const VALUE_SIZE: usize = 1024;
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        for value in 0_u8..100 {
+            tree.set(b"a", vec![value; VALUE_SIZE]).unwrap();
+        }
+        let tree_path = tempdir.path().join("test.nebari");
+        assert!(std::fs::metadata(&tree_path).unwrap().len() > 100 * VALUE_SIZE as u64);
+    }
+
+    #[test]
+    fn configured_clock() {
+        #[derive(Debug, Clone, Default)]
+        struct MockClock(Arc<std::sync::atomic::AtomicU64>);
+
+        impl Clock for MockClock {
+            fn now(&self) -> std::time::SystemTime {
+                std::time::UNIX_EPOCH
+                    + std::time::Duration::from_secs(
+                        self.0.load(std::sync::atomic::Ordering::SeqCst),
+                    )
+            }
+        }
+
+        let tempdir = tempdir().unwrap();
+        let clock = MockClock::default();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .clock(clock.clone())
+            .open()
+            .unwrap();
+        assert_eq!(roots.clock().now(), std::time::UNIX_EPOCH);
+        clock.0.store(60, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(
+            roots.clock().now(),
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn mixed_root_transaction() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let transaction = roots
+            .transaction_builder()
+            .add::<Versioned>("versioned")
+            .add::<Unversioned>("unversioned")
+            .begin()
+            .unwrap();
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"a", b"versioned")
+            .unwrap();
+        transaction
+            .tree::<Unversioned>(1)
+            .unwrap()
+            .set(b"a", b"unversioned")
+            .unwrap();
+        transaction.commit().unwrap();
+
+        let versioned = roots.tree(Versioned::tree("versioned")).unwrap();
+        assert_eq!(versioned.get(b"a").unwrap().unwrap(), b"versioned");
+        let unversioned = roots.tree(Unversioned::tree("unversioned")).unwrap();
+        assert_eq!(unversioned.get(b"a").unwrap().unwrap(), b"unversioned");
+    }
+
+    #[test]
+    fn multi_set_test() {
+        let tempdir = tempdir().unwrap();
+
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        roots
+            .multi_set::<Versioned>(&[
+                ("b", b"2", b"b2"),
+                ("a", b"1", b"a1"),
+                ("b", b"1", b"b1"),
+                ("a", b"1", b"a1-updated"),
+            ])
+            .unwrap();
+
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(a.count(), 1);
+        assert_eq!(a.get(b"1").unwrap().unwrap(), b"a1-updated");
+        assert_eq!(b.count(), 2);
+        assert_eq!(b.get(b"1").unwrap().unwrap(), b"b1");
+        assert_eq!(b.get(b"2").unwrap().unwrap(), b"b2");
+
+        // Invalid names should prevent any writes from happening.
+        assert!(matches!(
+            roots
+                .multi_set::<Versioned>(&[("a", b"2", b"a2"), ("in valid", b"1", b"x")])
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidTreeName
+        ));
+        assert!(a.get(b"2").unwrap().is_none());
+    }
+
+    #[test]
+    fn transaction_log_entries() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut ids = Vec::new();
+        for index in 0_u32..100 {
+            let mut transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+            transaction
+                .tree::<Versioned>(0)
+                .unwrap()
+                .set(index.to_be_bytes().to_vec(), b"value")
+                .unwrap();
+            if index % 2 == 0 {
+                transaction
+                    .entry_mut()
+                    .set_data(index.to_be_bytes().to_vec())
+                    .unwrap();
+            }
+            ids.push(transaction.entry().id);
+            transaction.commit().unwrap();
+        }
+
+        // Rolled back transactions are never written to the log.
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        transaction.rollback();
+
+        // Entries are returned in commit order across multiple batches.
+        let entries = roots
+            .transactions()
+            .entries(..)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            ids
+        );
+        for (index, entry) in (0_u32..).zip(&entries) {
+            if index % 2 == 0 {
+                assert_eq!(entry.data().unwrap().as_slice(), &index.to_be_bytes()[..]);
+            } else {
+                assert!(entry.data().is_none());
+            }
+        }
+
+        let entries = roots
+            .transactions()
+            .entries(ids[10]..=ids[80])
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 71);
+        assert_eq!(entries[0].id, ids[10]);
+    }
+
+    #[test]
+    fn basic_transaction_rollback_test() {
+        let tempdir = tempdir().unwrap();
+
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"test", b"value").unwrap();
+
+        // Begin a transaction
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+
+        // Replace the key with a new value.
+        transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .set(b"test", b"updated value")
+            .unwrap();
+
+        // Roll the transaction back
+        transaction.rollback();
+
+        // Ensure that the reader still sees the old value
+        let result = tree.get(b"test").unwrap().expect("key not found");
+        assert_eq!(result, b"value");
+
+        // Begin a new transaction
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        // Check that the transaction has the original value
+        let result = transaction
+            .tree::<Versioned>(0)
+            .unwrap()
+            .get(b"test")
+            .unwrap()
+            .expect("key not found");
+        assert_eq!(result, b"value");
+    }
+
+    #[test]
+    fn std_compact_test_versioned() {
+        compact_test::<Versioned, _>(StdFileManager::default());
+    }
+
+    #[test]
+    fn std_compact_test_unversioned() {
+        compact_test::<Unversioned, _>(StdFileManager::default());
+    }
+
+    #[test]
+    fn memory_compact_test_versioned() {
+        compact_test::<Versioned, _>(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn memory_compact_test_unversioned() {
+        compact_test::<Unversioned, _>(MemoryFileManager::default());
+    }
+
+    #[test]
+    fn any_compact_test_versioned() {
+        compact_test::<Versioned, _>(AnyFileManager::std());
+        compact_test::<Versioned, _>(AnyFileManager::memory());
+    }
+
+    #[test]
+    fn any_compact_test_unversioned() {
+        compact_test::<Unversioned, _>(AnyFileManager::std());
+        compact_test::<Unversioned, _>(AnyFileManager::memory());
+    }
+
+    #[test]
+    fn concurrent_compaction() {
+        const THREADS: usize = 8;
+        let tempdir = tempdir().unwrap();
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        for id in 0_u32..100 {
+            tree.set(id.to_be_bytes().to_vec(), b"value").unwrap();
+            tree.set(id.to_be_bytes().to_vec(), id.to_be_bytes().to_vec())
+                .unwrap();
+        }
+
+        let barrier = Arc::new(std::sync::Barrier::new(THREADS));
+        let threads = (0..THREADS)
+            .map(|_| {
+                let tree = tree.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    tree.compact()
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        for id in 0_u32..100 {
+            assert_eq!(
+                tree.get(&id.to_be_bytes()).unwrap().unwrap(),
+                id.to_be_bytes()
+            );
+        }
+        for entry in std::fs::read_dir(tempdir.path()).unwrap() {
+            let name = entry.unwrap().file_name();
+            assert!(!name.to_string_lossy().ends_with(".compacting"));
+        }
+    }
+
+    fn compact_test<R: Root, M: FileManager>(file_manager: M)
+    where
+        R::Reducer: Default,
+    {
+        const OPERATION_COUNT: usize = 256;
+        const WORKER_COUNT: usize = 4;
+        let tempdir = tempdir().unwrap();
+
+        let roots = Config::new(tempdir.path())
+            .file_manager(file_manager)
+            .open()
+            .unwrap();
+        let tree = roots.tree(R::tree("test")).unwrap();
+        tree.set("foo", b"bar").unwrap();
+
+        // Spawn a pool of threads that will perform a series of operations
+        let mut threads = Vec::new();
+        for worker in 0..WORKER_COUNT {
+            let tree = tree.clone();
+            threads.push(std::thread::spawn(move || {
+                for relative_id in 0..OPERATION_COUNT {
+                    let absolute_id = (worker * OPERATION_COUNT + relative_id) as u64;
+                    tree.set(absolute_id.to_be_bytes(), absolute_id.to_be_bytes())
+                        .unwrap();
+                    let (value, _) = tree
+                        .remove(&absolute_id.to_be_bytes())
+                        .unwrap()
+                        .ok_or_else(|| panic!("value not found: {:?}", absolute_id))
+                        .unwrap();
+                    assert_eq!(BigEndian::read_u64(&value), absolute_id);
+                    tree.set(absolute_id.to_be_bytes(), absolute_id.to_be_bytes())
+                        .unwrap();
+                    let newer_value = tree
+                        .get(&absolute_id.to_be_bytes())
+                        .unwrap()
+                        .expect("couldn't find found");
+                    assert_eq!(value, newer_value);
+                }
+            }));
+        }
+
+        threads.push(std::thread::spawn(move || {
+            // While those workers are running, this thread is going to continually
+            // execute compaction.
+            while tree.count() < (OPERATION_COUNT * WORKER_COUNT) as u64 {
+                tree.compact().unwrap();
+            }
+        }));
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    fn defragment_transaction_log_test<M: FileManager>(file_manager: M) {
+        let tempdir = tempdir().unwrap();
+        let config = Config::new(tempdir.path()).file_manager(file_manager);
+        let roots = config.clone().open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        b.set(b"b", b"0").unwrap();
+        for i in 0_u64..100 {
+            a.set(i.to_be_bytes(), b"a").unwrap();
+        }
+        b.set(b"b", b"1").unwrap();
+        a.set(b"last", b"a").unwrap();
+
+        let original_length = roots.transactions().len();
+        let last_transaction = roots.transactions().current_transaction_id();
+        roots.defragment_transaction_log().unwrap();
+        assert!(roots.transactions().len() < original_length);
+        assert_eq!(
+            roots.transactions().current_transaction_id(),
+            last_transaction
+        );
+
+        assert_eq!(a.count(), 101);
+        assert_eq!(b.get(b"b").unwrap().unwrap(), b"1");
+        b.set(b"b", b"2").unwrap();
+        assert!(roots.transactions().current_transaction_id() > last_transaction);
+        drop((a, b, roots));
+
+        let roots = config.open().unwrap();
+        let a = roots.tree(Versioned::tree("a")).unwrap();
+        let b = roots.tree(Versioned::tree("b")).unwrap();
+        assert_eq!(a.get(b"last").unwrap().unwrap(), b"a");
+        assert_eq!(b.get(b"b").unwrap().unwrap(), b"2");
+    }
+
+    #[test]
+    fn std_defragment_transaction_log() {
+        defragment_transaction_log_test(StdFileManager::default());
+    }
+
+    #[test]
+    fn memory_defragment_transaction_log() {
+        defragment_transaction_log_test(MemoryFileManager::default());
+    }
+
+    struct FillFile(usize);
+
+    impl FileOp<Result<(), Error>> for FillFile {
+        fn execute(self, file: &mut dyn io::File) -> Result<(), Error> {
+            use std::io::Write;
+
+            file.write_all(&vec![0; self.0])?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn defragment_transaction_log_failure() {
+        const CAPACITY: u64 = 10_000_000;
+        let tempdir = tempdir().unwrap();
+        let file_manager = MemoryFileManager::with_capacity(CAPACITY, CapacityPolicy::Error);
+        let roots = Config::new(tempdir.path())
+            .file_manager(file_manager.clone())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        for i in 0_u64..100 {
+            tree.set(i.to_be_bytes(), b"value").unwrap();
+        }
+
+        // Fill the remaining capacity, so the defragmented log can't be written.
+        let filler_path = tempdir.path().join("filler");
+        let remaining = usize::try_from(CAPACITY - file_manager.used_bytes()).unwrap();
+        file_manager
+            .append(&filler_path)
+            .unwrap()
+            .execute(FillFile(remaining))
+            .unwrap();
+        let error = roots.defragment_transaction_log().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::OutOfSpace));
+        file_manager.delete(&filler_path).unwrap();
+
+        // The current log is still used to write transactions.
+        tree.set(b"after", b"value").unwrap();
+        roots.defragment_transaction_log().unwrap();
+        assert_eq!(tree.count(), 101);
+        assert_eq!(tree.get(b"after").unwrap().unwrap(), b"value");
+    }
+
+    #[test]
+    fn get_with_sequence_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert!(tree.get_with_sequence(b"a").unwrap().is_none());
+
+        tree.set(b"a", b"first").unwrap();
+        let (value, first_sequence) = tree.get_with_sequence(b"a").unwrap().unwrap();
+        assert_eq!(value, b"first");
+        assert_eq!(first_sequence, tree.current_sequence_id());
+
+        tree.set(b"b", b"other").unwrap();
+        tree.set(b"a", b"second").unwrap();
+        let (value, second_sequence) = tree.get_with_sequence(b"a").unwrap().unwrap();
+        assert_eq!(value, b"second");
+        assert_eq!(second_sequence, tree.current_sequence_id());
+        assert!(second_sequence > first_sequence);
+
+        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        let mut tree = transaction.tree::<Versioned>(0).unwrap();
+        tree.set(b"a", b"third").unwrap();
+        let (value, third_sequence) = tree.get_with_sequence(b"a").unwrap().unwrap();
+        assert_eq!(value, b"third");
+        assert!(third_sequence > second_sequence);
+    }
+
+    #[test]
+    fn compare_and_swap_sequence_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+
+        tree.compare_and_swap_sequence(b"a", None, Some(ArcBytes::from(b"first")))
+            .unwrap();
+        let (_, sequence) = tree.get_with_sequence(b"a").unwrap().unwrap();
+        assert!(matches!(
+            tree.compare_and_swap_sequence(b"a", None, Some(ArcBytes::from(b"second"))),
+            Err(CompareAndSwapSequenceError::Conflict(Some(existing))) if existing == sequence
+        ));
+
+        tree.compare_and_swap_sequence(b"a", Some(sequence), Some(ArcBytes::from(b"second")))
+            .unwrap();
+        let (value, new_sequence) = tree.get_with_sequence(b"a").unwrap().unwrap();
+        assert_eq!(value, b"second");
+        assert!(matches!(
+            tree.compare_and_swap_sequence(b"a", Some(sequence), None),
+            Err(CompareAndSwapSequenceError::Conflict(Some(existing))) if existing == new_sequence
+        ));
+        assert_eq!(tree.get(b"a").unwrap().unwrap(), b"second");
+
+        tree.compare_and_swap_sequence(b"a", Some(new_sequence), None)
+            .unwrap();
+        assert!(tree.get(b"a").unwrap().is_none());
+        assert!(matches!(
+            tree.compare_and_swap_sequence(b"a", Some(new_sequence), None),
+            Err(CompareAndSwapSequenceError::Conflict(None))
+        ));
+    }
+
+    fn metadata_test<R: Root>()
+    where
+        R::Reducer: Default,
+    {
+        let tempdir = tempdir().unwrap();
+        let config = Config::new(tempdir.path());
+        let roots = config.clone().open().unwrap();
+        let tree = roots.tree(R::tree("test")).unwrap();
+        assert!(tree.metadata().unwrap().is_none());
+
+        tree.set(b"key", b"value").unwrap();
+        tree.set_metadata(Some(ArcBytes::from(b"schema v1")))
+            .unwrap();
+        assert_eq!(tree.metadata().unwrap().unwrap(), b"schema v1");
+        assert_eq!(tree.count(), 1);
+
+        // Metadata changes are only visible once the transaction is committed.
+        let transaction = roots.transaction(&[R::tree("test")]).unwrap();
+        let mut transaction_tree = transaction.tree::<R>(0).unwrap();
+        transaction_tree
+            .set_metadata(Some(ArcBytes::from(b"schema v2")))
+            .unwrap();
+        assert_eq!(transaction_tree.metadata().unwrap(), b"schema v2");
+        drop(transaction_tree);
+        transaction.rollback();
+        assert_eq!(tree.metadata().unwrap().unwrap(), b"schema v1");
+
+        assert!(matches!(
+            tree.set_metadata(Some(ArcBytes::from(vec![0; 65_536])))
+                .unwrap_err()
+                .kind,
+            ErrorKind::ValueTooLarge
+        ));
+
+        tree.compact().unwrap();
+        assert_eq!(tree.metadata().unwrap().unwrap(), b"schema v1");
+        drop((tree, roots));
+
+        let roots = config.open().unwrap();
+        let tree = roots.tree(R::tree("test")).unwrap();
+        assert_eq!(tree.metadata().unwrap().unwrap(), b"schema v1");
+        assert_eq!(tree.get(b"key").unwrap().unwrap(), b"value");
+        tree.set_metadata(None).unwrap();
+        assert!(tree.metadata().unwrap().is_none());
+    }
+
+    #[test]
+    fn metadata_versioned() {
+        metadata_test::<Versioned>();
+    }
+
+    #[test]
+    fn metadata_unversioned() {
+        metadata_test::<Unversioned>();
+    }
+
+    #[test]
+    fn scan_keys_reduce_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Versioned::tree("test")).unwrap();
+        let prefix = |key: &[u8]| key[0];
+        assert!(tree.scan_keys_reduce(&(..), prefix).unwrap().is_empty());
+
+        // Insert 1,000 keys for each of 10 prefixes.
+        let key = |prefix: u8, id: u16| {
+            let mut key = vec![prefix];
+            key.extend(id.to_be_bytes());
+            ArcBytes::from(key)
+        };
+        for group in 0..10 {
+            tree.modify(
+                (0..1000).map(|id| key(group, id)).collect(),
+                Operation::Set(ArcBytes::from(b"value")),
+            )
+            .unwrap();
+        }
+        tree.modify((0..100).map(|id| key(3, id)).collect(), Operation::Remove)
+            .unwrap();
+
+        let counts = tree.scan_keys_reduce(&(..), prefix).unwrap();
+        assert_eq!(counts.len(), 10);
+        for group in 0..10 {
+            let expected = if group == 3 { 900 } else { 1000 };
+            assert_eq!(counts[&group], expected);
+        }
+
+        let start = key(2, 500);
+        let end = key(5, 0);
+        let counts = tree
+            .scan_keys_reduce(&(start.as_slice()..end.as_slice()), prefix)
+            .unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&2], 500);
+        assert_eq!(counts[&3], 900);
+        assert_eq!(counts[&4], 1000);
+    }
+
+    #[test]
+    fn count_range_test() {
+        const KEYS: u64 = 10_000;
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Versioned::tree("test")).unwrap();
+        let key = |id: u64| ArcBytes::from(id.to_be_bytes());
+        assert_eq!(tree.count_range(&(..)).unwrap(), 0);
+
+        tree.modify(
+            (0..KEYS).map(key).collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
+        tree.modify((0..100).map(key).collect(), Operation::Remove)
+            .unwrap();
+
+        assert_eq!(tree.count_range(&(..)).unwrap(), KEYS - 100);
+        let (start, end) = (key(1_000), key(3_000));
+        assert_eq!(
+            tree.count_range(&(start.as_slice()..end.as_slice()))
+                .unwrap(),
+            2_000
+        );
+        assert_eq!(
+            tree.count_range(&(start.as_slice()..=end.as_slice()))
+                .unwrap(),
+            2_001
+        );
+        assert_eq!(
+            tree.count_range(&(
+                Bound::Excluded(start.as_slice()),
+                Bound::Included(end.as_slice())
+            ))
+            .unwrap(),
+            2_000
+        );
+        let removed = key(50);
+        assert_eq!(tree.count_range(&(..removed.as_slice())).unwrap(), 0);
+        assert_eq!(
+            tree.count_range(&(end.as_slice()..start.as_slice()))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            tree.count_range(&(start.as_slice()..start.as_slice()))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn numeric_reducers() {
+        use crate::tree::{
+            reducers::{CountIndex, IntegerIndexer, MaxIndex, MinIndex, Sum, SumIndex},
+            ByIdIndexer, UnversionedTreeRoot, VersionedTreeRoot,
+        };
 
-impl AbortError<Infallible> {
-    /// Unwraps the error contained within an infallible abort error.
-    #[must_use]
-    pub fn infallible(self) -> Error {
-        match self {
-            AbortError::Other(_) => unreachable!(),
-            AbortError::Nebari(error) => error,
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let sums = roots
+            .tree(VersionedTreeRoot::<SumIndex>::tree("sums"))
+            .unwrap();
+        let minimums = roots
+            .tree(UnversionedTreeRoot::<MinIndex>::tree("minimums"))
+            .unwrap();
+        let maximums = roots
+            .tree(VersionedTreeRoot::<MaxIndex>::tree("maximums"))
+            .unwrap();
+        assert!(sums.reduce(&(..)).unwrap().is_none());
+        for value in -50_i64..50 {
+            let key = (value + 50).to_be_bytes();
+            sums.set(key, value.to_be_bytes().to_vec()).unwrap();
+            minimums.set(key, value.to_be_bytes().to_vec()).unwrap();
+            maximums.set(key, value.to_be_bytes().to_vec()).unwrap();
         }
+        // Values too short to contain an integer are ignored.
+        sums.set(b"short", b"short").unwrap();
+        maximums.set(b"short", b"short").unwrap();
+        // Removed values are no longer included.
+        maximums.remove(&99_i64.to_be_bytes()).unwrap();
+
+        assert_eq!(sums.reduce(&(..)).unwrap().unwrap().embedded, Sum(-50));
+        let start = 60_i64.to_be_bytes();
+        let end = 70_i64.to_be_bytes();
+        assert_eq!(
+            sums.reduce(&(&start[..]..&end[..]))
+                .unwrap()
+                .unwrap()
+                .embedded,
+            Sum((10..20).sum())
+        );
+        assert_eq!(
+            minimums.reduce(&(..)).unwrap().unwrap().embedded,
+            MinIndex(Some(-50))
+        );
+        assert_eq!(
+            maximums.reduce(&(..)).unwrap().unwrap().embedded,
+            MaxIndex(Some(48))
+        );
+
+        // Count the 2-byte integers following a 1-byte header.
+        let counts = roots
+            .tree(UnversionedTreeRoot::<CountIndex>::tree_with_reducer(
+                "counts",
+                ByIdIndexer(IntegerIndexer::new(1, 2)),
+            ))
+            .unwrap();
+        counts.set(b"a", vec![0, 0xFF, 0xFE]).unwrap();
+        counts.set(b"b", vec![0, 1]).unwrap();
+        counts.set(b"c", vec![0, 0, 1, 0]).unwrap();
+        assert_eq!(
+            counts.reduce(&(..)).unwrap().unwrap().embedded,
+            CountIndex(2)
+        );
+        assert_eq!(IntegerIndexer::new(1, 2).read(&[0, 0xFF, 0xFE]), Some(-2));
     }
-}
 
-/// A thread pool that commits transactions to disk in parallel.
-#[derive(Debug)]
-pub struct ThreadPool<File>
-where
-    File: ManagedFile,
-{
-    sender: flume::Sender<ThreadCommit<File>>,
-    receiver: flume::Receiver<ThreadCommit<File>>,
-    thread_count: Arc<AtomicU16>,
-    maximum_threads: usize,
-}
+    #[test]
+    fn histogram_reducer() {
+        use crate::tree::{
+            reducers::{Histogram, HistogramIndex, HistogramIndexer, IntegerIndexer},
+            ByIdIndexer, UnversionedTreeRoot, VersionedTreeRoot,
+        };
 
-impl<File: ManagedFile> ThreadPool<File> {
-    /// Returns a thread pool that will spawn up to `maximum_threads` to process
-    /// file operations.
-    #[must_use]
-    pub fn new(maximum_threads: usize) -> Self {
-        let (sender, receiver) = flume::unbounded();
-        Self {
-            sender,
-            receiver,
-            thread_count: Arc::new(AtomicU16::new(0)),
-            maximum_threads,
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let sizes = roots
+            .tree(VersionedTreeRoot::<HistogramIndex>::tree_with_reducer(
+                "sizes",
+                ByIdIndexer(HistogramIndexer::value_lengths(&[10, 100])),
+            ))
+            .unwrap();
+        assert!(sizes.reduce(&(..)).unwrap().is_none());
+        for length in 0_u32..200 {
+            sizes
+                .set(length.to_be_bytes(), vec![0; length as usize])
+                .unwrap();
+        }
+        assert_eq!(
+            sizes.reduce(&(..)).unwrap().unwrap().embedded,
+            Histogram(vec![10, 90, 100])
+        );
+        let start = 50_u32.to_be_bytes();
+        let end = 150_u32.to_be_bytes();
+        let histogram = sizes
+            .reduce(&(&start[..]..&end[..]))
+            .unwrap()
+            .unwrap()
+            .embedded;
+        assert_eq!(histogram, Histogram(vec![0, 50, 50]));
+        assert_eq!(histogram.total(), 100);
+
+        // Removed values are no longer counted.
+        sizes.remove(&0_u32.to_be_bytes()).unwrap();
+        assert_eq!(
+            sizes.reduce(&(..)).unwrap().unwrap().embedded,
+            Histogram(vec![9, 90, 100])
+        );
+
+        // Bucket the integers stored in each value.
+        let numbers = roots
+            .tree(UnversionedTreeRoot::<HistogramIndex>::tree_with_reducer(
+                "numbers",
+                ByIdIndexer(HistogramIndexer::integers(
+                    IntegerIndexer::default(),
+                    &[-10, 0, 10],
+                )),
+            ))
+            .unwrap();
+        for value in -20_i64..20 {
+            numbers
+                .set((value + 20).to_be_bytes(), value.to_be_bytes().to_vec())
+                .unwrap();
         }
+        // Values too short to contain an integer aren't counted.
+        numbers.set(b"short", b"short").unwrap();
+        assert_eq!(
+            numbers.reduce(&(..)).unwrap().unwrap().embedded,
+            Histogram(vec![10, 10, 10, 10])
+        );
+
+        let indexer = HistogramIndexer::value_lengths(&[10, 100]);
+        assert_eq!(indexer.bucket_count(), 3);
+        assert_eq!(indexer.bucket(&[0; 9]), Some(0));
+        assert_eq!(indexer.bucket(&[0; 10]), Some(1));
+        assert_eq!(indexer.bucket(&[0; 100]), Some(2));
     }
 
-    fn commit_trees(
-        &self,
-        trees: Vec<UnlockedTransactionTree<File>>,
-    ) -> Result<Vec<Box<dyn AnyTransactionTree<File>>>, Error> {
-        // If we only have one tree, there's no reason to split IO across
-        // threads. If we have multiple trees, we should split even with one
-        // cpu: if one thread blocks, the other can continue executing.
-        if trees.len() == 1 {
-            let mut tree = trees.into_iter().next().unwrap().0.into_inner();
-            tree.commit()?;
-            Ok(vec![tree])
-        } else {
-            // Push the trees so that any existing threads can begin processing the queue.
-            let (completion_sender, completion_receiver) = flume::unbounded();
-            let tree_count = trees.len();
-            for tree in trees {
-                self.sender.send(ThreadCommit {
-                    tree: tree.0.into_inner(),
-                    completion_sender: completion_sender.clone(),
-                })?;
-            }
+    #[test]
+    #[should_panic(expected = "histogram boundaries must be in strictly ascending order")]
+    fn histogram_unordered_boundaries() {
+        drop(crate::tree::reducers::HistogramIndexer::value_lengths(&[
+            10, 10,
+        ]));
+    }
 
-            // Scale the queue if needed.
-            let desired_threads = tree_count.min(self.maximum_threads);
-            loop {
-                let thread_count = self.thread_count.load(Ordering::SeqCst);
-                if (thread_count as usize) >= desired_threads {
-                    break;
-                }
+    #[test]
+    fn scan_cancellable_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        for id in 0_u32..100 {
+            tree.set(id.to_be_bytes().to_vec(), b"value").unwrap();
+        }
 
-                // Spawn a thread, but ensure that we don't spin up too many threads if another thread is committing at the same time.
-                if self
-                    .thread_count
-                    .compare_exchange(
-                        thread_count,
-                        thread_count + 1,
-                        Ordering::SeqCst,
-                        Ordering::SeqCst,
-                    )
-                    .is_ok()
-                {
-                    let commit_receiver = self.receiver.clone();
-                    std::thread::Builder::new()
-                        .name(String::from("roots-txwriter"))
-                        .spawn(move || transaction_commit_thread(commit_receiver))
-                        .unwrap();
+        let cancellation = CancellationToken::new();
+        let mut keys_read = 0;
+        let result = tree.scan_cancellable::<Infallible, _, _, _, _>(
+            &(..),
+            true,
+            &cancellation,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| {
+                keys_read += 1;
+                if keys_read == 10 {
+                    cancellation.cancel();
                 }
-            }
+                ScanEvaluation::ReadData
+            },
+            |_, _, _| Ok(()),
+        );
+        assert!(matches!(
+            result.err().unwrap().into_nebari().unwrap().kind,
+            ErrorKind::Cancelled
+        ));
+        assert_eq!(keys_read, 10);
+
+        // A cancelled token stops scans before any keys are visited.
+        let result = tree.scan_cancellable::<Infallible, _, _, _, _>(
+            &(..),
+            true,
+            &cancellation,
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| unreachable!(),
+            |_, _, _| Ok(()),
+        );
+        assert!(result.is_err());
 
-            // Wait for our results
-            let mut results = Vec::with_capacity(tree_count);
-            for _ in 0..tree_count {
-                results.push(completion_receiver.recv()??);
-            }
+        let mut keys_read = 0;
+        tree.scan_cancellable::<Infallible, _, _, _, _>(
+            &(..),
+            true,
+            &CancellationToken::new(),
+            |_, _, _| ScanEvaluation::ReadData,
+            |_, _| {
+                keys_read += 1;
+                ScanEvaluation::ReadData
+            },
+            |_, _, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(keys_read, 100);
+    }
+
+    #[test]
+    fn changes_after_resumes_without_duplicates() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
 
-            Ok(results)
+        let mut delivered = Vec::new();
+        let mut last_processed = SequenceId::default();
+        for batch in 0_u8..3 {
+            for key in 0_u8..5 {
+                tree.set(vec![batch, key], b"value").unwrap();
+            }
+            tree.changes_after::<Infallible, _, _>(
+                last_processed,
+                &mut |sequence| {
+                    delivered.push(sequence.sequence);
+                    last_processed = sequence.sequence;
+                    ScanEvaluation::Skip
+                },
+                &mut |_, _| unreachable!(),
+            )
+            .unwrap();
+            assert_eq!(delivered.len(), usize::from(batch + 1) * 5);
         }
+        assert!(delivered.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(last_processed, tree.current_sequence_id());
     }
-}
 
-impl<File: ManagedFile> Clone for ThreadPool<File> {
-    fn clone(&self) -> Self {
-        Self {
-            sender: self.sender.clone(),
-            receiver: self.receiver.clone(),
-            thread_count: self.thread_count.clone(),
-            maximum_threads: self.maximum_threads,
+    #[test]
+    fn open_at_transaction() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        let mut transaction_ids = Vec::new();
+        for value in 0_u8..3 {
+            tree.set(b"key", vec![value]).unwrap();
+            transaction_ids.push(roots.transactions().current_transaction_id().unwrap());
+        }
+        let other = roots.tree(Versioned::tree("other")).unwrap();
+        other.set(b"key", b"value").unwrap();
+        let unrelated_id = roots.transactions().current_transaction_id().unwrap();
+
+        let open = |transaction_id| {
+            TreeFile::<Versioned, StdFile>::open_at_transaction(
+                tree.path(),
+                transaction_id,
+                State::default(),
+                roots.context(),
+                roots.transactions(),
+            )
+        };
+        for (value, transaction_id) in transaction_ids.iter().enumerate() {
+            let mut historical = open(*transaction_id).unwrap();
+            assert_eq!(
+                historical.get(b"key", false).unwrap().as_deref(),
+                Some(&[u8::try_from(value).unwrap()][..])
+            );
         }
-    }
-}
 
-impl<File: ManagedFile> Default for ThreadPool<File> {
-    fn default() -> Self {
-        static CPU_COUNT: Lazy<usize> = Lazy::new(num_cpus::get);
-        Self::new(*CPU_COUNT)
-    }
-}
+        // Transactions that didn't modify the tree use the tree's root from
+        // the most recent transaction before them.
+        let mut historical = open(unrelated_id).unwrap();
+        assert_eq!(
+            historical.get(b"key", false).unwrap().as_deref(),
+            Some(&[2][..])
+        );
 
-#[allow(clippy::needless_pass_by_value)]
-fn transaction_commit_thread<File: ManagedFile>(receiver: flume::Receiver<ThreadCommit<File>>) {
-    while let Ok(ThreadCommit {
-        mut tree,
-        completion_sender,
-    }) = receiver.recv()
-    {
-        let result = tree.commit();
-        let result = result.map(move |_| tree);
-        drop(completion_sender.send(result));
+        assert!(open(TransactionId(transaction_ids[0].0 - 1)).is_err());
     }
-}
 
-struct ThreadCommit<File>
-where
-    File: ManagedFile,
-{
-    tree: Box<dyn AnyTransactionTree<File>>,
-    completion_sender: Sender<Result<Box<dyn AnyTransactionTree<File>>, Error>>,
-}
+    #[test]
+    fn sequence_range() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert_eq!(tree.sequence_range().unwrap(), None);
+
+        tree.set(b"a", b"0").unwrap();
+        let first = tree.current_sequence_id();
+        tree.set(b"b", b"0").unwrap();
+        tree.set(b"a", b"1").unwrap();
+        let last = tree.current_sequence_id();
+        assert_eq!(tree.sequence_range().unwrap(), Some((first, last)));
+
+        // Compaction removes the first version of "a".
+        tree.compact().unwrap();
+        let (compacted_first, compacted_last) = tree.sequence_range().unwrap().unwrap();
+        assert!(compacted_first > first);
+        assert_eq!(compacted_last, last);
+    }
 
-fn catch_compaction_and_retry<R, F: Fn() -> Result<R, Error>>(func: F) -> Result<R, Error> {
-    loop {
-        match func() {
-            Ok(result) => return Ok(result),
-            Err(error) => {
-                if matches!(error.kind, ErrorKind::TreeCompacted) {
-                    continue;
+    #[test]
+    fn export_changes_after_test() {
+        fn replicate(
+            tree: &Tree<Versioned, StdFile>,
+            backup: &Tree<Versioned, StdFile>,
+            after: SequenceId,
+        ) -> SequenceId {
+            let mut last = after;
+            tree.export_changes_after::<Infallible, _>(after, &mut |key, value| {
+                assert!(key.sequence > last);
+                last = key.sequence;
+                match value {
+                    Some(value) => backup.set(key.key, value)?,
+                    None => {
+                        backup.remove(&key.key)?;
+                    }
                 }
+                Ok(())
+            })
+            .unwrap();
+            last
+        }
 
-                return Err(error);
-            }
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("source")).unwrap();
+        let backup = roots.tree(Versioned::tree("backup")).unwrap();
+
+        // Enough changes to require multiple batches.
+        for id in 0_u32..2_500 {
+            tree.set(id.to_be_bytes().to_vec(), id.to_string().into_bytes())
+                .unwrap();
         }
-    }
-}
+        let last = replicate(&tree, &backup, SequenceId::default());
+        assert_eq!(last, tree.current_sequence_id());
+        assert_eq!(backup.count(), 2_500);
+
+        // Only the changes since the last export are delivered, and removals
+        // are replicated.
+        tree.remove(&0_u32.to_be_bytes()).unwrap();
+        tree.set(1_u32.to_be_bytes().to_vec(), b"updated").unwrap();
+        tree.remove(&1_u32.to_be_bytes()).unwrap();
+        tree.set(1_u32.to_be_bytes().to_vec(), b"restored").unwrap();
+        let mut exported = Vec::new();
+        tree.export_changes_after::<Infallible, _>(last, &mut |key, value| {
+            exported.push((key.key, value));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            exported,
+            vec![
+                (ArcBytes::from(0_u32.to_be_bytes().to_vec()), None),
+                (
+                    ArcBytes::from(1_u32.to_be_bytes().to_vec()),
+                    Some(ArcBytes::from(b"updated"))
+                ),
+                (ArcBytes::from(1_u32.to_be_bytes().to_vec()), None),
+                (
+                    ArcBytes::from(1_u32.to_be_bytes().to_vec()),
+                    Some(ArcBytes::from(b"restored"))
+                ),
+            ]
+        );
 
-fn catch_compaction_and_retry_abortable<
-    R,
-    E: Display + Debug,
-    F: FnMut() -> Result<R, AbortError<E>>,
->(
-    mut func: F,
-) -> Result<R, AbortError<E>> {
-    loop {
-        match func() {
-            Ok(result) => return Ok(result),
-            Err(AbortError::Nebari(error)) => {
-                if matches!(error.kind, ErrorKind::TreeCompacted) {
-                    continue;
-                }
+        let last = replicate(&tree, &backup, last);
+        assert_eq!(last, tree.current_sequence_id());
+        assert_eq!(backup.get(&0_u32.to_be_bytes()).unwrap(), None);
+        assert_eq!(
+            backup.get(&1_u32.to_be_bytes()).unwrap().unwrap(),
+            b"restored"
+        );
+        assert_eq!(backup.count(), 2_499);
+    }
 
-                return Err(AbortError::Nebari(error));
+    #[test]
+    fn get_page_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        for id in 0_u8..10 {
+            tree.set(vec![id], vec![id]).unwrap();
+        }
+        tree.remove(&[3_u8]).unwrap();
+
+        let range = &[1_u8][..]..&[9_u8][..];
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = tree.get_page(&range, cursor.as_ref(), 3).unwrap();
+            pages.push(page.into_iter().map(|(key, _)| key[0]).collect::<Vec<_>>());
+            cursor = next;
+            if cursor.is_none() {
+                break;
             }
-            Err(other) => return Err(other),
+
+            // Cursors are based on keys, so they remain valid after the tree
+            // is modified or compacted.
+            tree.compact().unwrap();
         }
+        assert_eq!(pages, vec![vec![1, 2, 4], vec![5, 6, 7], vec![8]]);
+
+        // The last page is detected when it is exactly full.
+        let (page, next) = tree.get_page(&range, None, 7).unwrap();
+        assert_eq!(page.len(), 7);
+        assert!(next.is_none());
+
+        // Cursors can be restored from a key, and cursors before the start of
+        // the range resume at the start of the range.
+        let (page, _) = tree
+            .get_page(&range, Some(&ScanCursor::after(vec![6])), 10)
+            .unwrap();
+        assert_eq!(page.len(), 2);
+        let (page, _) = tree
+            .get_page(&range, Some(&ScanCursor::after(vec![0])), 1)
+            .unwrap();
+        assert_eq!(page[0].0, b"\x01");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use byteorder::{BigEndian, ByteOrder};
-    use tempfile::tempdir;
+    #[test]
+    fn get_range_limited() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        let range = &[1_u8][..]..&[9_u8][..];
+        assert_eq!(
+            tree.get_range_limited(&range, 3).unwrap(),
+            (Vec::new(), false)
+        );
+        for id in 0_u8..10 {
+            tree.set(vec![id], vec![id]).unwrap();
+        }
+        tree.remove(&[3_u8]).unwrap();
+
+        let (results, more) = tree.get_range_limited(&range, 3).unwrap();
+        assert_eq!(
+            results
+                .into_iter()
+                .map(|(key, _)| key[0])
+                .collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert!(more);
 
-    use super::*;
-    use crate::{
-        io::{any::AnyFileManager, fs::StdFileManager, memory::MemoryFileManager},
-        test_util::RotatorVault,
-        tree::{Root, Unversioned, Versioned},
-    };
+        // Exactly filling the limit doesn't report more keys.
+        let (results, more) = tree.get_range_limited(&range, 7).unwrap();
+        assert_eq!(results.len(), 7);
+        assert!(!more);
+    }
 
-    fn basic_get_set<M: FileManager>(file_manager: M) {
+    #[test]
+    fn tree_iter() {
         let tempdir = tempdir().unwrap();
-        let roots = Config::new(tempdir.path())
-            .file_manager(file_manager)
-            .open()
-            .unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        assert_eq!(tree.iter().unwrap().count(), 0);
 
-        let tree = roots.tree(Versioned::tree("test")).unwrap();
-        tree.set(b"test", b"value").unwrap();
-        let result = tree.get(b"test").unwrap().expect("key not found");
+        for id in 0_u32..200 {
+            tree.set(id.to_be_bytes(), id.to_le_bytes()).unwrap();
+        }
+        tree.remove(&10_u32.to_be_bytes()).unwrap();
+
+        let iter = tree.iter().unwrap();
+        // Writes made after the iterator was created aren't observed.
+        tree.set(500_u32.to_be_bytes(), b"new").unwrap();
+        tree.remove(&20_u32.to_be_bytes()).unwrap();
+        let keys = iter
+            .map(|entry| {
+                let (key, value) = entry.unwrap();
+                let id = u32::from_be_bytes(key[..].try_into().unwrap());
+                assert_eq!(&value[..], &id.to_le_bytes()[..]);
+                id
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            (0_u32..200).filter(|&id| id != 10).collect::<Vec<_>>()
+        );
 
-        assert_eq!(result, b"value");
+        let start = 100_u32.to_be_bytes();
+        let end = 150_u32.to_be_bytes();
+        let mut keys = Vec::new();
+        for entry in tree.iter_range(&(&start[..]..&end[..])).unwrap() {
+            let (key, _) = entry.unwrap();
+            keys.push(u32::from_be_bytes(key[..].try_into().unwrap()));
+        }
+        assert_eq!(keys, (100_u32..150).collect::<Vec<_>>());
     }
 
     #[test]
-    fn memory_basic_get_set() {
-        basic_get_set(MemoryFileManager::default());
+    fn max_order() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let limited = roots
+            .tree(Unversioned::tree("limited").with_max_order(4))
+            .unwrap();
+        let unlimited = roots.tree(Unversioned::tree("unlimited")).unwrap();
+        for id in 0_u32..1000 {
+            limited.set(id.to_be_bytes(), b"value").unwrap();
+            unlimited.set(id.to_be_bytes(), b"value").unwrap();
+        }
+        let limited_stats = limited.statistics().unwrap();
+        let unlimited_stats = unlimited.statistics().unwrap();
+        assert_eq!(limited_stats.order, 4);
+        assert!(unlimited_stats.order > 4);
+        assert!(limited_stats.depth() > unlimited_stats.depth());
+        drop(limited);
+        drop(unlimited);
+        drop(roots);
+
+        // The maximum order isn't persisted, and changing it is safe.
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let reopened = roots.tree(Unversioned::tree("limited")).unwrap();
+        reopened.set(b"another", b"value").unwrap();
+        assert_eq!(reopened.statistics().unwrap().order, unlimited_stats.order);
+        for id in 0_u32..1000 {
+            assert!(reopened.get(&id.to_be_bytes()).unwrap().is_some());
+        }
     }
 
     #[test]
-    fn std_basic_get_set() {
-        basic_get_set(StdFileManager::default());
+    #[should_panic(expected = "max_order must be at least 4")]
+    fn max_order_too_small() {
+        drop(Unversioned::tree::<StdFile>("invalid").with_max_order(3));
     }
 
     #[test]
-    fn basic_transaction_isolation_test() {
+    fn scan_with_progress_test() {
+        const KEYS: u64 = 10_000;
         let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Unversioned::tree("test")).unwrap();
+        tree.modify(
+            (0..KEYS)
+                .map(|id| ArcBytes::from(id.to_be_bytes()))
+                .collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
 
-        let roots = Config::<StdFileManager>::new(tempdir.path())
-            .open()
+        let mut reports = Vec::new();
+        let results = tree
+            .get_range_with_progress(&(..), |progress| reports.push(progress))
             .unwrap();
-        let tree = roots.tree(Versioned::tree("test")).unwrap();
-        tree.set(b"test", b"value").unwrap();
-
-        // Begin a transaction
-        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
-
-        // Replace the key with a new value.
-        transaction
-            .tree::<Versioned>(0)
-            .unwrap()
-            .set(b"test", b"updated value")
+        assert_eq!(results.len(), KEYS as usize);
+        assert_eq!(reports.len(), KEYS as usize);
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].keys_visited < pair[1].keys_visited));
+        let last = reports.last().unwrap();
+        assert_eq!(last.keys_visited, KEYS);
+        assert_eq!(last.estimated_keys, KEYS);
+        assert!((last.fraction() - 1.).abs() < f64::EPSILON);
+
+        // Skipped nodes contribute their key counts without visiting keys.
+        let mut last_progress = None;
+        tree.scan_with_progress::<Infallible, _, _, _, _, _>(
+            &(..),
+            true,
+            |_, _, depth| {
+                if depth == 0 {
+                    ScanEvaluation::Skip
+                } else {
+                    ScanEvaluation::ReadData
+                }
+            },
+            |_, _| ScanEvaluation::ReadData,
+            |_, _, _| Ok(()),
+            |progress| last_progress = Some(progress),
+        )
+        .unwrap();
+        assert_eq!(last_progress.unwrap().keys_visited, KEYS);
+
+        // Only the keys of skipped nodes that are within the range are
+        // counted, in either direction.
+        let start = 1_000_u64.to_be_bytes();
+        let end = 2_000_u64.to_be_bytes();
+        for forwards in [true, false] {
+            let mut last_progress = None;
+            tree.scan_with_progress::<Infallible, _, _, _, _, _>(
+                &(&start[..]..&end[..]),
+                forwards,
+                |_, _, depth| {
+                    if depth == 0 {
+                        ScanEvaluation::Skip
+                    } else {
+                        ScanEvaluation::ReadData
+                    }
+                },
+                |_, _| ScanEvaluation::ReadData,
+                |_, _, _| Ok(()),
+                |progress| last_progress = Some(progress),
+            )
             .unwrap();
+            let keys_visited = last_progress.unwrap().keys_visited;
+            assert!(keys_visited.max(1_000) - keys_visited.min(1_000) <= 5);
+        }
+    }
 
-        // Check that the transaction can read the new value
-        let result = transaction
-            .tree::<Versioned>(0)
-            .unwrap()
-            .get(b"test")
-            .unwrap()
-            .expect("key not found");
-        assert_eq!(result, b"updated value");
+    #[test]
+    fn abort_error_origin() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        tree.set(b"a", b"value").unwrap();
+
+        let error = tree
+            .scan(
+                &(..),
+                true,
+                |_, _, _| ScanEvaluation::ReadData,
+                |_, _| ScanEvaluation::ReadData,
+                |_, _, _| Err(AbortError::Other("aborted")),
+            )
+            .unwrap_err();
+        assert_eq!(error.as_caller(), Some(&"aborted"));
+        assert!(error.as_nebari().is_none());
+        assert_eq!(error.into_caller(), Some("aborted"));
+
+        let error = AbortError::<&str>::from(Error::from(ErrorKind::TreeCompacted));
+        assert!(error.as_caller().is_none());
+        assert!(matches!(
+            error.into_nebari().unwrap().kind,
+            ErrorKind::TreeCompacted
+        ));
+    }
 
-        // Ensure that existing read-access doesn't see the new value
-        let result = tree.get(b"test").unwrap().expect("key not found");
-        assert_eq!(result, b"value");
+    fn bulk_remove_sorted_test<R: Root>()
+    where
+        R::Reducer: Default,
+    {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(R::tree("test")).unwrap();
+        let key = |id: u32| ArcBytes::from(id.to_be_bytes());
+        let mut keys = (0..1000).map(key).collect::<Vec<_>>();
+        tree.modify(keys.clone(), Operation::Set(ArcBytes::from(b"value")))
+            .unwrap();
 
-        // Commit the transaction
-        transaction.commit().unwrap();
+        // Remove a large contiguous run along with a few sparse keys.
+        let removed = (100..900)
+            .chain([901, 950, 2000])
+            .map(key)
+            .collect::<Vec<_>>();
+        tree.bulk_remove_sorted(removed.clone()).unwrap();
+        keys.retain(|key| !removed.contains(key));
+        let remaining = tree
+            .get_range(&(..))
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, keys);
 
-        // Ensure that the reader now sees the new value
-        let result = tree.get(b"test").unwrap().expect("key not found");
-        assert_eq!(result, b"updated value");
+        let error = tree.bulk_remove_sorted(vec![key(2), key(1)]).err().unwrap();
+        assert!(matches!(error.kind, ErrorKind::KeysNotOrdered));
     }
 
-    #[test]
-    fn basic_transaction_rollback_test() {
+    fn remove_range_test<R: Root>()
+    where
+        R::Reducer: Default,
+    {
         let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(R::tree("test")).unwrap();
+        let key = |id: u32| ArcBytes::from(id.to_be_bytes());
+        tree.modify(
+            (0..25_000).map(key).collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
+        tree.remove(&key(10)).unwrap();
 
-        let roots = Config::<StdFileManager>::new(tempdir.path())
-            .open()
-            .unwrap();
-        let tree = roots.tree(Versioned::tree("test")).unwrap();
-        tree.set(b"test", b"value").unwrap();
+        // Empty ranges remove nothing.
+        assert_eq!(tree.remove_range(&(&key(5)[..]..&key(5)[..])).unwrap(), 0);
 
-        // Begin a transaction
-        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
+        // Previously removed keys aren't counted.
+        assert_eq!(tree.remove_range(&(&key(0)[..]..&key(20)[..])).unwrap(), 19);
 
-        // Replace the key with a new value.
-        transaction
-            .tree::<Versioned>(0)
+        // Ranges larger than a single batch are removed completely.
+        assert_eq!(
+            tree.remove_range(&(&key(100)[..]..=&key(24_000)[..]))
+                .unwrap(),
+            23_901
+        );
+        assert_eq!(tree.remove_range(&(&key(100)[..]..)).unwrap(), 999);
+
+        let remaining = tree
+            .get_range(&(..))
             .unwrap()
-            .set(b"test", b"updated value")
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, (20..100).map(key).collect::<Vec<_>>());
+        assert_eq!(tree.count(), 80);
+    }
+
+    #[test]
+    fn par_scan_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Unversioned::tree("test")).unwrap();
+        let key = |id: u32| ArcBytes::from(id.to_be_bytes());
+        tree.modify(
+            (0..25_000).map(key).collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
+
+        let collect = |range: (Bound<&[u8]>, Bound<&[u8]>), parallelism| {
+            let keys = Arc::new(Mutex::new(Vec::new()));
+            let collected = keys.clone();
+            tree.par_scan::<Infallible, _, _, _>(
+                &range,
+                parallelism,
+                |_, _| ScanEvaluation::ReadData,
+                move |key, _, value| {
+                    assert_eq!(value, b"value");
+                    collected.lock().push(key);
+                    Ok(())
+                },
+            )
             .unwrap();
+            let mut keys = std::mem::take(&mut *keys.lock());
+            keys.sort();
+            keys
+        };
 
-        // Roll the transaction back
-        transaction.rollback();
+        // Every key is visited exactly once.
+        let all_keys = (0..25_000).map(key).collect::<Vec<_>>();
+        for parallelism in [None, Some(1), Some(4), Some(100)] {
+            assert_eq!(
+                collect((Bound::Unbounded, Bound::Unbounded), parallelism),
+                all_keys
+            );
+        }
+        assert_eq!(
+            collect(
+                (
+                    Bound::Excluded(&key(100)[..]),
+                    Bound::Included(&key(20_000)[..])
+                ),
+                Some(4)
+            ),
+            (101..=20_000).map(key).collect::<Vec<_>>()
+        );
 
-        // Ensure that the reader still sees the old value
-        let result = tree.get(b"test").unwrap().expect("key not found");
-        assert_eq!(result, b"value");
+        // The first error is returned.
+        let error = tree
+            .par_scan(
+                &(..),
+                Some(4),
+                |_, _| ScanEvaluation::ReadData,
+                move |scanned, _, _| {
+                    if scanned == key(24_999) {
+                        Err(AbortError::Other("failed"))
+                    } else {
+                        Ok(())
+                    }
+                },
+            )
+            .unwrap_err();
+        assert!(matches!(error, AbortError::Other("failed")));
+    }
 
-        // Begin a new transaction
-        let transaction = roots.transaction(&[Versioned::tree("test")]).unwrap();
-        // Check that the transaction has the original value
-        let result = transaction
-            .tree::<Versioned>(0)
-            .unwrap()
-            .get(b"test")
-            .unwrap()
-            .expect("key not found");
-        assert_eq!(result, b"value");
+    #[test]
+    fn remove_range_versioned() {
+        remove_range_test::<Versioned>();
     }
 
     #[test]
-    fn std_compact_test_versioned() {
-        compact_test::<Versioned, _>(StdFileManager::default());
+    fn remove_range_unversioned() {
+        remove_range_test::<Unversioned>();
     }
 
     #[test]
-    fn std_compact_test_unversioned() {
-        compact_test::<Unversioned, _>(StdFileManager::default());
+    fn bulk_remove_sorted_versioned() {
+        bulk_remove_sorted_test::<Versioned>();
     }
 
     #[test]
-    fn memory_compact_test_versioned() {
-        compact_test::<Versioned, _>(MemoryFileManager::default());
+    fn bulk_remove_sorted_unversioned() {
+        bulk_remove_sorted_test::<Unversioned>();
     }
 
     #[test]
-    fn memory_compact_test_unversioned() {
-        compact_test::<Unversioned, _>(MemoryFileManager::default());
+    fn estimate_range_size_test() {
+        const KEYS: u64 = 50_000;
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let mut tree = roots.tree(Unversioned::tree("test")).unwrap();
+        let key = |id: u64| ArcBytes::from((id * 2).to_be_bytes());
+        assert_eq!(tree.estimate_range_size(&(..)).unwrap(), 0);
+
+        tree.modify(
+            (0..KEYS).map(key).collect(),
+            Operation::Set(ArcBytes::from(b"value")),
+        )
+        .unwrap();
+        tree.modify((0..100).map(key).collect(), Operation::Remove)
+            .unwrap();
+
+        let live_keys = KEYS - 100;
+        assert_eq!(tree.estimate_range_size(&(..)).unwrap(), live_keys);
+
+        // The error is bounded by twice the tree's order.
+        let max_error = 2 * cbrt_order(KEYS);
+        for (start, end) in [
+            (100, KEYS),
+            (1_000, 3_000),
+            (12_345, 12_400),
+            (40_000, KEYS),
+            (0, 10),
+        ] {
+            let expected = end.saturating_sub(start.max(100));
+            let (start, end) = (key(start), key(end));
+            let estimate = tree
+                .estimate_range_size(&(start.as_slice()..end.as_slice()))
+                .unwrap();
+            assert!(
+                estimate.max(expected) - estimate.min(expected) <= max_error,
+                "estimate {} too far from {}",
+                estimate,
+                expected
+            );
+        }
+    }
+
+    fn cbrt_order(keys: u64) -> u64 {
+        (1..).find(|order: &u64| order.pow(3) > keys).unwrap()
     }
 
     #[test]
-    fn any_compact_test_versioned() {
-        compact_test::<Versioned, _>(AnyFileManager::std());
-        compact_test::<Versioned, _>(AnyFileManager::memory());
+    fn apply_batch_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        tree.set(b"counter", 1_u64.to_be_bytes()).unwrap();
+        tree.set(b"removed", b"value").unwrap();
+        tree.set(b"swapped", b"old").unwrap();
+
+        let increment = |_key: &ArcBytes<'_>, value: Option<ArcBytes<'static>>| {
+            let current = value.map_or(0, |value| BigEndian::read_u64(&value));
+            Some(ArcBytes::from((current + 1).to_be_bytes()))
+        };
+        let results = tree
+            .apply_batch(
+                Batch::new()
+                    .set(b"set", b"value")
+                    .remove(b"removed")
+                    .compare_and_swap(
+                        b"swapped",
+                        Some(ArcBytes::from(b"old")),
+                        Some(ArcBytes::from(b"new")),
+                    )
+                    .merge(b"counter", increment),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(tree.get(b"set").unwrap().unwrap(), b"value");
+        assert!(tree.get(b"removed").unwrap().is_none());
+        assert_eq!(tree.get(b"swapped").unwrap().unwrap(), b"new");
+        assert_eq!(
+            BigEndian::read_u64(&tree.get(b"counter").unwrap().unwrap()),
+            2
+        );
+
+        // A failed condition prevents the entire batch from being applied.
+        assert!(matches!(
+            tree.apply_batch(
+                Batch::new()
+                    .set(b"set", b"other")
+                    .compare_and_swap(b"swapped", None, Some(ArcBytes::from(b"newer")))
+                    .merge(b"counter", increment),
+            ),
+            Err(CompareAndSwapError::Conflict(Some(existing))) if existing == b"new"
+        ));
+        assert_eq!(tree.get(b"set").unwrap().unwrap(), b"value");
+        assert_eq!(
+            BigEndian::read_u64(&tree.get(b"counter").unwrap().unwrap()),
+            2
+        );
+
+        let error = tree
+            .apply_batch(Batch::new().set(b"a", b"1").remove(b"a"))
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::DuplicateKey(key) if &key[..] == b"a"));
+        assert!(tree.get(b"a").unwrap().is_none());
     }
 
     #[test]
-    fn any_compact_test_unversioned() {
-        compact_test::<Unversioned, _>(AnyFileManager::std());
-        compact_test::<Unversioned, _>(AnyFileManager::memory());
+    fn retry_on_conflict_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Unversioned::tree("tree")).unwrap();
+        tree.set(b"key", b"a").unwrap();
+
+        // Conflicts are retried, reading the new value on each attempt.
+        let mut attempts = 0;
+        retry_on_conflict(3, Some(Duration::from_millis(1)), || {
+            attempts += 1;
+            let current = tree.get(b"key")?;
+            if attempts == 1 {
+                tree.set(b"key", b"b")?;
+            }
+            tree.compare_and_swap(b"key", current.as_deref(), Some(ArcBytes::from(b"c")))
+        })
+        .unwrap();
+        assert_eq!(attempts, 2);
+        assert_eq!(tree.get(b"key").unwrap().unwrap(), b"c");
+
+        // The last conflict is returned once all attempts have been used.
+        let mut attempts = 0;
+        assert!(matches!(
+            retry_on_conflict(3, None, || {
+                attempts += 1;
+                tree.compare_and_swap(b"key", Some(b"a"), None)
+            }),
+            Err(CompareAndSwapError::Conflict(Some(existing))) if existing == b"c"
+        ));
+        assert_eq!(attempts, 3);
+
+        // Other errors are returned without retrying.
+        let mut attempts = 0;
+        assert!(matches!(
+            retry_on_conflict(3, None, || -> Result<(), CompareAndSwapError> {
+                attempts += 1;
+                Err(CompareAndSwapError::Error(Error::from(ErrorKind::message(
+                    "failed",
+                ))))
+            }),
+            Err(CompareAndSwapError::Error(_))
+        ));
+        assert_eq!(attempts, 1);
     }
 
-    fn compact_test<R: Root, M: FileManager>(file_manager: M)
+    fn modify_with_previous<R: Root>()
     where
         R::Reducer: Default,
     {
-        const OPERATION_COUNT: usize = 256;
-        const WORKER_COUNT: usize = 4;
         let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(R::tree("test")).unwrap();
+        tree.set(b"a", b"1").unwrap();
+        tree.set(b"b", b"2").unwrap();
+
+        let summarize = |results: Vec<ModificationResultWithPrevious<R::Index>>| {
+            results
+                .into_iter()
+                .map(|result| {
+                    (
+                        result.key.to_vec(),
+                        result.index.is_some(),
+                        result.previous_value.map(|value| value.to_vec()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
 
-        let roots = Config::new(tempdir.path())
-            .file_manager(file_manager)
-            .open()
+        // Inserted keys report no previous value.
+        let results = tree
+            .modify_with_previous(
+                vec![ArcBytes::from(b"a"), ArcBytes::from(b"c")],
+                Operation::SetEach(vec![ArcBytes::from(b"3"), ArcBytes::from(b"4")]),
+            )
             .unwrap();
-        let tree = roots.tree(R::tree("test")).unwrap();
-        tree.set("foo", b"bar").unwrap();
+        assert_eq!(
+            summarize(results),
+            vec![
+                (b"a".to_vec(), true, Some(b"1".to_vec())),
+                (b"c".to_vec(), true, None),
+            ]
+        );
+        assert_eq!(tree.get(b"a").unwrap().unwrap(), b"3");
+        assert_eq!(tree.get(b"c").unwrap().unwrap(), b"4");
+
+        // Removals report the removed value, and missing keys are skipped.
+        let results = tree
+            .modify_with_previous(
+                vec![ArcBytes::from(b"b"), ArcBytes::from(b"d")],
+                Operation::Remove,
+            )
+            .unwrap();
+        let results = summarize(results);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, b"b");
+        assert_eq!(results[0].2.as_deref(), Some(&b"2"[..]));
+        assert!(tree.get(b"b").unwrap().is_none());
+        assert!(tree.get(b"d").unwrap().is_none());
+
+        // Compare-swap callbacks receive the existing value.
+        let mut swap =
+            |_key: &ArcBytes<'_>, _index: Option<&R::Index>, value: Option<ArcBytes<'static>>| {
+                assert_eq!(value.unwrap(), b"3");
+                KeyOperation::Set(ArcBytes::from(b"5"))
+            };
+        let results = tree
+            .modify_with_previous(
+                vec![ArcBytes::from(b"a")],
+                Operation::CompareSwap(CompareSwap::new(&mut swap)),
+            )
+            .unwrap();
+        assert_eq!(
+            summarize(results),
+            vec![(b"a".to_vec(), true, Some(b"3".to_vec()))]
+        );
+        assert_eq!(tree.get(b"a").unwrap().unwrap(), b"5");
+    }
 
-        // Spawn a pool of threads that will perform a series of operations
-        let mut threads = Vec::new();
-        for worker in 0..WORKER_COUNT {
-            let tree = tree.clone();
-            threads.push(std::thread::spawn(move || {
-                for relative_id in 0..OPERATION_COUNT {
-                    let absolute_id = (worker * OPERATION_COUNT + relative_id) as u64;
-                    tree.set(absolute_id.to_be_bytes(), absolute_id.to_be_bytes())
-                        .unwrap();
-                    let (value, _) = tree
-                        .remove(&absolute_id.to_be_bytes())
-                        .unwrap()
-                        .ok_or_else(|| panic!("value not found: {:?}", absolute_id))
-                        .unwrap();
-                    assert_eq!(BigEndian::read_u64(&value), absolute_id);
-                    tree.set(absolute_id.to_be_bytes(), absolute_id.to_be_bytes())
-                        .unwrap();
-                    let newer_value = tree
-                        .get(&absolute_id.to_be_bytes())
-                        .unwrap()
-                        .expect("couldn't find found");
-                    assert_eq!(value, newer_value);
-                }
-            }));
-        }
+    #[test]
+    fn increment_test() {
+        let tempdir = tempdir().unwrap();
+        let roots = Config::new(tempdir.path()).open().unwrap();
+        let tree = roots.tree(Versioned::tree("counters")).unwrap();
 
-        threads.push(std::thread::spawn(move || {
-            // While those workers are running, this thread is going to continually
-            // execute compaction.
-            while tree.count() < (OPERATION_COUNT * WORKER_COUNT) as u64 {
-                tree.compact().unwrap();
-            }
-        }));
+        // Missing keys start at 0.
+        assert_eq!(tree.increment(b"hits", 5).unwrap(), 5);
+        assert_eq!(tree.increment(b"hits", -7).unwrap(), -2);
+        assert_eq!(tree.get(b"hits").unwrap().unwrap(), &(-2_i64).to_be_bytes());
 
-        for thread in threads {
-            thread.join().unwrap();
+        // Multiple counters can be updated in one transaction.
+        let transaction = roots.transaction(&[Versioned::tree("counters")]).unwrap();
+        {
+            let mut tree = transaction.tree::<Versioned>(0).unwrap();
+            assert_eq!(tree.increment(b"hits", 1).unwrap(), -1);
+            assert_eq!(tree.increment(b"visitors", 1).unwrap(), 1);
         }
+        transaction.commit().unwrap();
+        assert_eq!(tree.increment(b"visitors", 0).unwrap(), 1);
+
+        // Values of the wrong width and overflows are rejected without
+        // changing the stored value.
+        tree.set(b"invalid", b"abc").unwrap();
+        assert!(matches!(
+            tree.increment(b"invalid", 1).unwrap_err().kind,
+            ErrorKind::Message(_)
+        ));
+        assert_eq!(tree.get(b"invalid").unwrap().unwrap(), b"abc");
+        tree.increment(b"big", i64::MAX).unwrap();
+        assert!(tree.increment(b"big", 1).is_err());
+        assert_eq!(tree.increment(b"big", 0).unwrap(), i64::MAX);
+    }
+
+    #[test]
+    fn modify_with_previous_versioned() {
+        modify_with_previous::<Versioned>();
+    }
+
+    #[test]
+    fn modify_with_previous_unversioned() {
+        modify_with_previous::<Unversioned>();
     }
 
     #[test]
@@ -1938,20 +8732,249 @@ mod tests {
             let value = tree.get(b"test").unwrap();
             assert_eq!(value.as_deref(), Some(&b"other"[..]));
         }
-        {
+        // Opening the roots without the vault, or with a different vault, is
+        // detected rather than making the transaction log unreadable.
+        let error = Config::<StdFileManager>::new(tempdir.path())
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
+        let error = Config::<StdFileManager>::new(tempdir.path())
+            .vault(RotatorVault::new(42))
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
+
+        // Databases without a vault can't be opened with one.
+        let unencrypted = tempdir.path().join("unencrypted");
+        Config::<StdFileManager>::new(&unencrypted).open().unwrap();
+        let error = Config::<StdFileManager>::new(&unencrypted)
+            .vault(RotatorVault::new(13))
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
+
+        // An interrupted write of the check value doesn't prevent opening.
+        let interrupted = tempdir.path().join("interrupted");
+        fs::create_dir(&interrupted).unwrap();
+        fs::write(interrupted.join("_vault.writing"), b"").unwrap();
+        Config::<StdFileManager>::new(&interrupted)
+            .vault(RotatorVault::new(13))
+            .open()
+            .unwrap();
+
+        // Databases created before the check value was introduced are
+        // verified using their transaction log, and record the check value
+        // once they are opened for writing.
+        let legacy = tempdir.path().join("legacy");
+        let roots = Config::<StdFileManager>::new(&legacy)
+            .vault(RotatorVault::new(13))
+            .open()
+            .unwrap();
+        roots
+            .tree(Versioned::tree("test"))
+            .unwrap()
+            .set(b"test", b"value")
+            .unwrap();
+        drop(roots);
+        let check_path = vault::vault_check_path(&legacy);
+        fs::remove_file(&check_path).unwrap();
+        let error = Config::<StdFileManager>::new(&legacy)
+            .vault(UnreadableVault)
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
+        assert!(!check_path.exists());
+        Config::<StdFileManager>::new(&legacy)
+            .vault(RotatorVault::new(13))
+            .open()
+            .unwrap();
+        assert!(check_path.exists());
+        let error = Config::<StdFileManager>::new(&legacy).open().unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
+    }
+
+    /// A vault that can't decrypt anything, like a vault using the wrong key.
+    #[derive(Debug)]
+    struct UnreadableVault;
+
+    impl crate::Vault for UnreadableVault {
+        type Error = std::io::Error;
+
+        fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Self::Error> {
+            Ok(payload.to_vec())
+        }
+
+        fn decrypt(&self, _payload: &[u8]) -> Result<Vec<u8>, Self::Error> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unreadable",
+            ))
+        }
+    }
+
+    #[test]
+    fn rotate_vault() {
+        let tempdir = tempdir().unwrap();
+        // Opens the database from scratch, using the RotatorVault with `key`
+        // if one is provided.
+        let open = |key: Option<u8>| {
             let roots = Config::<StdFileManager>::new(tempdir.path())
                 .open()
                 .unwrap();
-            // Try to access roots without the vault.
-            let bad_tree = roots.tree(Versioned::tree("test")).unwrap();
-            assert!(bad_tree.get(b"test").is_err());
+            let mut root = Versioned::tree("test");
+            if let Some(key) = key {
+                root = root.with_vault(RotatorVault::new(key));
+            }
+            let tree = roots.tree(root).unwrap();
+            (roots, tree)
+        };
+
+        let (roots, tree) = open(Some(13));
+        for i in 0_u32..100 {
+            tree.set(i.to_be_bytes(), format!("value {}", i).into_bytes())
+                .unwrap();
+        }
+        let clone = tree.clone();
+
+        let old: Arc<dyn AnyVault> = Arc::new(RotatorVault::new(13));
+        let new: Arc<dyn AnyVault> = Arc::new(RotatorVault::new(42));
+        tree.rotate_vault(Some(old), Some(new)).unwrap();
+
+        // Handles opened with the previous vault are refused.
+        for stale in [&tree, &clone] {
+            assert!(matches!(
+                stale.set(b"stale", b"write").unwrap_err().kind,
+                ErrorKind::VaultMismatch
+            ));
+            assert!(matches!(
+                stale.get(&0_u32.to_be_bytes()).unwrap_err().kind,
+                ErrorKind::VaultMismatch
+            ));
+        }
+        // Handles opened after the rotation can use the new vault.
+        let reopened = roots
+            .tree(Versioned::tree("test").with_vault(RotatorVault::new(42)))
+            .unwrap();
+        assert_eq!(
+            reopened.get(&0_u32.to_be_bytes()).unwrap().as_deref(),
+            Some(&b"value 0"[..])
+        );
+        drop((roots, tree, clone, reopened));
+
+        // Only the new key can read the tree's data.
+        let (roots, tree) = open(Some(42));
+        for i in 0_u32..100 {
+            let value = tree.get(&i.to_be_bytes()).unwrap();
+            assert_eq!(value.as_deref(), Some(format!("value {}", i).as_bytes()));
+        }
+        assert_eq!(tree.get(b"stale").unwrap(), None);
+        drop((roots, tree));
+
+        let (roots, unencrypted) = open(None);
+        assert!(unencrypted.get(&0_u32.to_be_bytes()).is_err());
+        drop((roots, unencrypted));
+
+        let (roots, old_tree) = open(Some(13));
+        assert!(!matches!(
+            old_tree.get(&0_u32.to_be_bytes()),
+            Ok(Some(value)) if &value[..] == b"value 0"
+        ));
+        drop((roots, old_tree));
+
+        // The tree remains writable using the new key.
+        let (_roots, tree) = open(Some(42));
+        tree.set(b"after", b"rotation").unwrap();
+        assert_eq!(
+            tree.get(b"after").unwrap().as_deref(),
+            Some(&b"rotation"[..])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_vault() {
+        use crate::vault::{AnyVault, ZstdVault};
+
+        let vault = ZstdVault::new(19);
+        for payload in [&b""[..], b"a", &[0_u8; 4096], &[0xFF; 3]] {
+            let compressed = AnyVault::encrypt(&vault, payload).unwrap();
+            assert_eq!(AnyVault::decrypt(&vault, &compressed).unwrap(), payload);
+        }
 
-            // Try to access roots with the vault specified. In this situation, the transaction log will be unreadable, causing itself to not consider any transactions valid.
-            let bad_tree = roots
-                .tree(Versioned::tree("test").with_vault(RotatorVault::new(13)))
+        let tempdir = tempdir().unwrap();
+        let value = vec![42_u8; 64 * 1024];
+        {
+            let roots = Config::<StdFileManager>::new(tempdir.path())
+                .vault(ZstdVault::default())
+                .open()
                 .unwrap();
-            assert_eq!(bad_tree.get(b"test").unwrap(), None);
+            let tree = roots.tree(Unversioned::tree("test")).unwrap();
+            tree.set(b"test", value.clone()).unwrap();
+        }
+        // The value compresses well, so the file is much smaller than it.
+        let file_length = std::fs::metadata(tempdir.path().join("test.nebari"))
+            .unwrap()
+            .len();
+        assert!(file_length < 4096);
+
+        let roots = Config::<StdFileManager>::new(tempdir.path())
+            .vault(ZstdVault::default())
+            .open()
+            .unwrap();
+        let tree = roots.tree(Unversioned::tree("test")).unwrap();
+        assert_eq!(tree.get(b"test").unwrap().as_deref(), Some(&value[..]));
+    }
+
+    #[test]
+    fn chained_vault() {
+        use crate::vault::{AnyVault, ChainedVault};
+
+        let chained = || {
+            ChainedVault::default()
+                .with(RotatorVault::new(13))
+                .with(RotatorVault::new(42))
+        };
+        let vault = chained();
+        let encrypted = vault.encrypt(b"hello").unwrap();
+        // Vaults are applied in order when encrypting.
+        let expected = RotatorVault::new(13).encrypt(b"hello").unwrap();
+        let expected = RotatorVault::new(42).encrypt(&expected).unwrap();
+        assert_eq!(encrypted, expected);
+        assert_eq!(vault.decrypt(&encrypted).unwrap(), b"hello");
+        assert_eq!(ChainedVault::default().decrypt(b"hello").unwrap(), b"hello");
+        // Errors from any vault in the chain are returned.
+        let reversed = ChainedVault::default()
+            .with(RotatorVault::new(42))
+            .with(RotatorVault::new(13));
+        assert!(reversed.decrypt(&encrypted).is_err());
+        assert!(vault.decrypt(b"hello").is_err());
+
+        let tempdir = tempdir().unwrap();
+        let config = Config::<StdFileManager>::new(tempdir.path()).vault(chained());
+        {
+            let roots = config.clone().open().unwrap();
+            let tree = roots.tree(Versioned::tree("test")).unwrap();
+            for value in 0_u8..10 {
+                tree.set(b"test", vec![value]).unwrap();
+                tree.set(vec![value], b"value").unwrap();
+            }
+            tree.compact().unwrap();
+            assert_eq!(tree.get(b"test").unwrap().as_deref(), Some(&[9_u8][..]));
+        }
+
+        let roots = config.open().unwrap();
+        let tree = roots.tree(Versioned::tree("test")).unwrap();
+        assert_eq!(tree.get(b"test").unwrap().as_deref(), Some(&[9_u8][..]));
+        for value in 0_u8..10 {
+            assert_eq!(tree.get(&[value]).unwrap().as_deref(), Some(&b"value"[..]));
         }
+
+        // Opening the database with only one of the vaults fails.
+        let error = Config::<StdFileManager>::new(tempdir.path())
+            .vault(RotatorVault::new(13))
+            .open()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::VaultMismatch));
     }
 
     #[test]
@@ -0,0 +1,45 @@
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use flume::r#async::RecvStream;
+use futures_core::Stream;
+
+use crate::{ArcBytes, Error};
+
+type StreamEntry = Result<(ArcBytes<'static>, ArcBytes<'static>), Error>;
+
+/// A stream of key-value pairs returned from
+/// [`Tree::stream_range()`](crate::Tree::stream_range).
+///
+/// The range is scanned on a background thread that can only read a bounded
+/// number of entries ahead of the consumer of the stream. Dropping the stream
+/// stops the scan.
+#[must_use]
+pub struct RangeStream {
+    entries: RecvStream<'static, StreamEntry>,
+}
+
+impl RangeStream {
+    pub(crate) fn new(entries: flume::Receiver<StreamEntry>) -> Self {
+        Self {
+            entries: entries.into_stream(),
+        }
+    }
+}
+
+impl Stream for RangeStream {
+    type Item = StreamEntry;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.entries).poll_next(cx)
+    }
+}
+
+impl Debug for RangeStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RangeStream").finish_non_exhaustive()
+    }
+}
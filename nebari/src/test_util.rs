@@ -4,8 +4,6 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::Vault;
-
 // TODO this should be shared between nebari and bonsaidb-core.
 
 pub struct TestDirectory(pub PathBuf);
@@ -43,43 +41,3 @@ impl Deref for TestDirectory {
         &self.0
     }
 }
-
-#[derive(Debug)]
-pub struct RotatorVault {
-    rotation_amount: u8,
-}
-
-impl RotatorVault {
-    pub const fn new(rotation_amount: u8) -> Self {
-        Self { rotation_amount }
-    }
-}
-
-impl Vault for RotatorVault {
-    type Error = NotEncrypted;
-    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, NotEncrypted> {
-        let mut output = Vec::with_capacity(payload.len() + 4);
-        output.extend(b"rotv");
-        output.extend(payload.iter().map(|c| c.wrapping_add(self.rotation_amount)));
-        Ok(output)
-    }
-
-    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, NotEncrypted> {
-        if payload.len() < 4 {
-            return Err(NotEncrypted);
-        }
-        let (header, payload) = payload.split_at(4);
-        if header != b"rotv" {
-            return Err(NotEncrypted);
-        }
-
-        Ok(payload
-            .iter()
-            .map(|c| c.wrapping_sub(self.rotation_amount))
-            .collect())
-    }
-}
-
-#[derive(thiserror::Error, Debug)]
-#[error("not an encrypted payload")]
-pub struct NotEncrypted;
@@ -0,0 +1,53 @@
+//! Utilities for testing code that uses Nebari. Requires the `test-util`
+//! feature.
+
+use crate::Vault;
+
+/// A [`Vault`] that "encrypts" data by adding `rotation_amount` to each byte.
+/// Useful for testing that code works when a vault is in use.
+///
+/// **This vault provides no security whatsoever.** The original data can be
+/// trivially recovered from its output. Never use it to protect real data.
+#[derive(Debug)]
+pub struct RotatorVault {
+    rotation_amount: u8,
+}
+
+impl RotatorVault {
+    /// Returns a vault that rotates each byte by `rotation_amount`.
+    #[must_use]
+    pub const fn new(rotation_amount: u8) -> Self {
+        Self { rotation_amount }
+    }
+}
+
+impl Vault for RotatorVault {
+    type Error = NotEncrypted;
+    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, NotEncrypted> {
+        let mut output = Vec::with_capacity(payload.len() + 4);
+        output.extend(b"rotv");
+        output.extend(payload.iter().map(|c| c.wrapping_add(self.rotation_amount)));
+        Ok(output)
+    }
+
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, NotEncrypted> {
+        if payload.len() < 4 {
+            return Err(NotEncrypted);
+        }
+        let (header, payload) = payload.split_at(4);
+        if header != b"rotv" {
+            return Err(NotEncrypted);
+        }
+
+        Ok(payload
+            .iter()
+            .map(|c| c.wrapping_sub(self.rotation_amount))
+            .collect())
+    }
+}
+
+/// An error returned by [`RotatorVault`] when decrypting data that it did not
+/// encrypt.
+#[derive(thiserror::Error, Debug)]
+#[error("not an encrypted payload")]
+pub struct NotEncrypted;
@@ -1,7 +1,6 @@
 use std::{
     borrow::Cow,
     cmp::Ordering,
-    fs::OpenOptions,
     io::{SeekFrom, Write},
     ops::{Bound, RangeBounds},
     path::Path,
@@ -13,13 +12,15 @@ use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use super::{State, TransactionHandle};
 use crate::{
     error::Error,
-    io::{File, FileManager, FileOp, ManagedFile, OpenableFile, OperableFile},
+    io::{File, FileManager, FileOp, ManagedFile, ManagedFileOpener, OpenableFile, OperableFile},
     transaction::TransactionId,
     vault::AnyVault,
     ArcBytes, Context, ErrorKind,
 };
 
 const PAGE_SIZE: usize = 1024;
+/// The number of entries written at a time while defragmenting.
+const DEFRAGMENT_BATCH: usize = 64;
 
 /// A transaction log that records changes for one or more trees.
 pub struct TransactionLog<File: ManagedFile> {
@@ -28,6 +29,12 @@ pub struct TransactionLog<File: ManagedFile> {
     log: <File::Manager as FileManager>::FileHandle,
 }
 
+/// A rewritten transaction log that hasn't replaced the current log yet.
+pub(crate) struct DefragmentedLog<File> {
+    file: File,
+    state: State,
+}
+
 impl<File: ManagedFile> TransactionLog<File> {
     /// Opens a transaction log for reading.
     pub fn read(
@@ -65,6 +72,24 @@ impl<File: ManagedFile> TransactionLog<File> {
     /// Initializes `state` to contain the information about the transaction log
     /// located at `log_path`.
     pub fn initialize_state(state: &State, context: &Context<File::Manager>) -> Result<(), Error> {
+        Self::initialize_state_with(state, context, false)
+    }
+
+    /// Initializes `state` like [`Self::initialize_state()`], without modifying
+    /// the log. Incomplete pages at the end of the log are ignored rather than
+    /// truncated.
+    pub(crate) fn initialize_state_read_only(
+        state: &State,
+        context: &Context<File::Manager>,
+    ) -> Result<(), Error> {
+        Self::initialize_state_with(state, context, true)
+    }
+
+    fn initialize_state_with(
+        state: &State,
+        context: &Context<File::Manager>,
+        read_only: bool,
+    ) -> Result<(), Error> {
         let mut log_length = match context.file_manager.file_length(state.path()) {
             Ok(length) => length,
             Err(Error {
@@ -80,18 +105,15 @@ impl<File: ManagedFile> TransactionLog<File> {
 
         let excess_length = log_length % PAGE_SIZE as u64;
         if excess_length > 0 {
-            // Truncate the file to the proper page size. This should only happen in a recovery situation.
-            eprintln!(
-                "Transaction log has {} extra bytes. Truncating.",
-                excess_length
-            );
-            let file = OpenOptions::new()
-                .append(true)
-                .write(true)
-                .open(state.path())?;
             log_length -= excess_length;
-            file.set_len(log_length)?;
-            file.sync_all()?;
+            if !read_only {
+                // Truncate the file to the proper page size. This should only happen in a recovery situation.
+                eprintln!(
+                    "Transaction log has {} extra bytes. Truncating.",
+                    excess_length
+                );
+                context.file_manager.truncate(state.path(), log_length)?;
+            }
         }
 
         let mut file = context.file_manager.read(state.path())?;
@@ -115,9 +137,31 @@ impl<File: ManagedFile> TransactionLog<File> {
             state: self.state.clone(),
             vault: self.vault.clone(),
             transactions: handles,
+            synchronize: true,
         })
     }
 
+    /// Logs one or more transactions like [`Self::push()`], without
+    /// synchronizing the log to disk. The entries are only guaranteed to be
+    /// on disk after a later call to [`Self::push()`] or
+    /// [`Self::synchronize()`].
+    pub(crate) fn push_unsynchronized(
+        &mut self,
+        handles: Vec<LogEntry<'static>>,
+    ) -> Result<(), Error> {
+        self.log.execute(LogWriter {
+            state: self.state.clone(),
+            vault: self.vault.clone(),
+            transactions: handles,
+            synchronize: false,
+        })
+    }
+
+    /// Ensures all entries written to the log are fully written to disk.
+    pub fn synchronize(&mut self) -> Result<(), Error> {
+        self.log.execute(LogSynchronizer)
+    }
+
     /// Returns the executed transaction with the id provided. Returns None if not found.
     pub fn get(&mut self, id: TransactionId) -> Result<Option<LogEntry<'static>>, Error> {
         match self.log.execute(EntryFetcher {
@@ -145,6 +189,98 @@ impl<File: ManagedFile> TransactionLog<File> {
         })
     }
 
+    /// Rewrites the transaction log, removing all entries whose ids are less
+    /// than `minimum_id`. The most recently written entry is always retained.
+    /// The rewritten log is written to a temporary file, which then atomically
+    /// replaces the current log file.
+    pub fn defragment(
+        self,
+        minimum_id: TransactionId,
+        file_manager: &File::Manager,
+    ) -> Result<Self, Error> {
+        let mut log = self;
+        match log.write_defragmented(minimum_id, file_manager)? {
+            Some(defragmented) => log.replace_with_defragmented(defragmented, file_manager),
+            None => Ok(log),
+        }
+    }
+
+    /// Writes the entries of this log whose ids are at least `minimum_id` to a
+    /// temporary file, without modifying this log. Returns `None` if the log
+    /// has no entries.
+    pub(crate) fn write_defragmented(
+        &mut self,
+        minimum_id: TransactionId,
+        file_manager: &File::Manager,
+    ) -> Result<Option<DefragmentedLog<File>>, Error> {
+        let last_written_id = match self.state.current_transaction_id() {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        // If the newest entry were removed, the log would restart its ids from
+        // the beginning when it was next opened.
+        let minimum_id = minimum_id.min(last_written_id);
+
+        let defragmented_path = self.state.path().with_extension("defragmenting");
+        file_manager.delete(&defragmented_path)?;
+        let mut defragmented = file_manager.open_for_append(&defragmented_path, None)?;
+        let defragmented_state = State::from_path(&defragmented_path);
+        defragmented_state.initialize(TransactionId(0), 0);
+
+        let vault = self.vault.clone();
+        let mut result = Ok(());
+        let mut entries = Vec::with_capacity(DEFRAGMENT_BATCH);
+        self.scan(minimum_id.., |entry| {
+            entries.push(entry);
+            if entries.len() == DEFRAGMENT_BATCH {
+                result = LogWriter {
+                    state: defragmented_state.clone(),
+                    vault: vault.clone(),
+                    transactions: std::mem::take(&mut entries),
+                    synchronize: true,
+                }
+                .execute(&mut defragmented);
+                result.is_ok()
+            } else {
+                true
+            }
+        })?;
+        result?;
+        if !entries.is_empty() {
+            LogWriter {
+                state: defragmented_state.clone(),
+                vault,
+                transactions: entries,
+                synchronize: true,
+            }
+            .execute(&mut defragmented)?;
+        }
+
+        Ok(Some(DefragmentedLog {
+            file: defragmented,
+            state: defragmented_state,
+        }))
+    }
+
+    /// Replaces this log's file with a log written by
+    /// [`write_defragmented()`](Self::write_defragmented). If this fails, the
+    /// log must be reopened.
+    pub(crate) fn replace_with_defragmented(
+        mut self,
+        defragmented: DefragmentedLog<File>,
+        file_manager: &File::Manager,
+    ) -> Result<Self, Error> {
+        let state = self.state.clone();
+        let guard = state.lock_file_for_replacement();
+        self.log = self
+            .log
+            .replace_with(defragmented.file, file_manager, |_file_id| {
+                state.note_file_replaced(defragmented.state.len());
+            })?;
+        drop(guard);
+        Ok(self)
+    }
+
     /// Closes the transaction log.
     pub fn close(self) -> Result<(), Error> {
         self.log.close()
@@ -455,20 +591,28 @@ struct LogWriter {
     state: State,
     transactions: Vec<LogEntry<'static>>,
     vault: Option<Arc<dyn AnyVault>>,
+    synchronize: bool,
 }
 
 impl FileOp<Result<(), Error>> for LogWriter {
     fn execute(mut self, log: &mut dyn File) -> Result<(), Error> {
         let mut log_position = self.state.lock_for_write();
+        // The position is only updated once every transaction has been
+        // written, so a failed write leaves it pointing at the end of the
+        // last complete entry.
+        let previous_last_written = log_position.last_written_transaction;
+        let previous_offset = log_position.file_offset;
+        let mut last_written_transaction = previous_last_written;
+        let mut file_offset = previous_offset;
         let mut scratch = [0_u8; PAGE_SIZE];
         let mut completed_transactions = Vec::with_capacity(self.transactions.len());
         for transaction in self.transactions.drain(..) {
-            if transaction.id > log_position.last_written_transaction {
-                log_position.last_written_transaction = transaction.id;
+            if transaction.id > last_written_transaction {
+                last_written_transaction = transaction.id;
             } else {
                 return Err(Error::from(ErrorKind::TransactionPushedOutOfOrder));
             }
-            completed_transactions.push((transaction.id, Some(log_position.file_offset)));
+            completed_transactions.push((transaction.id, Some(file_offset)));
             let mut bytes = transaction.serialize()?;
             if let Some(vault) = &self.vault {
                 bytes = vault.encrypt(&bytes)?;
@@ -506,13 +650,24 @@ impl FileOp<Result<(), Error>> for LogWriter {
                     .copy_from_slice(&bytes[offset..offset + bytes_to_write]);
                 log.write_all(&scratch)?;
                 offset += bytes_to_write;
-                log_position.file_offset += PAGE_SIZE as u64;
+                file_offset += PAGE_SIZE as u64;
             }
         }
 
+        log_position.last_written_transaction = last_written_transaction;
+        log_position.file_offset = file_offset;
         drop(log_position);
 
-        log.synchronize()?;
+        if self.synchronize {
+            if let Err(err) = log.synchronize() {
+                // Only this writer modifies the position, so the entries can
+                // be forgotten by restoring it.
+                let mut log_position = self.state.lock_for_write();
+                log_position.last_written_transaction = previous_last_written;
+                log_position.file_offset = previous_offset;
+                return Err(err);
+            }
+        }
 
         self.state
             .note_transaction_ids_completed(&completed_transactions);
@@ -521,6 +676,14 @@ impl FileOp<Result<(), Error>> for LogWriter {
     }
 }
 
+struct LogSynchronizer;
+
+impl FileOp<Result<(), Error>> for LogSynchronizer {
+    fn execute(self, log: &mut dyn File) -> Result<(), Error> {
+        log.synchronize()
+    }
+}
+
 /// An entry in a transaction log.
 #[derive(Eq, PartialEq, Debug)]
 pub struct LogEntry<'a> {
@@ -688,9 +851,9 @@ mod tests {
         io::{
             any::AnyFileManager,
             fs::{StdFile, StdFileManager},
-            memory::MemoryFileManager,
+            memory::{CapacityPolicy, MemoryFileManager},
         },
-        test_util::RotatorVault,
+        testing::RotatorVault,
         transaction::TransactionManager,
         ChunkCache,
     };
@@ -735,6 +898,10 @@ mod tests {
             file_manager,
             vault,
             cache,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
         };
         std::fs::create_dir(&temp_dir).unwrap();
         let log_path = {
@@ -790,7 +957,11 @@ mod tests {
                 &Context {
                     file_manager: context.file_manager.clone(),
                     vault: Some(Arc::new(RotatorVault::new(13))),
-                    cache: None
+                    cache: None,
+                    recovery_handler: None,
+                    page_size: None,
+                    write_buffer_size: None,
+                    key_compression: false,
                 }
             )
             .is_err());
@@ -859,6 +1030,10 @@ mod tests {
             file_manager,
             vault: None,
             cache: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
         };
         let log_path = temp_dir.path().join("_transactions");
         let mut rng = Pcg64::new_seed(1);
@@ -937,6 +1112,10 @@ mod tests {
             file_manager,
             vault,
             cache,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
         };
         let manager = TransactionManager::spawn(&temp_dir, context).unwrap();
         assert_eq!(manager.current_transaction_id(), None);
@@ -1030,6 +1209,10 @@ mod tests {
             file_manager,
             vault,
             cache,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
         };
         let manager = TransactionManager::spawn(&temp_dir, context).unwrap();
         let mut rng = Pcg64::new_seed(1);
@@ -1081,4 +1264,96 @@ mod tests {
             assert!(expected_ids.is_empty(), "{:?}", expected_ids);
         }
     }
+
+    #[test]
+    fn unsynchronized_manager_drop_waits_for_thread() {
+        let temp_dir = crate::test_util::TestDirectory::new("unsynchronized-manager-drop");
+        std::fs::create_dir(&temp_dir).unwrap();
+        // The manager's thread holds clones of the vault until it exits.
+        let vault: Arc<dyn AnyVault> = Arc::new(RotatorVault::new(13));
+        let context = Context {
+            file_manager: StdFileManager::default(),
+            vault: Some(vault.clone()),
+            cache: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
+        };
+        let manager = TransactionManager::spawn_unsynchronized(&temp_dir, context).unwrap();
+        manager.new_transaction([&b"tree"[..]]).commit().unwrap();
+        let clone = manager.clone();
+        drop(manager);
+        clone.new_transaction([&b"tree"[..]]).commit().unwrap();
+        assert!(Arc::strong_count(&vault) > 1);
+
+        // Dropping the last clone waits for the thread to synchronize the log
+        // and exit.
+        drop(clone);
+        assert_eq!(Arc::strong_count(&vault), 1);
+    }
+
+    struct Fill(usize);
+
+    impl FileOp<Result<(), Error>> for Fill {
+        fn execute(self, file: &mut dyn File) -> Result<(), Error> {
+            file.write_all(&vec![0; self.0])?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn manager_out_of_space() {
+        const CAPACITY: u64 = 1_000_000;
+        let temp_dir = crate::test_util::TestDirectory::new("manager-out-of-space");
+        std::fs::create_dir(&temp_dir).unwrap();
+        let file_manager = MemoryFileManager::with_capacity(CAPACITY, CapacityPolicy::Error);
+        let context = Context {
+            file_manager: file_manager.clone(),
+            vault: None,
+            cache: None,
+            recovery_handler: None,
+            page_size: None,
+            write_buffer_size: None,
+            key_compression: false,
+        };
+        let manager = TransactionManager::spawn(&temp_dir, context.clone()).unwrap();
+        let first = manager.new_transaction([&b"tree"[..]]);
+        let first_id = first.id;
+        first.commit().unwrap();
+
+        // Leave room for exactly one more entry.
+        let filler_path = temp_dir.join("filler");
+        let remaining = CAPACITY - file_manager.used_bytes() - PAGE_SIZE as u64;
+        file_manager
+            .append(&filler_path)
+            .unwrap()
+            .execute(Fill(usize::try_from(remaining).unwrap()))
+            .unwrap();
+        let second = manager.new_transaction([&b"tree"[..]]);
+        let second_id = second.id;
+        second.commit().unwrap();
+        let error = manager
+            .new_transaction([&b"tree"[..]])
+            .commit()
+            .unwrap_err();
+        assert!(matches!(error.kind, ErrorKind::OutOfSpace));
+
+        // Once space is available, transactions can be committed again.
+        file_manager.delete(&filler_path).unwrap();
+        let fourth = manager.new_transaction([&b"tree"[..]]);
+        let fourth_id = fourth.id;
+        fourth.commit().unwrap();
+        drop(manager);
+
+        let manager = TransactionManager::spawn(&temp_dir, context).unwrap();
+        let mut ids = Vec::new();
+        manager
+            .scan(.., |entry| {
+                ids.push(entry.id);
+                true
+            })
+            .unwrap();
+        assert_eq!(ids, vec![first_id, second_id, fourth_id]);
+    }
 }
@@ -1,10 +1,12 @@
 use std::{
-    ops::{Deref, DerefMut, RangeBounds},
+    collections::VecDeque,
+    ops::{Bound, Deref, DerefMut, RangeBounds},
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    thread::JoinHandle,
 };
 
 use parking_lot::Mutex;
@@ -21,8 +23,42 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct TransactionManager<Manager: FileManager> {
     state: State,
-    transaction_sender: flume::Sender<ThreadCommand>,
+    thread: Arc<ThreadHandle>,
     context: Context<Manager>,
+    read_only: bool,
+}
+
+/// The connection to a transaction manager's thread, shared by all clones of
+/// the manager. When the last clone is dropped, the thread is stopped and
+/// waited for, so any writes it still has to perform, such as synchronizing
+/// an unsynchronized log, have finished once the drop returns.
+#[derive(Debug)]
+struct ThreadHandle {
+    commands: Option<flume::Sender<ThreadCommand>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThreadHandle {
+    fn send(&self, command: ThreadCommand) -> Result<(), Error> {
+        self.commands
+            .as_ref()
+            .and_then(|commands| commands.send(command).ok())
+            .ok_or_else(|| {
+                Error::from(ErrorKind::Internal(
+                    InternalError::TransactionManagerStopped,
+                ))
+            })
+    }
+}
+
+impl Drop for ThreadHandle {
+    fn drop(&mut self) {
+        // The thread exits once its command channel is disconnected.
+        drop(self.commands.take());
+        if let Some(thread) = self.thread.take() {
+            drop(thread.join());
+        }
+    }
 }
 
 impl<Manager> TransactionManager<Manager>
@@ -32,26 +68,90 @@ where
     /// Spawns a new transaction manager. The transaction manager runs its own
     /// thread that writes to the transaction log.
     pub fn spawn(directory: &Path, context: Context<Manager>) -> Result<Self, Error> {
+        Self::spawn_with(directory, context, true)
+    }
+
+    /// Spawns a new transaction manager that doesn't synchronize the
+    /// transaction log to disk after each commit. The log is synchronized
+    /// when the last clone of the manager is dropped, which waits for the
+    /// manager's thread to finish. Errors synchronizing the log at that point
+    /// are printed to stderr, as they can't be returned.
+    ///
+    /// Committed transactions are still written to the log, but if the
+    /// process or machine crashes, the most recently committed transactions
+    /// may not have reached the disk. When the log is reopened, trees ignore
+    /// any roots written by those transactions, restoring the database to the
+    /// last transaction that was fully written.
+    pub fn spawn_unsynchronized(
+        directory: &Path,
+        context: Context<Manager>,
+    ) -> Result<Self, Error> {
+        Self::spawn_with(directory, context, false)
+    }
+
+    fn spawn_with(
+        directory: &Path,
+        context: Context<Manager>,
+        synchronize_log: bool,
+    ) -> Result<Self, Error> {
         let (transaction_sender, receiver) = flume::bounded(32);
         let log_path = Self::log_path(directory);
 
         let (state_sender, state_receiver) = flume::bounded(1);
         let thread_context = context.clone();
-        std::thread::Builder::new()
+        let thread = std::thread::Builder::new()
             .name(String::from("nebari-txlog"))
             .spawn(move || {
-                ManagerThread::<Manager>::run(&state_sender, &log_path, receiver, thread_context);
+                ManagerThread::<Manager>::run(
+                    &state_sender,
+                    &log_path,
+                    receiver,
+                    thread_context,
+                    synchronize_log,
+                );
             })
             .map_err(ErrorKind::message)?;
 
         let state = state_receiver.recv().expect("failed to initialize")?;
         Ok(Self {
             state,
-            transaction_sender,
+            thread: Arc::new(ThreadHandle {
+                commands: Some(transaction_sender),
+                thread: Some(thread),
+            }),
+            context,
+            read_only: false,
+        })
+    }
+
+    /// Opens the transaction log in `directory` without the ability to write
+    /// to it. No thread is spawned, and the log isn't created if it doesn't
+    /// exist. Committing a transaction or defragmenting the log returns
+    /// [`ErrorKind::ReadOnly`].
+    pub fn open_read_only(directory: &Path, context: Context<Manager>) -> Result<Self, Error> {
+        let state = State::from_path(Self::log_path(directory));
+        TransactionLog::<Manager::File>::initialize_state_read_only(&state, &context)?;
+        // Without a thread receiving commands, the sender is only used to
+        // satisfy the structure.
+        let (transaction_sender, _) = flume::bounded(1);
+        Ok(Self {
+            state,
+            thread: Arc::new(ThreadHandle {
+                commands: Some(transaction_sender),
+                thread: None,
+            }),
             context,
+            read_only: true,
         })
     }
 
+    /// Returns true if this manager was opened using
+    /// [`Self::open_read_only()`].
+    #[must_use]
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     /// Creates a new transaction, exclusively locking `trees`. Will block the thread until the trees can be locked.
     #[must_use]
     pub fn new_transaction<
@@ -71,18 +171,19 @@ where
     /// Push `transaction` to the log. Once this function returns, the
     /// transaction log entry has been fully flushed to disk.
     fn push(&self, transaction: TransactionHandle) -> Result<TreeLocks, Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
         let (completion_sender, completion_receiver) = flume::bounded(1);
-        self.transaction_sender
-            .send(ThreadCommand::Commit {
-                transaction,
-                completion_sender,
-            })
-            .map_err(|_| ErrorKind::Internal(InternalError::TransactionManagerStopped))?;
+        self.thread.send(ThreadCommand::Commit {
+            transaction,
+            completion_sender,
+        })?;
         completion_receiver.recv().map_err(|_| {
             Error::from(ErrorKind::Internal(
                 InternalError::TransactionManagerStopped,
             ))
-        })
+        })?
     }
 
     /// Scans the transaction log for entries with ids within `range`. Invokes
@@ -93,6 +194,7 @@ where
         range: impl RangeBounds<TransactionId>,
         callback: Callback,
     ) -> Result<(), Error> {
+        let _guard = self.state.lock_file_for_read();
         let mut log = TransactionLog::<Manager::File>::read(
             self.state.path(),
             self.state.clone(),
@@ -101,6 +203,28 @@ where
         log.scan(range, callback)
     }
 
+    /// Returns an iterator over the entries of the transaction log with ids
+    /// within `range`, in the order they were committed. Only transactions
+    /// that were fully written to the log are returned.
+    ///
+    /// Entries are read in batches as the iterator advances, so the log is
+    /// not locked while iterating. Transactions committed while iterating
+    /// are returned if they are within `range`.
+    ///
+    /// The log records each transaction's id and the data set using
+    /// [`LogEntry::set_data()`]. It doesn't record which trees a transaction
+    /// modified, so applications that need that information should store it
+    /// in the entry's data.
+    #[must_use]
+    pub fn entries(&self, range: impl RangeBounds<TransactionId>) -> LogEntryIter<Manager> {
+        LogEntryIter {
+            manager: Some(self.clone()),
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            buffered: VecDeque::new(),
+        }
+    }
+
     /// Returns true if the transaction id was recorded in the transaction log. This method caches
     pub fn transaction_was_successful(&self, transaction_id: TransactionId) -> Result<bool, Error> {
         self.transaction_position(transaction_id)
@@ -117,6 +241,7 @@ where
         } else if let Some(position) = self.state.transaction_id_position(transaction_id) {
             Ok(position)
         } else {
+            let _guard = self.state.lock_file_for_read();
             let mut log = self.context.file_manager.read(self.state.path())?;
             let transaction = log.execute(EntryFetcher {
                 state: self.state(),
@@ -137,14 +262,33 @@ where
         }
     }
 
+    /// Rewrites the transaction log, removing all entries whose ids are less
+    /// than `minimum_id`. The most recently written entry is always retained.
+    ///
+    /// Transactions that are committed while the log is being rewritten will
+    /// be written once the new log file is in place. Other readers of the log
+    /// are blocked while the file is being replaced.
+    pub fn defragment(&self, minimum_id: TransactionId) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::from(ErrorKind::ReadOnly));
+        }
+        let (completion_sender, completion_receiver) = flume::bounded(1);
+        self.thread.send(ThreadCommand::Defragment {
+            minimum_id,
+            completion_sender,
+        })?;
+        completion_receiver.recv().map_err(|_| {
+            Error::from(ErrorKind::Internal(
+                InternalError::TransactionManagerStopped,
+            ))
+        })?
+    }
+
     pub(crate) fn drop_transaction_id(&self, transaction_id: TransactionId) {
-        drop(
-            self.transaction_sender
-                .send(ThreadCommand::Drop(transaction_id)),
-        );
+        drop(self.thread.send(ThreadCommand::Drop(transaction_id)));
     }
 
-    fn log_path(directory: &Path) -> PathBuf {
+    pub(crate) fn log_path(directory: &Path) -> PathBuf {
         directory.join("_transactions")
     }
 
@@ -163,22 +307,83 @@ impl<Manager: FileManager> Deref for TransactionManager<Manager> {
     }
 }
 
+/// An iterator over the entries of a transaction log. Returned by
+/// [`TransactionManager::entries()`].
+#[derive(Debug)]
+#[must_use]
+pub struct LogEntryIter<Manager: FileManager> {
+    manager: Option<TransactionManager<Manager>>,
+    start: Bound<TransactionId>,
+    end: Bound<TransactionId>,
+    buffered: VecDeque<LogEntry<'static>>,
+}
+
+impl<Manager: FileManager> LogEntryIter<Manager> {
+    fn read_batch(&mut self) -> Result<(), Error> {
+        const BATCH_SIZE: usize = 64;
+
+        let manager = match &self.manager {
+            Some(manager) => manager,
+            None => return Ok(()),
+        };
+        let mut entries_read = 0;
+        manager.scan((self.start, self.end), |entry| {
+            self.buffered.push_back(entry);
+            entries_read += 1;
+            entries_read < BATCH_SIZE
+        })?;
+
+        if entries_read < BATCH_SIZE {
+            // The remainder of the range has been read.
+            self.manager = None;
+        }
+        if let Some(last_entry) = self.buffered.back() {
+            self.start = Bound::Excluded(last_entry.id);
+        }
+        Ok(())
+    }
+}
+
+impl<Manager: FileManager> Iterator for LogEntryIter<Manager> {
+    type Item = Result<LogEntry<'static>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffered.is_empty() {
+            if let Err(err) = self.read_batch() {
+                self.manager = None;
+                return Some(Err(err));
+            }
+        }
+        self.buffered.pop_front().map(Ok)
+    }
+}
+
 enum ThreadCommand {
     Commit {
         transaction: TransactionHandle,
-        completion_sender: flume::Sender<TreeLocks>,
+        completion_sender: flume::Sender<Result<TreeLocks, Error>>,
     },
     Drop(TransactionId),
+    Defragment {
+        minimum_id: TransactionId,
+        completion_sender: flume::Sender<Result<(), Error>>,
+    },
 }
 
 struct ManagerThread<Manager: FileManager> {
     state: ThreadState,
     commands: flume::Receiver<ThreadCommand>,
-    log: TransactionLog<Manager::File>,
+    context: Context<Manager>,
+    /// The open transaction log. This is `None` after a write to the log
+    /// failed, or if the log couldn't be reopened after its file was
+    /// replaced, in which case it is reopened before the next write.
+    log: Option<TransactionLog<Manager::File>>,
+    log_state: State,
     pending_transaction_ids: IdSequence,
     last_processed_id: TransactionId,
     transaction_batch: Vec<LogEntry<'static>>,
-    completion_senders: Vec<(flume::Sender<Vec<TreeLockHandle>>, Vec<TreeLockHandle>)>,
+    completion_senders: Vec<(flume::Sender<Result<TreeLocks, Error>>, TreeLocks)>,
+    synchronize_log: bool,
 }
 
 enum ThreadState {
@@ -195,12 +400,14 @@ impl<Manager: FileManager> ManagerThread<Manager> {
         log_path: &Path,
         transactions: flume::Receiver<ThreadCommand>,
         context: Context<Manager>,
+        synchronize_log: bool,
     ) {
         let state = State::from_path(&log_path);
 
         let log = match TransactionLog::<Manager::File>::initialize_state(&state, &context)
-            .and_then(|_| TransactionLog::<Manager::File>::open(log_path, state.clone(), context))
-        {
+            .and_then(|_| {
+                TransactionLog::<Manager::File>::open(log_path, state.clone(), context.clone())
+            }) {
             Ok(log) => log,
             Err(err) => {
                 drop(state_sender.send(Err(err)));
@@ -208,22 +415,33 @@ impl<Manager: FileManager> ManagerThread<Manager> {
             }
         };
         let transaction_id = log.state().next_transaction_id();
-        drop(state_sender.send(Ok(state)));
+        drop(state_sender.send(Ok(state.clone())));
 
         Self {
             state: ThreadState::Fresh,
             commands: transactions,
             last_processed_id: transaction_id,
             pending_transaction_ids: IdSequence::new(transaction_id),
-            log,
+            context,
+            log: Some(log),
+            log_state: state,
             transaction_batch: Vec::with_capacity(Self::BATCH),
             completion_senders: Vec::with_capacity(Self::BATCH),
+            synchronize_log,
         }
         .save_transactions();
     }
 
     fn save_transactions(mut self) {
         while self.process_next_command() {}
+
+        if !self.synchronize_log {
+            if let Some(log) = &mut self.log {
+                if let Err(err) = log.synchronize() {
+                    eprintln!("Error synchronizing transaction log: {:?}", err);
+                }
+            }
+        }
     }
 
     fn process_next_command(&mut self) -> bool {
@@ -263,6 +481,12 @@ impl<Manager: FileManager> ManagerThread<Manager> {
                     ThreadCommand::Drop(id) => {
                         self.mark_transaction_handled(id);
                     }
+                    ThreadCommand::Defragment {
+                        minimum_id,
+                        completion_sender,
+                    } => {
+                        self.defragment(minimum_id, &completion_sender);
+                    }
                 }
                 true
             }
@@ -299,6 +523,12 @@ impl<Manager: FileManager> ManagerThread<Manager> {
                     ThreadCommand::Drop(id) => {
                         self.note_potentially_sequntial_id(id);
                     }
+                    ThreadCommand::Defragment {
+                        minimum_id,
+                        completion_sender,
+                    } => {
+                        self.defragment(minimum_id, &completion_sender);
+                    }
                 }
                 true
             }
@@ -346,6 +576,12 @@ impl<Manager: FileManager> ManagerThread<Manager> {
                     ThreadCommand::Drop(id) => {
                         self.mark_transaction_handled(id);
                     }
+                    ThreadCommand::Defragment {
+                        minimum_id,
+                        completion_sender,
+                    } => {
+                        self.defragment(minimum_id, &completion_sender);
+                    }
                 }
                 true
             }
@@ -359,11 +595,79 @@ impl<Manager: FileManager> ManagerThread<Manager> {
         transaction_batch.sort_unstable_by(|a, b| a.id.cmp(&b.id));
         self.last_processed_id = transaction_batch.last().unwrap().id;
         self.state = ThreadState::Fresh;
-        self.log.push(transaction_batch).unwrap();
-        for (completion_sender, tree_locks) in self.completion_senders.drain(..) {
-            drop(completion_sender.send(tree_locks));
+        let synchronize_log = self.synchronize_log;
+        let result = self.log().and_then(|log| {
+            if synchronize_log {
+                log.push(transaction_batch)
+            } else {
+                log.push_unsynchronized(transaction_batch)
+            }
+        });
+        if result.is_err() {
+            // The log may contain part of the batch. The handle is closed, and
+            // the incomplete entries are removed before the log is reopened.
+            self.log = None;
+        }
+        match result {
+            Ok(()) => {
+                for (completion_sender, tree_locks) in self.completion_senders.drain(..) {
+                    drop(completion_sender.send(Ok(tree_locks)));
+                }
+            }
+            Err(err) => {
+                // Each transaction in the batch failed for the same reason.
+                for (completion_sender, _) in self.completion_senders.drain(..) {
+                    drop(completion_sender.send(Err(Error::from(err.kind.duplicate()))));
+                }
+            }
         }
     }
+
+    /// Returns the open transaction log, reopening it if it was closed after a
+    /// failed write or a failed attempt to reopen it. Any data written after
+    /// the last complete entry is removed before the log is reopened.
+    fn log(&mut self) -> Result<&mut TransactionLog<Manager::File>, Error> {
+        if self.log.is_none() {
+            let valid_length = self.log_state.len();
+            let file_manager = &self.context.file_manager;
+            if file_manager.file_length(self.log_state.path())? > valid_length {
+                file_manager.truncate(self.log_state.path(), valid_length)?;
+            }
+            self.log = Some(TransactionLog::<Manager::File>::open(
+                self.log_state.path(),
+                self.log_state.clone(),
+                self.context.clone(),
+            )?);
+        }
+        Ok(self.log.as_mut().unwrap())
+    }
+
+    fn defragment(
+        &mut self,
+        minimum_id: TransactionId,
+        completion_sender: &flume::Sender<Result<(), Error>>,
+    ) {
+        drop(completion_sender.send(self.defragment_log(minimum_id)));
+    }
+
+    fn defragment_log(&mut self, minimum_id: TransactionId) -> Result<(), Error> {
+        let file_manager = self.context.file_manager.clone();
+        // Any batched transactions haven't been written yet, so they will be
+        // written to the defragmented log once it replaces the current one.
+        // The current log is untouched until the defragmented log has been
+        // written, so it remains usable if writing fails.
+        let defragmented = match self.log()?.write_defragmented(minimum_id, &file_manager)? {
+            Some(defragmented) => defragmented,
+            None => return Ok(()),
+        };
+        let log = self.log.take().expect("transaction log missing");
+        let log = log.replace_with_defragmented(defragmented, &file_manager)?;
+        // If replacing the file failed, the previous handle has been consumed.
+        // The log is reopened before the next write, which returns the error
+        // if the log still can't be opened.
+        self.log = Some(log);
+        Ok(())
+    }
 }
 
 /// A transaction that is managed by a [`TransactionManager`].
@@ -481,6 +785,18 @@ impl TreeLock {
             data: self.data.clone(),
         })
     }
+
+    /// Acquires the lock without blocking. Returns None if the lock is
+    /// already held.
+    pub(crate) fn try_lock(&self) -> Option<TreeLockHandle> {
+        self.data
+            .locked
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        Some(TreeLockHandle(Self {
+            data: self.data.clone(),
+        }))
+    }
 }
 
 #[derive(Debug)]
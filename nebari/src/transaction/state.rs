@@ -9,7 +9,7 @@ use std::{
 };
 
 use lru::LruCache;
-use parking_lot::{Mutex, MutexGuard};
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use super::{LogEntry, TransactionHandle, TreeLock, TreeLocks};
 use crate::transaction::TransactionId;
@@ -29,6 +29,7 @@ struct ActiveState {
     tree_locks: Mutex<HashMap<Cow<'static, [u8]>, TreeLock>>,
     log_position: Mutex<LogPosition>,
     known_completed_transactions: Mutex<LruCache<TransactionId, Option<u64>>>,
+    file_replacement: RwLock<()>,
 }
 
 /// The active log position information.
@@ -59,6 +60,7 @@ impl State {
                 current_transaction_id: AtomicU64::new(UNINITIALIZED_ID),
                 log_position: Mutex::new(LogPosition::default()),
                 known_completed_transactions: Mutex::new(LruCache::new(1024)),
+                file_replacement: RwLock::default(),
             }),
         }
     }
@@ -135,6 +137,23 @@ impl State {
         }
     }
 
+    /// Exclusively locks `trees` without blocking. Returns None if any of the
+    /// trees are already locked, such as by an executing transaction.
+    pub(crate) fn try_lock_trees<'a>(
+        &self,
+        trees: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Option<TreeLocks> {
+        let mut tree_locks = self.state.tree_locks.lock();
+        let mut locks = Vec::new();
+        for tree in trees {
+            let lock = tree_locks
+                .entry(Cow::Owned(tree.to_vec()))
+                .or_insert_with(TreeLock::new);
+            locks.push(lock.try_lock()?);
+        }
+        Some(locks)
+    }
+
     /// Creates a new transaction, exclusively locking `trees`. Will block the thread until the trees can be locked.
     #[must_use]
     pub fn new_transaction<
@@ -199,4 +218,24 @@ impl State {
     pub(crate) fn lock_for_write(&self) -> MutexGuard<'_, LogPosition> {
         self.state.log_position.lock()
     }
+
+    /// Prevents the log file from being replaced while the returned guard is
+    /// held. Readers that open their own handle to the log file must hold
+    /// this guard to ensure the file and this state remain consistent.
+    pub(crate) fn lock_file_for_read(&self) -> RwLockReadGuard<'_, ()> {
+        self.state.file_replacement.read()
+    }
+
+    /// Blocks all readers from accessing the log file while it is replaced.
+    pub(crate) fn lock_file_for_replacement(&self) -> RwLockWriteGuard<'_, ()> {
+        self.state.file_replacement.write()
+    }
+
+    /// Updates the state after the log file has been replaced with a file
+    /// that is `log_length` bytes long. All cached transaction positions are
+    /// forgotten, as they no longer refer to the current file.
+    pub(crate) fn note_file_replaced(&self, log_length: u64) {
+        self.state.log_position.lock().file_offset = log_length;
+        self.state.known_completed_transactions.lock().clear();
+    }
 }
@@ -0,0 +1,148 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt::Debug,
+};
+
+use crate::{error::Error, ArcBytes, ErrorKind};
+
+/// A set of operations to apply to a tree at once.
+///
+/// Unlike a [`Modification`](crate::tree::Modification), which applies one
+/// [`Operation`](crate::tree::Operation) to all of its keys, each key in a
+/// batch has its own operation.
+///
+/// Keys are kept sorted as they are added. Each key may only appear once in a
+/// batch.
+#[derive(Default, Debug)]
+#[must_use]
+pub struct Batch<'a> {
+    operations: BTreeMap<ArcBytes<'static>, BatchOperation<'a>>,
+    duplicate_key: Option<ArcBytes<'static>>,
+}
+
+impl<'a> Batch<'a> {
+    /// Returns a new, empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `operation` for `key` to this batch.
+    pub fn push(
+        mut self,
+        key: impl Into<ArcBytes<'static>>,
+        operation: BatchOperation<'a>,
+    ) -> Self {
+        match self.operations.entry(key.into()) {
+            Entry::Vacant(entry) => {
+                entry.insert(operation);
+            }
+            Entry::Occupied(entry) => {
+                self.duplicate_key
+                    .get_or_insert_with(|| entry.key().clone());
+            }
+        }
+        self
+    }
+
+    /// Sets `key` to `value`.
+    pub fn set(
+        self,
+        key: impl Into<ArcBytes<'static>>,
+        value: impl Into<ArcBytes<'static>>,
+    ) -> Self {
+        self.push(key, BatchOperation::Set(value.into()))
+    }
+
+    /// Removes `key`.
+    pub fn remove(self, key: impl Into<ArcBytes<'static>>) -> Self {
+        self.push(key, BatchOperation::Remove)
+    }
+
+    /// Compares the value of `key` against `current`. If the values match, key
+    /// will be set to the new value if `new` is `Some` or removed if `new` is
+    /// `None`. If the values do not match, none of the batch's operations are
+    /// applied.
+    pub fn compare_and_swap(
+        self,
+        key: impl Into<ArcBytes<'static>>,
+        current: Option<ArcBytes<'static>>,
+        new: Option<ArcBytes<'static>>,
+    ) -> Self {
+        self.push(key, BatchOperation::CompareAndSwap { current, new })
+    }
+
+    /// Invokes `merge` with the key and its current value, if present. If
+    /// `merge` returns `Some`, the key is set to the returned value. If `None`
+    /// is returned, the key is removed.
+    pub fn merge<
+        F: FnOnce(&ArcBytes<'_>, Option<ArcBytes<'static>>) -> Option<ArcBytes<'static>> + 'a,
+    >(
+        self,
+        key: impl Into<ArcBytes<'static>>,
+        merge: F,
+    ) -> Self {
+        self.push(key, BatchOperation::Merge(Box::new(merge)))
+    }
+
+    /// Returns the number of keys in this batch.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns true if this batch contains no operations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns the operations of this batch, ordered by key. Returns an error
+    /// if a key was added more than once.
+    pub(crate) fn into_operations(
+        self,
+    ) -> Result<BTreeMap<ArcBytes<'static>, BatchOperation<'a>>, Error> {
+        match self.duplicate_key {
+            Some(key) => Err(Error::from(ErrorKind::DuplicateKey(key))),
+            None => Ok(self.operations),
+        }
+    }
+}
+
+/// A function that computes the new value of a key from its current value.
+/// Returning `None` removes the key.
+pub type MergeFn<'a> =
+    dyn FnOnce(&ArcBytes<'_>, Option<ArcBytes<'static>>) -> Option<ArcBytes<'static>> + 'a;
+
+/// An operation on a single key within a [`Batch`].
+pub enum BatchOperation<'a> {
+    /// Sets the key to the value.
+    Set(ArcBytes<'static>),
+    /// Removes the key.
+    Remove,
+    /// Sets the key to `new` if `Some`, or removes the key if `None`, as long
+    /// as the key's value matches `current`.
+    CompareAndSwap {
+        /// The value the key must currently have. `None` requires that the key
+        /// not have a value.
+        current: Option<ArcBytes<'static>>,
+        /// The new value for the key.
+        new: Option<ArcBytes<'static>>,
+    },
+    /// Computes the new value of the key from its current value.
+    Merge(Box<MergeFn<'a>>),
+}
+
+impl Debug for BatchOperation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Set(value) => f.debug_tuple("Set").field(value).finish(),
+            Self::Remove => write!(f, "Remove"),
+            Self::CompareAndSwap { current, new } => f
+                .debug_struct("CompareAndSwap")
+                .field("current", current)
+                .field("new", new)
+                .finish(),
+            Self::Merge(_) => f.debug_tuple("Merge").finish(),
+        }
+    }
+}
@@ -9,7 +9,7 @@ use std::{
 use byteorder::{ReadBytesExt, WriteBytesExt};
 
 use super::{
-    interior::Interior,
+    interior::{Interior, Pointer},
     key_entry::KeyEntry,
     modify::{Modification, Operation},
     serialization::BinarySerialization,
@@ -20,7 +20,14 @@ use crate::{
     chunk_cache::CacheEntry,
     error::Error,
     io::File,
-    tree::{key_entry::ValueIndex, read_chunk, versioned::Children, ScanEvaluation},
+    tree::{
+        key_entry::ValueIndex,
+        read_chunk,
+        verification::ChunkVerifier,
+        versioned::Children,
+        walk::{NodeContents, NodeInfo},
+        ScanEvaluation,
+    },
     vault::AnyVault,
     AbortError, ArcBytes, ChunkCache, ErrorKind,
 };
@@ -303,6 +310,18 @@ where
 
             let search_result = children[last_index..].binary_search_by(|child| child.key.cmp(key));
             match search_result {
+                Ok(matching_index) if matches!(modification.operation, Operation::Remove) => {
+                    last_index += matching_index;
+                    any_changes |= Self::remove_leaf_run(
+                        children,
+                        last_index,
+                        modification,
+                        context,
+                        max_key,
+                        changes,
+                        writer,
+                    )?;
+                }
                 Ok(matching_index) => {
                     let key = modification.keys.pop().unwrap();
                     last_index += matching_index;
@@ -424,6 +443,82 @@ where
         Ok(any_changes)
     }
 
+    /// Removes the run of consecutive entries beginning at `start` whose keys
+    /// are the next keys being removed by `modification`. When every entry in
+    /// the run is removed, the entries are drained at once rather than being
+    /// removed individually. Returns true if any entries were changed.
+    fn remove_leaf_run<IndexedType, Context, Indexer, Loader, IndexReducer>(
+        children: &mut Vec<KeyEntry<Index>>,
+        start: usize,
+        modification: &mut Modification<'_, IndexedType, Index>,
+        context: &mut ModificationContext<
+            IndexedType,
+            Index,
+            ReducedIndex,
+            Context,
+            Indexer,
+            Loader,
+            IndexReducer,
+        >,
+        max_key: Option<&ArcBytes<'_>>,
+        changes: &mut Context,
+        writer: &mut PagedWriter<'_>,
+    ) -> Result<bool, Error>
+    where
+        Indexer: FnMut(
+            &ArcBytes<'_>,
+            Option<&IndexedType>,
+            Option<&Index>,
+            &mut Context,
+            &mut PagedWriter<'_>,
+        ) -> Result<KeyOperation<Index>, Error>,
+        IndexReducer: Reducer<Index, ReducedIndex>,
+        Loader: FnMut(&Index, &mut PagedWriter<'_>) -> Result<Option<IndexedType>, Error>,
+    {
+        let mut operations = Vec::new();
+        while let Some(child) = children.get(start + operations.len()) {
+            match modification.keys.last() {
+                Some(key)
+                    if key == &child.key
+                        && !max_key.map(|max_key| key > max_key).unwrap_or_default() => {}
+                _ => break,
+            }
+
+            let key = modification.keys.pop().unwrap();
+            operations.push((context.indexer)(
+                &key,
+                None,
+                Some(&child.index),
+                changes,
+                writer,
+            )?);
+        }
+
+        if operations
+            .iter()
+            .all(|operation| matches!(operation, KeyOperation::Remove))
+        {
+            children.drain(start..start + operations.len());
+            return Ok(!operations.is_empty());
+        }
+
+        let mut any_changes = false;
+        for (offset, operation) in operations.into_iter().enumerate().rev() {
+            match operation {
+                KeyOperation::Skip => {}
+                KeyOperation::Set(index) => {
+                    children[start + offset].index = index;
+                    any_changes = true;
+                }
+                KeyOperation::Remove => {
+                    children.remove(start + offset);
+                    any_changes = true;
+                }
+            }
+        }
+        Ok(any_changes)
+    }
+
     fn modify_interior<IndexedType, Context, Indexer, Loader, IndexReducer>(
         children: &mut Vec<Interior<Index, ReducedIndex>>,
         modification: &mut Modification<'_, IndexedType, Index>,
@@ -1111,7 +1206,7 @@ where
         match &self.node {
             BTreeNode::Leaf(children) => {
                 for child in DirectionalSliceIterator::new(args.forwards, children) {
-                    if range.contains(&child.key.as_slice()) {
+                    if !args.is_before_seek(&child.key) && range.contains(&child.key.as_slice()) {
                         match (args.key_evaluator)(&child.key, &child.index) {
                             ScanEvaluation::ReadData => {
                                 if child.index.position() > 0 {
@@ -1129,6 +1224,7 @@ where
                                 }
                             }
                             ScanEvaluation::Skip => {}
+                            ScanEvaluation::SkipTo(key) => args.seek = Some(key),
                             ScanEvaluation::Stop => return Ok(false),
                         };
                     }
@@ -1181,20 +1277,29 @@ where
                         Bound::Unbounded => {}
                     }
 
-                    let keep_scanning =
-                        match (args.node_evaluator)(&child.key, &child.stats, current_depth) {
-                            ScanEvaluation::Stop => false,
-                            ScanEvaluation::ReadData => child.position.map_loaded_entry(
-                                file,
-                                vault,
-                                cache,
-                                Some(children.len()),
-                                |entry, file| {
-                                    entry.scan(range, args, file, vault, cache, current_depth + 1)
-                                },
-                            )?,
-                            ScanEvaluation::Skip => true,
-                        };
+                    let lower_key = preceding_key(children, index, args.forwards);
+                    if args.node_is_before_seek(&child.key, lower_key) {
+                        continue;
+                    }
+
+                    let keep_scanning = match args.evaluate_node(
+                        &child.key,
+                        lower_key,
+                        &child.stats,
+                        current_depth,
+                    ) {
+                        ScanEvaluation::Stop => false,
+                        ScanEvaluation::ReadData => child.position.map_loaded_entry(
+                            file,
+                            vault,
+                            cache,
+                            Some(children.len()),
+                            |entry, file| {
+                                entry.scan(range, args, file, vault, cache, current_depth + 1)
+                            },
+                        )?,
+                        ScanEvaluation::Skip | ScanEvaluation::SkipTo(_) => true,
+                    };
                     if !keep_scanning {
                         return Ok(false);
                     }
@@ -1288,7 +1393,7 @@ where
                                 ScanEvaluation::ReadData => {
                                     key_reader(entry.key.clone(), &entry.index)?;
                                 }
-                                ScanEvaluation::Skip => {}
+                                ScanEvaluation::Skip | ScanEvaluation::SkipTo(_) => {}
                                 ScanEvaluation::Stop => return Ok(false),
                             }
                         }
@@ -1395,6 +1500,118 @@ where
         self.dirty |= true;
         Ok(any_changes)
     }
+
+    /// Verifies every node and value chunk reachable from this node. Nodes
+    /// that have been loaded and not modified are verified using their
+    /// location on-disk.
+    pub(crate) fn verify(
+        &self,
+        file: &mut dyn File,
+        verifier: &mut ChunkVerifier<'_>,
+    ) -> Result<(), Error> {
+        match &self.node {
+            BTreeNode::Leaf(children) => {
+                for child in children {
+                    verifier.verify_value(child.index.position(), file)?;
+                }
+            }
+            BTreeNode::Interior(children) => {
+                for child in children {
+                    match &child.position {
+                        Pointer::Loaded {
+                            previous_location: None,
+                            entry,
+                        } => entry.verify(file, verifier)?,
+                        Pointer::Loaded { entry, .. } if entry.dirty => {
+                            entry.verify(file, verifier)?;
+                        }
+                        Pointer::OnDisk(position)
+                        | Pointer::Loaded {
+                            previous_location: Some(position),
+                            ..
+                        } => {
+                            if let Some(mut contents) = verifier.verify_node(*position, file)? {
+                                Self::deserialize_from(&mut contents, None)?
+                                    .verify(file, verifier)?;
+                            }
+                        }
+                    }
+                }
+            }
+            BTreeNode::Uninitialized => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Invokes `visitor` with this node, and then walks each child of this
+    /// node if `visitor` returns [`ScanEvaluation::ReadData`]. Returns false
+    /// if `visitor` returned [`ScanEvaluation::Stop`].
+    pub(crate) fn walk(
+        &self,
+        position: Option<u64>,
+        depth: usize,
+        visitor: &mut dyn FnMut(NodeInfo<'_, Index, ReducedIndex>) -> ScanEvaluation,
+        file: &mut dyn File,
+        vault: Option<&dyn AnyVault>,
+        cache: Option<&ChunkCache>,
+    ) -> Result<bool, Error> {
+        match &self.node {
+            BTreeNode::Leaf(children) => Ok(!matches!(
+                visitor(NodeInfo {
+                    depth,
+                    position,
+                    contents: NodeContents::Leaf(children),
+                }),
+                ScanEvaluation::Stop
+            )),
+            BTreeNode::Interior(children) => {
+                match visitor(NodeInfo {
+                    depth,
+                    position,
+                    contents: NodeContents::Interior(children),
+                }) {
+                    ScanEvaluation::ReadData => {}
+                    ScanEvaluation::Stop => return Ok(false),
+                    ScanEvaluation::Skip | ScanEvaluation::SkipTo(_) => return Ok(true),
+                }
+                for child in children {
+                    let child_position = match &child.position {
+                        Pointer::OnDisk(position) => Some(*position),
+                        Pointer::Loaded {
+                            previous_location,
+                            entry,
+                        } => (*previous_location).filter(|_| !entry.dirty),
+                    };
+                    let keep_walking = child
+                        .position
+                        .map_loaded_entry::<_, Infallible, _>(
+                            file,
+                            vault,
+                            cache,
+                            Some(children.len()),
+                            |entry, file| {
+                                entry
+                                    .walk(
+                                        child_position,
+                                        depth + 1,
+                                        &mut *visitor,
+                                        file,
+                                        vault,
+                                        cache,
+                                    )
+                                    .map_err(AbortError::Nebari)
+                            },
+                        )
+                        .map_err(AbortError::infallible)?;
+                    if !keep_walking {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            BTreeNode::Uninitialized => unreachable!(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Copy, Eq, PartialEq)]
@@ -1431,6 +1648,20 @@ impl<
         let mut bytes_written = 0;
         // The next byte determines the node type.
         match &mut self.node {
+            BTreeNode::Leaf(leafs) if paged_writer.key_compression => {
+                assert_children_order!(leafs);
+                writer.write_u8(2)?;
+                bytes_written += 1;
+                let mut previous_key: Option<ArcBytes<'static>> = None;
+                for leaf in leafs {
+                    bytes_written += leaf.serialize_with_prefix_to(
+                        previous_key.as_deref().unwrap_or_default(),
+                        writer,
+                        paged_writer,
+                    )?;
+                    previous_key = Some(leaf.key.clone());
+                }
+            }
             BTreeNode::Leaf(leafs) => {
                 assert_children_order!(leafs);
                 writer.write_u8(1)?;
@@ -1487,11 +1718,155 @@ impl<
                     dirty: false,
                 })
             }
+            2 => {
+                // Leaf with prefix-compressed keys
+                let mut nodes: Vec<KeyEntry<Index>> = Vec::new();
+                if let Some(current_order) = current_order {
+                    nodes.reserve(current_order);
+                }
+                while !reader.is_empty() {
+                    let previous_key = nodes.last().map_or(&[][..], |entry| &entry.key[..]);
+                    let entry = KeyEntry::deserialize_with_prefix_from(
+                        previous_key,
+                        reader,
+                        current_order,
+                    )?;
+                    nodes.push(entry);
+                }
+                Ok(Self {
+                    node: BTreeNode::Leaf(nodes),
+                    dirty: false,
+                })
+            }
             _ => Err(Error::data_integrity("invalid node header")),
         }
     }
 }
 
+/// Builds a B-Tree from entries provided in ascending key order. Each node
+/// other than the root is written as soon as it is filled, and each node
+/// contains at most `order` entries.
+///
+/// Each level holds back up to two nodes' worth of entries, which allows the
+/// last entries of a level to be divided between two nodes rather than leaving
+/// the final node nearly empty.
+pub(crate) struct SortedTreeBuilder<'a, Index, ReducedIndex, IndexReducer> {
+    order: usize,
+    reducer: &'a IndexReducer,
+    leaves: Vec<KeyEntry<Index>>,
+    leaves_written: bool,
+    interiors: Vec<(Vec<Interior<Index, ReducedIndex>>, bool)>,
+    scratch: Vec<u8>,
+}
+
+impl<'a, Index, ReducedIndex, IndexReducer> SortedTreeBuilder<'a, Index, ReducedIndex, IndexReducer>
+where
+    Index: ValueIndex + Clone + BinarySerialization + Debug + 'static,
+    ReducedIndex: Clone + BinarySerialization + Debug + 'static,
+    IndexReducer: Reducer<Index, ReducedIndex>,
+{
+    pub fn new(order: usize, reducer: &'a IndexReducer) -> Self {
+        Self {
+            order,
+            reducer,
+            leaves: Vec::with_capacity(order * 2),
+            leaves_written: false,
+            interiors: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Adds `entry` to the tree. Returns [`ErrorKind::KeysNotOrdered`] if the
+    /// entry's key isn't greater than the previously added key.
+    pub fn push(
+        &mut self,
+        entry: KeyEntry<Index>,
+        writer: &mut PagedWriter<'_>,
+    ) -> Result<(), Error> {
+        if matches!(self.leaves.last(), Some(last) if last.key >= entry.key) {
+            return Err(Error::from(ErrorKind::KeysNotOrdered));
+        }
+        self.leaves.push(entry);
+        if self.leaves.len() == self.order * 2 {
+            let node = self.leaves.drain(..self.order).collect();
+            self.leaves_written = true;
+            self.write_node(BTreeNode::Leaf(node), 0, writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining nodes and returns the root of the tree. The root
+    /// is not written.
+    pub fn finish(
+        mut self,
+        writer: &mut PagedWriter<'_>,
+    ) -> Result<BTreeEntry<Index, ReducedIndex>, Error> {
+        let leaves = std::mem::take(&mut self.leaves);
+        if !self.leaves_written && leaves.len() <= self.order {
+            return Ok(BTreeEntry::from(BTreeNode::Leaf(leaves)));
+        }
+        for node in self.divide(leaves) {
+            self.write_node(BTreeNode::Leaf(node), 0, writer)?;
+        }
+
+        let mut level = 0;
+        loop {
+            let (children, written) = std::mem::take(&mut self.interiors[level]);
+            if !written && children.len() <= self.order {
+                return Ok(BTreeEntry::from(BTreeNode::Interior(children)));
+            }
+            for node in self.divide(children) {
+                self.write_node(BTreeNode::Interior(node), level + 1, writer)?;
+            }
+            level += 1;
+        }
+    }
+
+    /// Divides the remaining entries of a level, which contains fewer than
+    /// two nodes' worth of entries, into as few nodes as possible.
+    fn divide<T>(&self, mut entries: Vec<T>) -> Vec<Vec<T>> {
+        if entries.len() > self.order {
+            let upper = entries.split_off(entries.len() / 2);
+            vec![entries, upper]
+        } else {
+            vec![entries]
+        }
+    }
+
+    /// Writes `node` and adds a pointer to it to the interior nodes at
+    /// `level`, where level 0 contains the parents of leaf nodes.
+    fn write_node(
+        &mut self,
+        node: BTreeNode<Index, ReducedIndex>,
+        level: usize,
+        writer: &mut PagedWriter<'_>,
+    ) -> Result<(), Error> {
+        let mut entry = BTreeEntry::from(node);
+        let key = entry.max_key().clone();
+        let stats = entry.stats(self.reducer);
+        self.scratch.clear();
+        entry.serialize_to(&mut self.scratch, writer)?;
+        let position = writer.write_chunk(&self.scratch)?;
+
+        if self.interiors.len() == level {
+            self.interiors
+                .push((Vec::with_capacity(self.order * 2), false));
+        }
+        let (children, written) = &mut self.interiors[level];
+        children.push(Interior {
+            key,
+            position: Pointer::OnDisk(position),
+            stats,
+        });
+        if children.len() == self.order * 2 {
+            let node = children.drain(..self.order).collect();
+            *written = true;
+            self.write_node(BTreeNode::Interior(node), level + 1, writer)?;
+        }
+        Ok(())
+    }
+}
+
 /// An operation to perform on a key.
 #[derive(Debug)]
 pub enum KeyOperation<T> {
@@ -1503,6 +1878,23 @@ pub enum KeyOperation<T> {
     Remove,
 }
 
+/// Returns the key that precedes the keys contained in the child visited at
+/// `index` when iterating `children` in the given direction.
+fn preceding_key<Index, ReducedIndex>(
+    children: &[Interior<Index, ReducedIndex>],
+    index: usize,
+    forwards: bool,
+) -> Option<&[u8]> {
+    let position = if forwards {
+        index
+    } else {
+        children.len() - 1 - index
+    };
+    position
+        .checked_sub(1)
+        .map(|previous| children[previous].key.as_slice())
+}
+
 struct DirectionalSliceIterator<'a, I> {
     forwards: bool,
     index: usize,
@@ -1553,6 +1945,9 @@ pub struct ScanArgs<
     pub node_evaluator: NodeEvaluator,
     pub key_evaluator: KeyEvaluator,
     pub data_callback: DataCallback,
+    /// The key the scan is skipping to, set when an evaluator returns
+    /// [`ScanEvaluation::SkipTo`].
+    seek: Option<ArcBytes<'static>>,
     _phantom: PhantomData<(Index, ReducedIndex, CallerError)>,
 }
 
@@ -1581,7 +1976,63 @@ where
             node_evaluator,
             key_evaluator,
             data_callback,
+            seek: None,
             _phantom: PhantomData,
         }
     }
+
+    /// Evaluates a node whose keys are greater than `lower_key` and less than or
+    /// equal to `max_key`. If the evaluator skips to a key, the node is read
+    /// only if it may contain keys at or beyond the key.
+    fn evaluate_node(
+        &mut self,
+        max_key: &ArcBytes<'static>,
+        lower_key: Option<&[u8]>,
+        stats: &ReducedIndex,
+        depth: usize,
+    ) -> ScanEvaluation {
+        match (self.node_evaluator)(max_key, stats, depth) {
+            ScanEvaluation::SkipTo(key) => {
+                self.seek = Some(key);
+                if self.node_is_before_seek(max_key, lower_key) {
+                    ScanEvaluation::Skip
+                } else {
+                    ScanEvaluation::ReadData
+                }
+            }
+            evaluation => evaluation,
+        }
+    }
+
+    /// Returns true if `key` is visited before the key being skipped to. Once a
+    /// key at or beyond the seek key is reached, the seek is cleared.
+    fn is_before_seek(&mut self, key: &[u8]) -> bool {
+        match &self.seek {
+            Some(seek) if self.precedes(key, seek) => true,
+            Some(_) => {
+                self.seek = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if every key in a node, whose keys are greater than
+    /// `lower_key` and less than or equal to `max_key`, is visited before the
+    /// key being skipped to.
+    fn node_is_before_seek(&self, max_key: &[u8], lower_key: Option<&[u8]>) -> bool {
+        match &self.seek {
+            Some(seek) if self.forwards => max_key < &seek[..],
+            Some(seek) => lower_key.map_or(false, |lower_key| lower_key >= &seek[..]),
+            None => false,
+        }
+    }
+
+    fn precedes(&self, key: &[u8], seek: &[u8]) -> bool {
+        if self.forwards {
+            key < seek
+        } else {
+            key > seek
+        }
+    }
 }
@@ -0,0 +1,158 @@
+use std::path::Path;
+
+use super::{manifest_path, root, save_tree, ManifestEntry, PagedWriter, State, TreeFile};
+use crate::{
+    error::Error,
+    io::{File, FileManager, FileOp, ManagedFile},
+    vault::AnyVault,
+    ArcBytes, ChunkCache, Context, ErrorKind,
+};
+
+/// Creates a new tree from entries that are already sorted by key.
+///
+/// Inserting sorted entries one modification at a time repeatedly splits the
+/// tree's nodes as it grows. Instead, this loader fills leaf nodes in order and
+/// builds the interior nodes above them from the bottom up, writing each node
+/// once in a single pass. The tree's root is written after all entries have
+/// been written.
+#[derive(Debug)]
+#[must_use]
+pub struct BulkLoader<Root: root::Root> {
+    order: usize,
+    reducer: Root::Reducer,
+}
+
+impl<Root: root::Root> BulkLoader<Root> {
+    /// Returns a loader that creates nodes containing up to `order` entries.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `order` is less than 4.
+    pub fn new(order: usize) -> Self
+    where
+        Root::Reducer: Default,
+    {
+        Self::with_reducer(order, <Root::Reducer as Default>::default())
+    }
+
+    /// Returns a loader that creates nodes containing up to `order` entries,
+    /// using `reducer` to index and reduce the loaded entries.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `order` is less than 4.
+    pub fn with_reducer(order: usize, reducer: Root::Reducer) -> Self {
+        assert!(order >= 4, "order must be at least 4");
+        Self { order, reducer }
+    }
+
+    /// Creates a new tree file at `path` containing `entries`, which must be
+    /// sorted in strictly ascending order by key.
+    ///
+    /// ## Errors
+    ///
+    /// - [`KeysNotOrdered`](ErrorKind::KeysNotOrdered): A key wasn't greater
+    ///   than the key before it. The tree file is deleted.
+    /// - [`Io`](ErrorKind::Io): A file already exists at `path`, in which case
+    ///   the error's kind is [`AlreadyExists`](std::io::ErrorKind::AlreadyExists).
+    pub fn load<File, Entries, Key, Value>(
+        self,
+        path: impl AsRef<Path>,
+        context: &Context<File::Manager>,
+        entries: Entries,
+    ) -> Result<TreeFile<Root, File>, Error>
+    where
+        File: ManagedFile,
+        Entries: IntoIterator<Item = (Key, Value)>,
+        Key: Into<ArcBytes<'static>>,
+        Value: Into<ArcBytes<'static>>,
+    {
+        let path = path.as_ref();
+        if context.file_manager.exists(path)? {
+            return Err(Error::from(std::io::Error::from(
+                std::io::ErrorKind::AlreadyExists,
+            )));
+        }
+
+        let state = State::new(None, None, Root::default_with(self.reducer));
+        let mut tree = TreeFile::<Root, File>::write(path, state, context, None)?;
+        let mut saved_root = None;
+        let result = tree.file.execute(TreeBulkLoader {
+            state: &tree.state,
+            vault: tree.vault.as_deref(),
+            cache: tree.cache.as_ref(),
+            entries: &mut entries.into_iter().map(
+                |(key, value)| -> (ArcBytes<'static>, ArcBytes<'static>) {
+                    (key.into(), value.into())
+                },
+            ),
+            order: self.order,
+            scratch: &mut tree.scratch,
+            saved_root: &mut saved_root,
+            page_size: tree.page_size,
+            write_buffer_size: tree.write_buffer_size,
+            key_compression: tree.key_compression,
+        });
+        if let Err(err) = result {
+            // The file only contains the entries written before the failure,
+            // and no root.
+            drop(tree);
+            context.file_manager.delete(path)?;
+            context.file_manager.delete(manifest_path(path))?;
+            return Err(err);
+        }
+        tree.append_to_manifest(saved_root)?;
+        Ok(tree)
+    }
+}
+
+struct TreeBulkLoader<'a, 'e, Root: root::Root> {
+    state: &'a State<Root>,
+    vault: Option<&'a dyn AnyVault>,
+    cache: Option<&'a ChunkCache>,
+    entries: &'e mut dyn Iterator<Item = (ArcBytes<'static>, ArcBytes<'static>)>,
+    order: usize,
+    scratch: &'a mut Vec<u8>,
+    saved_root: &'a mut Option<ManifestEntry>,
+    page_size: usize,
+    write_buffer_size: usize,
+    key_compression: bool,
+}
+
+impl<'a, 'e, Root> FileOp<Result<(), Error>> for TreeBulkLoader<'a, 'e, Root>
+where
+    Root: root::Root,
+{
+    fn execute(self, file: &mut dyn File) -> Result<(), Error> {
+        let mut active_state = self.state.lock();
+        if active_state.file_id != file.id() {
+            return Err(Error::from(ErrorKind::TreeCompacted));
+        }
+
+        let mut data_block = PagedWriter::new(
+            None,
+            file,
+            self.vault,
+            self.cache,
+            self.page_size,
+            self.write_buffer_size,
+            active_state.current_position,
+        )?
+        .with_key_compression(self.key_compression);
+
+        active_state
+            .root
+            .bulk_load(self.entries, &mut data_block, self.order)?;
+
+        *self.saved_root = Some(save_tree(
+            &mut *active_state,
+            self.vault,
+            self.cache,
+            data_block,
+            self.scratch,
+            true,
+        )?);
+        active_state.publish(self.state);
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use super::{btree_entry::Reducer, BinarySerialization, PagedWriter};
+use super::{
+    btree_entry::Reducer,
+    serialization::{decode_index, encode_index},
+    BinarySerialization, PagedWriter,
+};
 use crate::{
     error::Error,
     tree::{by_sequence::SequenceId, key_entry::ValueIndex},
@@ -12,7 +16,8 @@ use crate::{
 pub struct VersionedByIdIndex<EmbeddedIndex: super::EmbeddedIndex> {
     /// The unique sequence id generated when writing the value to the file.
     pub sequence_id: SequenceId,
-    /// The size of the value stored on disk.
+    /// The size of the value stored on disk. Values longer than `u32::MAX`
+    /// bytes record `u32::MAX`.
     pub value_length: u32,
     /// The position of the value on disk.
     pub position: u64,
@@ -20,6 +25,31 @@ pub struct VersionedByIdIndex<EmbeddedIndex: super::EmbeddedIndex> {
     pub embedded: EmbeddedIndex,
 }
 
+impl<EmbeddedIndex: super::EmbeddedIndex> VersionedByIdIndex<EmbeddedIndex> {
+    /// Encodes this index into a standalone, versioned format that can be
+    /// stored outside of a tree. Use [`Self::decode()`] to restore the index.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        encode_index(|writer| self.write_to(writer))
+    }
+
+    /// Decodes an index previously encoded using [`Self::encode()`].
+    ///
+    /// ## Errors
+    ///
+    /// - [`DataIntegrity`](crate::ErrorKind::DataIntegrity): `bytes` wasn't
+    ///   encoded by a compatible version of this crate or is malformed.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        decode_index(bytes)
+    }
+
+    fn write_to(&self, writer: &mut Vec<u8>) -> Result<usize, Error> {
+        writer.write_u64::<BigEndian>(self.sequence_id.0)?;
+        writer.write_u32::<BigEndian>(self.value_length)?;
+        writer.write_u64::<BigEndian>(self.position)?;
+        Ok(20 + self.embedded.serialize_to(writer)?)
+    }
+}
+
 impl<EmbeddedIndex> BinarySerialization for VersionedByIdIndex<EmbeddedIndex>
 where
     EmbeddedIndex: super::EmbeddedIndex,
@@ -29,10 +59,7 @@ where
         writer: &mut Vec<u8>,
         _paged_writer: &mut PagedWriter<'_>,
     ) -> Result<usize, Error> {
-        writer.write_u64::<BigEndian>(self.sequence_id.0)?;
-        writer.write_u32::<BigEndian>(self.value_length)?;
-        writer.write_u64::<BigEndian>(self.position)?;
-        Ok(20 + self.embedded.serialize_to(writer)?)
+        self.write_to(writer)
     }
 
     fn deserialize_from(
@@ -60,7 +87,8 @@ impl<EmbeddedIndex: super::EmbeddedIndex> ValueIndex for VersionedByIdIndex<Embe
 /// The index stored within [`UnversionedTreeRoot::by_id_root`](crate::tree::UnversionedTreeRoot::by_id_root).
 #[derive(Clone, Debug)]
 pub struct UnversionedByIdIndex<EmbeddedIndex: super::EmbeddedIndex> {
-    /// The size of the value stored on disk.
+    /// The size of the value stored on disk. Values longer than `u32::MAX`
+    /// bytes record `u32::MAX`.
     pub value_length: u32,
     /// The position of the value on disk.
     pub position: u64,
@@ -68,6 +96,30 @@ pub struct UnversionedByIdIndex<EmbeddedIndex: super::EmbeddedIndex> {
     pub embedded: EmbeddedIndex,
 }
 
+impl<EmbeddedIndex: super::EmbeddedIndex> UnversionedByIdIndex<EmbeddedIndex> {
+    /// Encodes this index into a standalone, versioned format that can be
+    /// stored outside of a tree. Use [`Self::decode()`] to restore the index.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        encode_index(|writer| self.write_to(writer))
+    }
+
+    /// Decodes an index previously encoded using [`Self::encode()`].
+    ///
+    /// ## Errors
+    ///
+    /// - [`DataIntegrity`](crate::ErrorKind::DataIntegrity): `bytes` wasn't
+    ///   encoded by a compatible version of this crate or is malformed.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        decode_index(bytes)
+    }
+
+    fn write_to(&self, writer: &mut Vec<u8>) -> Result<usize, Error> {
+        writer.write_u32::<BigEndian>(self.value_length)?;
+        writer.write_u64::<BigEndian>(self.position)?;
+        Ok(12 + self.embedded.serialize_to(writer)?)
+    }
+}
+
 impl<EmbeddedIndex> BinarySerialization for UnversionedByIdIndex<EmbeddedIndex>
 where
     EmbeddedIndex: super::EmbeddedIndex,
@@ -77,9 +129,7 @@ where
         writer: &mut Vec<u8>,
         _paged_writer: &mut PagedWriter<'_>,
     ) -> Result<usize, Error> {
-        writer.write_u32::<BigEndian>(self.value_length)?;
-        writer.write_u64::<BigEndian>(self.position)?;
-        Ok(12 + self.embedded.serialize_to(writer)?)
+        self.write_to(writer)
     }
 
     fn deserialize_from(
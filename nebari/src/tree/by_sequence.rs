@@ -4,7 +4,12 @@ use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::{
     error::Error,
-    tree::{btree_entry::Reducer, key_entry::ValueIndex, BinarySerialization, PagedWriter},
+    tree::{
+        btree_entry::Reducer,
+        key_entry::ValueIndex,
+        serialization::{decode_index, encode_index},
+        BinarySerialization, PagedWriter,
+    },
     ArcBytes, ErrorKind,
 };
 
@@ -49,7 +54,8 @@ pub struct BySequenceIndex<Embedded> {
     pub key: ArcBytes<'static>,
     /// The previous sequence of this key.
     pub last_sequence: Option<SequenceId>,
-    /// The size of the value stored on disk.
+    /// The size of the value stored on disk. Values longer than `u32::MAX`
+    /// bytes record `u32::MAX`.
     pub value_length: u32,
     /// The position of the value on disk.
     pub position: u64,
@@ -60,15 +66,24 @@ pub struct BySequenceIndex<Embedded> {
     pub embedded: Option<Embedded>,
 }
 
-impl<Embedded> BinarySerialization for BySequenceIndex<Embedded>
-where
-    Embedded: super::EmbeddedIndex,
-{
-    fn serialize_to(
-        &mut self,
-        writer: &mut Vec<u8>,
-        _paged_writer: &mut PagedWriter<'_>,
-    ) -> Result<usize, Error> {
+impl<Embedded: super::EmbeddedIndex> BySequenceIndex<Embedded> {
+    /// Encodes this index into a standalone, versioned format that can be
+    /// stored outside of a tree. Use [`Self::decode()`] to restore the index.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        encode_index(|writer| self.write_to(writer))
+    }
+
+    /// Decodes an index previously encoded using [`Self::encode()`].
+    ///
+    /// ## Errors
+    ///
+    /// - [`DataIntegrity`](crate::ErrorKind::DataIntegrity): `bytes` wasn't
+    ///   encoded by a compatible version of this crate or is malformed.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        decode_index(bytes)
+    }
+
+    fn write_to(&self, writer: &mut Vec<u8>) -> Result<usize, Error> {
         let mut bytes_written = 0;
         writer.write_u32::<BigEndian>(self.value_length)?;
         bytes_written += 4;
@@ -89,6 +104,19 @@ where
 
         Ok(bytes_written)
     }
+}
+
+impl<Embedded> BinarySerialization for BySequenceIndex<Embedded>
+where
+    Embedded: super::EmbeddedIndex,
+{
+    fn serialize_to(
+        &mut self,
+        writer: &mut Vec<u8>,
+        _paged_writer: &mut PagedWriter<'_>,
+    ) -> Result<usize, Error> {
+        self.write_to(writer)
+    }
 
     fn deserialize_from(
         reader: &mut ArcBytes<'_>,
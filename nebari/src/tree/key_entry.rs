@@ -50,6 +50,75 @@ impl<Index: ValueIndex + BinarySerialization> KeyEntry<Index> {
     }
 }
 
+impl<Index: BinarySerialization> KeyEntry<Index> {
+    /// Serializes this entry, only storing the bytes of the key that follow
+    /// the prefix it shares with `previous_key`.
+    pub(crate) fn serialize_with_prefix_to(
+        &mut self,
+        previous_key: &[u8],
+        writer: &mut Vec<u8>,
+        paged_writer: &mut PagedWriter<'_>,
+    ) -> Result<usize, Error> {
+        let mut bytes_written = 0;
+        // Write the key's shared prefix length followed by the remainder of
+        // the key.
+        let key_len = u16::try_from(self.key.len()).map_err(|_| ErrorKind::KeyTooLarge)?;
+        let shared = self
+            .key
+            .iter()
+            .zip(previous_key)
+            .take_while(|(a, b)| a == b)
+            .count();
+        // The shared prefix can't be longer than the key itself.
+        #[allow(clippy::cast_possible_truncation)]
+        let shared_len = shared as u16;
+        writer.write_u16::<BigEndian>(shared_len)?;
+        writer.write_u16::<BigEndian>(key_len - shared_len)?;
+        writer.extend_from_slice(&self.key[shared..]);
+        bytes_written += 4 + self.key.len() - shared;
+
+        // Write the value
+        bytes_written += self.index.serialize_to(writer, paged_writer)?;
+        Ok(bytes_written)
+    }
+
+    /// Deserializes an entry written by
+    /// [`serialize_with_prefix_to()`](Self::serialize_with_prefix_to),
+    /// restoring the key's shared prefix from `previous_key`.
+    pub(crate) fn deserialize_with_prefix_from(
+        previous_key: &[u8],
+        reader: &mut ArcBytes<'_>,
+        current_order: Option<usize>,
+    ) -> Result<Self, Error> {
+        let shared = reader.read_u16::<BigEndian>()? as usize;
+        if shared > previous_key.len() {
+            return Err(Error::data_integrity(format!(
+                "shared key prefix length {} found but previous key is {} bytes",
+                shared,
+                previous_key.len()
+            )));
+        }
+        let suffix_len = reader.read_u16::<BigEndian>()? as usize;
+        if suffix_len > reader.len() {
+            return Err(Error::data_integrity(format!(
+                "key length {} found but only {} bytes remaining",
+                suffix_len,
+                reader.len()
+            )));
+        }
+        let mut key = Vec::with_capacity(shared + suffix_len);
+        key.extend_from_slice(&previous_key[..shared]);
+        key.extend_from_slice(&reader.read_bytes(suffix_len)?);
+
+        let value = Index::deserialize_from(reader, current_order)?;
+
+        Ok(Self {
+            key: ArcBytes::from(key),
+            index: value,
+        })
+    }
+}
+
 impl<Index: BinarySerialization> BinarySerialization for KeyEntry<Index> {
     fn serialize_to(
         &mut self,
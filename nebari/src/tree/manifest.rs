@@ -0,0 +1,139 @@
+use std::{
+    convert::TryFrom,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    io::{File, FileManager, FileOp, ManagedFileOpener, OperableFile},
+    transaction::TransactionId,
+    Context, Error,
+};
+
+/// The number of bytes each [`ManifestEntry`] occupies in a manifest file.
+const ENTRY_SIZE: usize = 16;
+
+/// The location of a root written to a tree file.
+///
+/// Each tree file opened for writing keeps an append-only manifest next to it
+/// that lists every root written to the file. The manifest allows the most
+/// recent root to be located without scanning the tree file's pages. It is
+/// never trusted on its own: the newest entry is validated against the tree
+/// file, and a missing manifest is rebuilt starting with the tree's most recent
+/// root.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ManifestEntry {
+    /// The transaction id stored in the root.
+    pub transaction_id: TransactionId,
+    /// The position of the root's page header in the tree file.
+    pub position: u64,
+}
+
+/// Returns the path of the manifest for the tree file at `tree_path`.
+pub fn manifest_path(tree_path: &Path) -> PathBuf {
+    let mut path = tree_path.as_os_str().to_os_string();
+    path.push(".manifest");
+    PathBuf::from(path)
+}
+
+/// An open manifest of a tree file.
+#[derive(Debug)]
+pub struct Manifest<Manager: FileManager> {
+    path: PathBuf,
+    file: Manager::FileHandle,
+}
+
+impl<Manager: FileManager> Manifest<Manager> {
+    /// Opens the manifest of the tree file at `tree_path` for appending. If the
+    /// tree file contains data but no manifest exists, the manifest is rebuilt
+    /// starting with `newest_root`, the location of the tree's most recent
+    /// root. Older roots aren't listed. If the location isn't known, the
+    /// manifest starts with the next root appended to it.
+    pub fn open(
+        tree_path: &Path,
+        context: &Context<Manager>,
+        newest_root: Option<ManifestEntry>,
+    ) -> Result<Self, Error> {
+        let path = manifest_path(tree_path);
+        let needs_rebuild = context.file_manager.file_length(tree_path)? > 0
+            && !matches!(context.file_manager.file_length(&path), Ok(length) if length > 0);
+        let mut manifest = Self {
+            file: context.file_manager.append(&path)?,
+            path,
+        };
+        if needs_rebuild {
+            if let Some(newest_root) = newest_root {
+                manifest.reset(&context.file_manager, &[newest_root])?;
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Appends `entry` to the manifest.
+    pub fn append(&mut self, entry: ManifestEntry) -> Result<(), Error> {
+        self.file.execute(ManifestAppender { entries: &[entry] })
+    }
+
+    /// Replaces the contents of the manifest with `entries`.
+    pub fn reset(
+        &mut self,
+        file_manager: &Manager,
+        entries: &[ManifestEntry],
+    ) -> Result<(), Error> {
+        file_manager.delete(&self.path)?;
+        self.file = file_manager.append(&self.path)?;
+        self.file.execute(ManifestAppender { entries })
+    }
+}
+
+/// Appends `entries` to the manifest file this operation is executed on.
+struct ManifestAppender<'a> {
+    entries: &'a [ManifestEntry],
+}
+
+impl FileOp<Result<(), Error>> for ManifestAppender<'_> {
+    fn execute(self, file: &mut dyn File) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in self.entries {
+            bytes.write_u64::<BigEndian>(entry.transaction_id.0)?;
+            bytes.write_u64::<BigEndian>(entry.position)?;
+        }
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads all complete entries from the manifest of the tree file at
+/// `tree_path`. A missing manifest contains no entries.
+pub fn read_manifest<Manager: FileManager>(
+    tree_path: &Path,
+    context: &Context<Manager>,
+) -> Result<Vec<ManifestEntry>, Error> {
+    let path = manifest_path(tree_path);
+    let length = match context.file_manager.file_length(&path) {
+        Ok(length) => length,
+        Err(err) if err.kind.is_file_not_found() => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    // An interrupted append can leave a partial entry at the end of the file.
+    let complete_length = usize::try_from(length - length % ENTRY_SIZE as u64)
+        .map_err(|_| Error::data_integrity("manifest is too large to read"))?;
+    if complete_length == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut file = context.file_manager.open_for_read(&path, None)?;
+    let mut bytes = vec![0; complete_length];
+    file.read_exact(&mut bytes)?;
+    let mut bytes = &bytes[..];
+    let mut entries = Vec::with_capacity(complete_length / ENTRY_SIZE);
+    while !bytes.is_empty() {
+        entries.push(ManifestEntry {
+            transaction_id: TransactionId(bytes.read_u64::<BigEndian>()?),
+            position: bytes.read_u64::<BigEndian>()?,
+        });
+    }
+    Ok(entries)
+}
@@ -18,6 +18,16 @@ pub struct Modification<'a, T, Index> {
 }
 
 impl<'a, T, Index> Modification<'a, T, Index> {
+    /// Returns a builder for a modification. Keys may be provided in any
+    /// order.
+    pub fn builder() -> ModificationBuilder<'a, T, Index> {
+        ModificationBuilder {
+            persistence_mode: PersistenceMode::Sync,
+            keys: Vec::new(),
+            operation: None,
+        }
+    }
+
     pub(crate) fn reverse(&mut self) -> Result<(), Error> {
         if self.keys.windows(2).all(|w| w[0] < w[1]) {
             self.keys.reverse();
@@ -31,6 +41,86 @@ impl<'a, T, Index> Modification<'a, T, Index> {
     }
 }
 
+/// Builds a [`Modification`]. Returned from [`Modification::builder()`].
+#[derive(Debug)]
+#[must_use]
+pub struct ModificationBuilder<'a, T, Index> {
+    persistence_mode: PersistenceMode,
+    keys: Vec<ArcBytes<'a>>,
+    operation: Option<Operation<'a, T, Index>>,
+}
+
+impl<'a, T, Index> ModificationBuilder<'a, T, Index> {
+    /// Sets the persistence mode of the modification. Defaults to
+    /// [`PersistenceMode::Sync`].
+    pub fn persistence_mode(mut self, persistence_mode: impl Into<PersistenceMode>) -> Self {
+        self.persistence_mode = persistence_mode.into();
+        self
+    }
+
+    /// Adds `key` to the keys being modified.
+    pub fn key(mut self, key: impl Into<ArcBytes<'a>>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Adds `keys` to the keys being modified.
+    pub fn keys<Keys, Key>(mut self, keys: Keys) -> Self
+    where
+        Keys: IntoIterator<Item = Key>,
+        Key: Into<ArcBytes<'a>>,
+    {
+        self.keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets every key to `value`.
+    pub fn set(mut self, value: T) -> Self {
+        self.operation = Some(Operation::Set(value));
+        self
+    }
+
+    /// Removes every key.
+    pub fn remove(mut self) -> Self {
+        self.operation = Some(Operation::Remove);
+        self
+    }
+
+    /// Invokes `callback` for each key to determine how to modify it. See
+    /// [`Operation::CompareSwap`] for more information.
+    pub fn compare_swap<F>(mut self, callback: &'a mut F) -> Self
+    where
+        F: FnMut(&ArcBytes<'_>, Option<&Index>, Option<T>) -> KeyOperation<T> + 'a,
+    {
+        self.operation = Some(Operation::CompareSwap(CompareSwap::new(callback)));
+        self
+    }
+
+    /// Returns the modification, with its keys sorted in ascending order.
+    ///
+    /// ## Errors
+    ///
+    /// - [`DuplicateKey`](ErrorKind::DuplicateKey): A key was provided more
+    ///   than once.
+    /// - [`Message`](ErrorKind::Message): No operation was provided.
+    pub fn build(mut self) -> Result<Modification<'a, T, Index>, Error> {
+        let operation = self
+            .operation
+            .ok_or_else(|| ErrorKind::message("modification builder requires an operation"))?;
+        self.keys.sort();
+        if let Some(pair) = self.keys.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(Error::from(ErrorKind::DuplicateKey(
+                pair[0].clone().into_owned(),
+            )));
+        }
+        Ok(Modification {
+            persistence_mode: self.persistence_mode,
+            keys: self.keys,
+            operation,
+        })
+    }
+}
+
 /// Controls the persistence guarantees of write operations.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum PersistenceMode {
@@ -0,0 +1,582 @@
+//! Ready-made [`EmbeddedIndex`] implementations that aggregate an integer
+//! stored within each value, or build a histogram of the values in a tree.
+//!
+//! Each index reads a big-endian integer from a fixed position within every
+//! value using an [`IntegerIndexer`]. The aggregate for a range of keys can be
+//! retrieved using `reduce()`:
+//!
+//! ```rust
+//! # use nebari::tree::{reducers::SumIndex, Root, VersionedTreeRoot};
+//! # fn test() -> Result<(), nebari::Error> {
+//! # let tempdir = tempfile::tempdir().unwrap();
+//! let roots = nebari::Config::new(tempdir.path()).open()?;
+//! let tree = roots.tree(VersionedTreeRoot::<SumIndex>::tree("totals"))?;
+//! tree.set(b"a", 2_i64.to_be_bytes().to_vec())?;
+//! tree.set(b"b", 40_i64.to_be_bytes().to_vec())?;
+//! let stats = tree.reduce(&(..))?.unwrap();
+//! assert_eq!(stats.embedded.0, 42);
+//! # Ok(())
+//! # }
+//! # test().unwrap();
+//! ```
+//!
+//! To read the integer from a different location within each value, provide a
+//! configured [`IntegerIndexer`] using
+//! [`Root::tree_with_reducer()`](super::Root::tree_with_reducer):
+//!
+//! ```rust
+//! # use nebari::tree::{reducers::{IntegerIndexer, MaxIndex}, ByIdIndexer, Root, UnversionedTreeRoot};
+//! # fn test() -> Result<(), nebari::Error> {
+//! # let tempdir = tempfile::tempdir().unwrap();
+//! # let roots = nebari::Config::new(tempdir.path()).open()?;
+//! // Read a 4-byte integer following an 8-byte header.
+//! let tree = roots.tree(UnversionedTreeRoot::<MaxIndex>::tree_with_reducer(
+//!     "maximums",
+//!     ByIdIndexer(IntegerIndexer::new(8, 4)),
+//! ))?;
+//! tree.set(b"a", [&[0; 8][..], &7_i32.to_be_bytes()].concat())?;
+//! tree.set(b"b", [&[0; 8][..], &(-3_i32).to_be_bytes()].concat())?;
+//! let stats = tree.reduce(&(..))?.unwrap();
+//! assert_eq!(stats.embedded.0, Some(7));
+//! # Ok(())
+//! # }
+//! # test().unwrap();
+//! ```
+//!
+//! [`HistogramIndex`] counts the values falling into each of a set of buckets,
+//! measuring either the length of each value or an integer read using an
+//! [`IntegerIndexer`]. Because it has no default buckets, it is always
+//! configured using a [`HistogramIndexer`]:
+//!
+//! ```rust
+//! # use nebari::tree::{reducers::{Histogram, HistogramIndex, HistogramIndexer}, ByIdIndexer, Root, VersionedTreeRoot};
+//! # fn test() -> Result<(), nebari::Error> {
+//! # let tempdir = tempfile::tempdir().unwrap();
+//! # let roots = nebari::Config::new(tempdir.path()).open()?;
+//! // Count values shorter than 16 bytes, shorter than 1024 bytes, and the rest.
+//! let tree = roots.tree(VersionedTreeRoot::<HistogramIndex>::tree_with_reducer(
+//!     "sizes",
+//!     ByIdIndexer(HistogramIndexer::value_lengths(&[16, 1024])),
+//! ))?;
+//! tree.set(b"a", vec![0; 8])?;
+//! tree.set(b"b", vec![0; 100])?;
+//! tree.set(b"c", vec![0; 200])?;
+//! let stats = tree.reduce(&(..))?.unwrap();
+//! assert_eq!(stats.embedded, Histogram(vec![1, 2, 0]));
+//! # Ok(())
+//! # }
+//! # test().unwrap();
+//! ```
+//!
+//! The configuration of the indexer isn't stored in the tree. A tree must
+//! always be opened using the same configuration, otherwise the indexes
+//! already stored will not match newly written ones.
+
+use std::sync::Arc;
+
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+
+use super::{EmbeddedIndex, Indexer, Reducer, Serializable};
+use crate::{error::Error, ArcBytes};
+
+/// Reads a signed, big-endian integer from each value of a tree.
+///
+/// Integers narrower than 8 bytes are sign-extended. Values that are removed
+/// or too short to contain the integer are not included in any aggregate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IntegerIndexer {
+    offset: usize,
+    width: usize,
+}
+
+impl Default for IntegerIndexer {
+    /// Returns an indexer that reads an `i64` from the first 8 bytes of each
+    /// value.
+    fn default() -> Self {
+        Self::new(0, 8)
+    }
+}
+
+impl IntegerIndexer {
+    /// Returns an indexer that reads an integer of `width` bytes, starting
+    /// `offset` bytes into each value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0 or greater than 8.
+    #[must_use]
+    pub fn new(offset: usize, width: usize) -> Self {
+        assert!(
+            (1..=8).contains(&width),
+            "integer width must be between 1 and 8 bytes"
+        );
+        Self { offset, width }
+    }
+
+    /// Returns the number of bytes before the integer in each value.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of bytes the integer occupies.
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Reads the integer from `value`. Returns None if `value` is too short
+    /// to contain it.
+    #[must_use]
+    pub fn read(&self, value: &[u8]) -> Option<i64> {
+        let end = self.offset.checked_add(self.width)?;
+        value
+            .get(self.offset..end)
+            .map(|bytes| BigEndian::read_int(bytes, self.width))
+    }
+
+    fn read_value(&self, value: Option<&ArcBytes<'static>>) -> Option<i64> {
+        value.and_then(|value| self.read(value))
+    }
+}
+
+/// An [`EmbeddedIndex`] that sums the integers stored in each value. The
+/// reduced index is a [`Sum`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SumIndex(pub i64);
+
+/// The sum of the integers of one or more [`SumIndex`]es. The sum is stored
+/// using 128 bits so that it cannot overflow.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sum(pub i128);
+
+impl EmbeddedIndex for SumIndex {
+    type Reduced = Sum;
+    type Indexer = IntegerIndexer;
+}
+
+impl Indexer<SumIndex> for IntegerIndexer {
+    fn index(&self, _key: &ArcBytes<'_>, value: Option<&ArcBytes<'static>>) -> SumIndex {
+        SumIndex(self.read_value(value).unwrap_or_default())
+    }
+}
+
+impl Reducer<SumIndex, Sum> for IntegerIndexer {
+    fn reduce<'a, Indexes, IndexesIter>(&self, indexes: Indexes) -> Sum
+    where
+        Indexes: IntoIterator<Item = &'a SumIndex, IntoIter = IndexesIter> + ExactSizeIterator,
+        IndexesIter: Iterator<Item = &'a SumIndex> + ExactSizeIterator + Clone,
+    {
+        Sum(indexes.into_iter().map(|index| i128::from(index.0)).sum())
+    }
+
+    fn rereduce<'a, ReducedIndexes, ReducedIndexesIter>(&self, values: ReducedIndexes) -> Sum
+    where
+        Self: 'a,
+        ReducedIndexes:
+            IntoIterator<Item = &'a Sum, IntoIter = ReducedIndexesIter> + ExactSizeIterator,
+        ReducedIndexesIter: Iterator<Item = &'a Sum> + ExactSizeIterator + Clone,
+    {
+        Sum(values.into_iter().map(|sum| sum.0).sum())
+    }
+}
+
+impl Serializable for SumIndex {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        writer.write_i64::<BigEndian>(self.0)?;
+        Ok(8)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        Ok(Self(reader.read_i64::<BigEndian>()?))
+    }
+}
+
+impl Serializable for Sum {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        writer.write_i128::<BigEndian>(self.0)?;
+        Ok(16)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        Ok(Self(reader.read_i128::<BigEndian>()?))
+    }
+}
+
+/// An [`EmbeddedIndex`] that tracks the smallest integer stored in any value.
+/// Contains None when no values contain an integer.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MinIndex(pub Option<i64>);
+
+impl EmbeddedIndex for MinIndex {
+    type Reduced = Self;
+    type Indexer = IntegerIndexer;
+}
+
+impl Indexer<MinIndex> for IntegerIndexer {
+    fn index(&self, _key: &ArcBytes<'_>, value: Option<&ArcBytes<'static>>) -> MinIndex {
+        MinIndex(self.read_value(value))
+    }
+}
+
+impl Reducer<MinIndex> for IntegerIndexer {
+    fn reduce<'a, Indexes, IndexesIter>(&self, indexes: Indexes) -> MinIndex
+    where
+        Indexes: IntoIterator<Item = &'a MinIndex, IntoIter = IndexesIter> + ExactSizeIterator,
+        IndexesIter: Iterator<Item = &'a MinIndex> + ExactSizeIterator + Clone,
+    {
+        MinIndex(indexes.into_iter().filter_map(|index| index.0).min())
+    }
+
+    fn rereduce<'a, ReducedIndexes, ReducedIndexesIter>(&self, values: ReducedIndexes) -> MinIndex
+    where
+        Self: 'a,
+        ReducedIndexes:
+            IntoIterator<Item = &'a MinIndex, IntoIter = ReducedIndexesIter> + ExactSizeIterator,
+        ReducedIndexesIter: Iterator<Item = &'a MinIndex> + ExactSizeIterator + Clone,
+    {
+        MinIndex(values.into_iter().filter_map(|index| index.0).min())
+    }
+}
+
+impl Serializable for MinIndex {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        serialize_optional_integer(self.0, writer)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        deserialize_optional_integer(reader).map(Self)
+    }
+}
+
+/// An [`EmbeddedIndex`] that tracks the largest integer stored in any value.
+/// Contains None when no values contain an integer.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MaxIndex(pub Option<i64>);
+
+impl EmbeddedIndex for MaxIndex {
+    type Reduced = Self;
+    type Indexer = IntegerIndexer;
+}
+
+impl Indexer<MaxIndex> for IntegerIndexer {
+    fn index(&self, _key: &ArcBytes<'_>, value: Option<&ArcBytes<'static>>) -> MaxIndex {
+        MaxIndex(self.read_value(value))
+    }
+}
+
+impl Reducer<MaxIndex> for IntegerIndexer {
+    fn reduce<'a, Indexes, IndexesIter>(&self, indexes: Indexes) -> MaxIndex
+    where
+        Indexes: IntoIterator<Item = &'a MaxIndex, IntoIter = IndexesIter> + ExactSizeIterator,
+        IndexesIter: Iterator<Item = &'a MaxIndex> + ExactSizeIterator + Clone,
+    {
+        MaxIndex(indexes.into_iter().filter_map(|index| index.0).max())
+    }
+
+    fn rereduce<'a, ReducedIndexes, ReducedIndexesIter>(&self, values: ReducedIndexes) -> MaxIndex
+    where
+        Self: 'a,
+        ReducedIndexes:
+            IntoIterator<Item = &'a MaxIndex, IntoIter = ReducedIndexesIter> + ExactSizeIterator,
+        ReducedIndexesIter: Iterator<Item = &'a MaxIndex> + ExactSizeIterator + Clone,
+    {
+        MaxIndex(values.into_iter().filter_map(|index| index.0).max())
+    }
+}
+
+impl Serializable for MaxIndex {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        serialize_optional_integer(self.0, writer)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        deserialize_optional_integer(reader).map(Self)
+    }
+}
+
+/// An [`EmbeddedIndex`] that counts the values containing an integer. Unlike
+/// [`ByIdStats::alive_keys`](super::ByIdStats::alive_keys), values too short
+/// to contain the integer aren't counted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CountIndex(pub u64);
+
+impl EmbeddedIndex for CountIndex {
+    type Reduced = Self;
+    type Indexer = IntegerIndexer;
+}
+
+impl Indexer<CountIndex> for IntegerIndexer {
+    fn index(&self, _key: &ArcBytes<'_>, value: Option<&ArcBytes<'static>>) -> CountIndex {
+        CountIndex(u64::from(self.read_value(value).is_some()))
+    }
+}
+
+impl Reducer<CountIndex> for IntegerIndexer {
+    fn reduce<'a, Indexes, IndexesIter>(&self, indexes: Indexes) -> CountIndex
+    where
+        Indexes: IntoIterator<Item = &'a CountIndex, IntoIter = IndexesIter> + ExactSizeIterator,
+        IndexesIter: Iterator<Item = &'a CountIndex> + ExactSizeIterator + Clone,
+    {
+        CountIndex(indexes.into_iter().map(|index| index.0).sum())
+    }
+
+    fn rereduce<'a, ReducedIndexes, ReducedIndexesIter>(&self, values: ReducedIndexes) -> CountIndex
+    where
+        Self: 'a,
+        ReducedIndexes:
+            IntoIterator<Item = &'a CountIndex, IntoIter = ReducedIndexesIter> + ExactSizeIterator,
+        ReducedIndexesIter: Iterator<Item = &'a CountIndex> + ExactSizeIterator + Clone,
+    {
+        CountIndex(values.into_iter().map(|index| index.0).sum())
+    }
+}
+
+impl Serializable for CountIndex {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        writer.write_u64::<BigEndian>(self.0)?;
+        Ok(8)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        Ok(Self(reader.read_u64::<BigEndian>()?))
+    }
+}
+
+/// Sorts values into buckets for a [`HistogramIndex`]. Each value is measured
+/// by its length or by an integer read using an [`IntegerIndexer`].
+///
+/// Buckets are separated by a list of boundaries in ascending order. A value
+/// whose measurement is less than the first boundary is counted in the first
+/// bucket. A value whose measurement is greater than or equal to boundary `n`,
+/// and less than boundary `n + 1`, is counted in bucket `n + 1`. The number of
+/// buckets is one more than the number of boundaries.
+///
+/// Every interior node of a tree stores a [`Histogram`] for each of its
+/// children, which requires 8 bytes per bucket. Histograms with many buckets
+/// will make a tree's nodes larger.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HistogramIndexer {
+    measurement: Measurement,
+    boundaries: Arc<[i64]>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Measurement {
+    ValueLength,
+    Integer(IntegerIndexer),
+}
+
+impl HistogramIndexer {
+    /// Returns an indexer that sorts values into buckets by their length in
+    /// bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `boundaries` isn't in strictly ascending order.
+    #[must_use]
+    pub fn value_lengths(boundaries: &[i64]) -> Self {
+        Self::new(Measurement::ValueLength, boundaries)
+    }
+
+    /// Returns an indexer that sorts values into buckets by the integer read
+    /// from each value by `integer`. Values that are too short to contain the
+    /// integer aren't counted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `boundaries` isn't in strictly ascending order.
+    #[must_use]
+    pub fn integers(integer: IntegerIndexer, boundaries: &[i64]) -> Self {
+        Self::new(Measurement::Integer(integer), boundaries)
+    }
+
+    fn new(measurement: Measurement, boundaries: &[i64]) -> Self {
+        assert!(
+            boundaries.windows(2).all(|pair| pair[0] < pair[1]),
+            "histogram boundaries must be in strictly ascending order"
+        );
+        assert!(
+            u32::try_from(boundaries.len()).is_ok(),
+            "too many histogram boundaries"
+        );
+        Self {
+            measurement,
+            boundaries: Arc::from(boundaries),
+        }
+    }
+
+    /// Returns the boundaries between each bucket.
+    #[must_use]
+    pub fn boundaries(&self) -> &[i64] {
+        &self.boundaries
+    }
+
+    /// Returns the number of buckets in each [`Histogram`].
+    #[must_use]
+    pub fn bucket_count(&self) -> usize {
+        self.boundaries.len() + 1
+    }
+
+    /// Returns the bucket that `value` is counted in. Returns None if `value`
+    /// can't be measured.
+    #[must_use]
+    pub fn bucket(&self, value: &[u8]) -> Option<u32> {
+        let measurement = match self.measurement {
+            Measurement::ValueLength => i64::try_from(value.len()).unwrap_or(i64::MAX),
+            Measurement::Integer(integer) => integer.read(value)?,
+        };
+        let bucket = self
+            .boundaries
+            .partition_point(|boundary| *boundary <= measurement);
+        // The number of boundaries is checked when the indexer is created.
+        u32::try_from(bucket).ok()
+    }
+}
+
+/// An [`EmbeddedIndex`] that records which bucket of a [`HistogramIndexer`]
+/// a value is counted in. Contains None when the value was removed or
+/// couldn't be measured. The reduced index is a [`Histogram`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HistogramIndex(pub Option<u32>);
+
+/// The number of values counted in each bucket of a [`HistogramIndexer`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Histogram(pub Vec<u64>);
+
+impl Histogram {
+    /// Returns the total number of values counted in all buckets.
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    fn add(&mut self, other: &Self) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (count, other) in self.0.iter_mut().zip(&other.0) {
+            *count += other;
+        }
+    }
+}
+
+impl EmbeddedIndex for HistogramIndex {
+    type Reduced = Histogram;
+    type Indexer = HistogramIndexer;
+}
+
+impl Indexer<HistogramIndex> for HistogramIndexer {
+    fn index(&self, _key: &ArcBytes<'_>, value: Option<&ArcBytes<'static>>) -> HistogramIndex {
+        HistogramIndex(value.and_then(|value| self.bucket(value)))
+    }
+}
+
+impl Reducer<HistogramIndex, Histogram> for HistogramIndexer {
+    fn reduce<'a, Indexes, IndexesIter>(&self, indexes: Indexes) -> Histogram
+    where
+        Indexes:
+            IntoIterator<Item = &'a HistogramIndex, IntoIter = IndexesIter> + ExactSizeIterator,
+        IndexesIter: Iterator<Item = &'a HistogramIndex> + ExactSizeIterator + Clone,
+    {
+        let mut counts = vec![0; self.bucket_count()];
+        for bucket in indexes.into_iter().filter_map(|index| index.0) {
+            let bucket = bucket as usize;
+            if bucket >= counts.len() {
+                counts.resize(bucket + 1, 0);
+            }
+            counts[bucket] += 1;
+        }
+        Histogram(counts)
+    }
+
+    fn rereduce<'a, ReducedIndexes, ReducedIndexesIter>(&self, values: ReducedIndexes) -> Histogram
+    where
+        Self: 'a,
+        ReducedIndexes:
+            IntoIterator<Item = &'a Histogram, IntoIter = ReducedIndexesIter> + ExactSizeIterator,
+        ReducedIndexesIter: Iterator<Item = &'a Histogram> + ExactSizeIterator + Clone,
+    {
+        let mut histogram = Histogram(vec![0; self.bucket_count()]);
+        for value in values {
+            histogram.add(value);
+        }
+        histogram
+    }
+}
+
+impl Serializable for HistogramIndex {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        match self.0 {
+            Some(bucket) => {
+                writer.write_u8(1)?;
+                writer.write_u32::<BigEndian>(bucket)?;
+                Ok(5)
+            }
+            None => {
+                writer.write_u8(0)?;
+                Ok(1)
+            }
+        }
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        match reader.read_u8()? {
+            0 => Ok(Self(None)),
+            1 => Ok(Self(Some(reader.read_u32::<BigEndian>()?))),
+            other => Err(Error::data_integrity(format!(
+                "invalid histogram bucket tag {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl Serializable for Histogram {
+    fn serialize_to<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, Error> {
+        let bucket_count =
+            u32::try_from(self.0.len()).map_err(|_| Error::from("too many histogram buckets"))?;
+        writer.write_u32::<BigEndian>(bucket_count)?;
+        for count in &self.0 {
+            writer.write_u64::<BigEndian>(*count)?;
+        }
+        Ok(4 + self.0.len() * 8)
+    }
+
+    fn deserialize_from<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        let bucket_count = reader.read_u32::<BigEndian>()?;
+        let mut counts = Vec::new();
+        for _ in 0..bucket_count {
+            counts.push(reader.read_u64::<BigEndian>()?);
+        }
+        Ok(Self(counts))
+    }
+}
+
+fn serialize_optional_integer<W: WriteBytesExt>(
+    value: Option<i64>,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    match value {
+        Some(value) => {
+            writer.write_u8(1)?;
+            writer.write_i64::<BigEndian>(value)?;
+            Ok(9)
+        }
+        None => {
+            writer.write_u8(0)?;
+            Ok(1)
+        }
+    }
+}
+
+fn deserialize_optional_integer<R: ReadBytesExt>(reader: &mut R) -> Result<Option<i64>, Error> {
+    match reader.read_u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(reader.read_i64::<BigEndian>()?)),
+        other => Err(Error::data_integrity(format!(
+            "invalid optional integer tag {}",
+            other
+        ))),
+    }
+}
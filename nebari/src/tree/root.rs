@@ -15,8 +15,9 @@ use crate::{
     roots::AnyTransactionTree,
     transaction::{TransactionId, TransactionManager},
     tree::{
-        btree_entry::ScanArgs, state::AnyTreeState, Modification, ModificationResult, PageHeader,
-        PagedWriter, Reducer, ScanEvaluation, State, TreeFile,
+        btree_entry::ScanArgs, state::AnyTreeState, ChunkVerifier, Modification,
+        ModificationResult, NodeInfo, PageHeader, PagedWriter, Reducer, ScanEvaluation, State,
+        TreeFile, ValueIndex,
     },
     vault::AnyVault,
     AbortError, ArcBytes, ChunkCache, Context, TransactionTree, Vault,
@@ -28,7 +29,7 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
     const HEADER: PageHeader;
 
     /// The primary index type contained within this root.
-    type Index: Clone + Debug + 'static;
+    type Index: ValueIndex + Clone + Debug + 'static;
     /// The primary index type contained within this root.
     type ReducedIndex: Clone + Debug + 'static;
     /// The reducer that reduces `Index`es and re-reduces `ReducedIndex`es.
@@ -44,6 +45,10 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
     /// deleted records.
     fn count(&self) -> u64;
 
+    /// Returns the number of bytes stored on disk for the values contained in
+    /// this tree, not including deleted records.
+    fn live_bytes(&self) -> u64;
+
     /// Returns a reference to a named tree that contains this type of root.
     fn tree<File: ManagedFile>(name: impl Into<Cow<'static, str>>) -> TreeRoot<Self, File>
     where
@@ -53,6 +58,7 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
             name: name.into(),
             vault: None,
             reducer: Arc::new(<Self::Reducer as Default>::default()),
+            max_order: None,
             _phantom: PhantomData,
         }
     }
@@ -66,6 +72,7 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
             name: name.into(),
             vault: None,
             reducer: Arc::new(reducer),
+            max_order: None,
             _phantom: PhantomData,
         }
     }
@@ -94,6 +101,32 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
     /// Returns the current transaction id.
     fn transaction_id(&self) -> TransactionId;
 
+    /// Returns the user-defined metadata stored alongside this root, if set.
+    fn metadata(&self) -> Option<&ArcBytes<'static>>;
+
+    /// Replaces the user-defined metadata stored alongside this root. If
+    /// `transaction_id` is provided, the root's transaction id is updated.
+    fn set_metadata(
+        &mut self,
+        metadata: Option<ArcBytes<'static>>,
+        transaction_id: Option<TransactionId>,
+    );
+
+    /// Removes every key from the tree, keeping the user-defined metadata. If
+    /// `transaction_id` is provided, the root's transaction id is updated.
+    fn clear(&mut self, transaction_id: Option<TransactionId>);
+
+    /// Replaces every key in the tree with `entries`, which must be sorted in
+    /// strictly ascending order by key. The nodes of the tree are built from
+    /// the bottom up, and each contains at most `order` entries. Every node
+    /// other than the root is written to `writer` as soon as it is filled.
+    fn bulk_load(
+        &mut self,
+        entries: &mut dyn Iterator<Item = (ArcBytes<'static>, ArcBytes<'static>)>,
+        writer: &mut PagedWriter<'_>,
+        order: usize,
+    ) -> Result<(), Error>;
+
     /// Modifies the tree. Returns a list of modified keys and their updated
     /// indexes, if the keys are still present.
     fn modify<'a, 'w>(
@@ -168,6 +201,21 @@ pub trait Root: Debug + Send + Sync + Clone + 'static {
         writer: &mut PagedWriter<'_>,
         vault: Option<&dyn AnyVault>,
     ) -> Result<(), Error>;
+
+    /// Verifies every node and value chunk reachable from this root using
+    /// `verifier`.
+    fn verify(&self, file: &mut dyn File, verifier: &mut ChunkVerifier<'_>) -> Result<(), Error>;
+
+    /// Invokes `visitor` with each node of this root's primary index that is
+    /// reached, starting with the root node. The children of an interior node
+    /// are only visited if `visitor` returns [`ScanEvaluation::ReadData`].
+    fn walk_nodes(
+        &self,
+        visitor: &mut dyn FnMut(NodeInfo<'_, Self::Index, Self::ReducedIndex>) -> ScanEvaluation,
+        file: &mut dyn File,
+        vault: Option<&dyn AnyVault>,
+        cache: Option<&ChunkCache>,
+    ) -> Result<(), Error>;
 }
 
 /// A named tree with a specific root type.
@@ -180,6 +228,9 @@ pub struct TreeRoot<R: Root, File: ManagedFile> {
     pub vault: Option<Arc<dyn AnyVault>>,
     /// The [`Reducer`] for this tree.
     pub(crate) reducer: Arc<dyn AnyReducer>,
+    /// The maximum number of children or entries stored in each node. If not
+    /// set, [`DEFAULT_MAX_ORDER`](super::DEFAULT_MAX_ORDER) is used.
+    pub(crate) max_order: Option<usize>,
     _phantom: PhantomData<(R, File)>,
 }
 
@@ -202,6 +253,28 @@ impl<R: Root, File: ManagedFile> TreeRoot<R, File> {
         self.vault = Some(Arc::new(vault));
         self
     }
+
+    /// Sets the maximum number of children or entries stored in each of the
+    /// tree's nodes. The order used for a node grows with the number of
+    /// records stored in the tree, up to `max_order`. Larger orders produce
+    /// shallower trees with larger nodes, which can be beneficial when values
+    /// are small.
+    ///
+    /// Changing the maximum order of an existing tree is safe. The order only
+    /// determines when nodes are split or merged as the tree is modified, and
+    /// nodes written using a different order are read normally. Because of
+    /// this, the order isn't stored in the tree's file, and it must be set
+    /// each time the tree is opened. If not set,
+    /// [`DEFAULT_MAX_ORDER`](super::DEFAULT_MAX_ORDER) is used.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `max_order` is less than 4.
+    pub fn with_max_order(mut self, max_order: usize) -> Self {
+        assert!(max_order >= 4, "max_order must be at least 4");
+        self.max_order = Some(max_order);
+        self
+    }
 }
 
 impl<R: Root, File: ManagedFile> Clone for TreeRoot<R, File> {
@@ -210,6 +283,7 @@ impl<R: Root, File: ManagedFile> Clone for TreeRoot<R, File> {
             name: self.name.clone(),
             vault: self.vault.clone(),
             reducer: self.reducer.clone(),
+            max_order: self.max_order,
             _phantom: PhantomData,
         }
     }
@@ -230,6 +304,17 @@ pub trait AnyTreeRoot<File: ManagedFile> {
         context: &Context<File::Manager>,
         transactions: Option<&TransactionManager<File::Manager>>,
     ) -> Result<Box<dyn AnyTransactionTree<File>>, Error>;
+    /// Loads the tree's state from `file_path` if it hasn't been loaded yet.
+    fn initialize_state(
+        &self,
+        file_path: &Path,
+        state: &dyn AnyTreeState,
+        context: &Context<File::Manager>,
+        transactions: Option<&TransactionManager<File::Manager>>,
+    ) -> Result<(), Error>;
+    /// The vault used to encrypt this tree, if it differs from the database's
+    /// vault.
+    fn vault(&self) -> Option<&Arc<dyn AnyVault>>;
 }
 
 impl<R: Root, File: ManagedFile> AnyTreeRoot<File> for TreeRoot<R, File> {
@@ -240,7 +325,7 @@ impl<R: Root, File: ManagedFile> AnyTreeRoot<File> for TreeRoot<R, File> {
     fn default_state(&self) -> Box<dyn AnyTreeState> {
         Box::new(State::<R>::new(
             None,
-            None,
+            self.max_order,
             R::default_with(
                 self.reducer
                     .as_ref()
@@ -276,4 +361,28 @@ impl<R: Root, File: ManagedFile> AnyTreeRoot<File> for TreeRoot<R, File> {
             tree,
         }))
     }
+
+    fn initialize_state(
+        &self,
+        file_path: &Path,
+        state: &dyn AnyTreeState,
+        context: &Context<File::Manager>,
+        transactions: Option<&TransactionManager<File::Manager>>,
+    ) -> Result<(), Error> {
+        let context = self.vault.as_ref().map_or_else(
+            || Cow::Borrowed(context),
+            |vault| Cow::Owned(context.clone().with_any_vault(vault.clone())),
+        );
+        TreeFile::<R, File>::read(
+            file_path,
+            state.as_any().downcast_ref::<State<R>>().unwrap().clone(),
+            &context,
+            transactions,
+        )?;
+        Ok(())
+    }
+
+    fn vault(&self) -> Option<&Arc<dyn AnyVault>> {
+        self.vault.as_ref()
+    }
 }
@@ -29,3 +29,41 @@ impl BinarySerialization for () {
         Ok(())
     }
 }
+
+/// The version written as the first byte of indexes encoded using
+/// [`encode_index()`]. Incremented when the format of an index changes.
+const INDEX_FORMAT_VERSION: u8 = 0;
+
+/// Encodes an index into a standalone buffer prefixed with
+/// [`INDEX_FORMAT_VERSION`]. `write` serializes the index into the buffer.
+pub(crate) fn encode_index<F: FnOnce(&mut Vec<u8>) -> Result<usize, Error>>(
+    write: F,
+) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![INDEX_FORMAT_VERSION];
+    write(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Decodes an index encoded using [`encode_index()`].
+pub(crate) fn decode_index<Index: BinarySerialization>(bytes: &[u8]) -> Result<Index, Error> {
+    match bytes.split_first() {
+        Some((&INDEX_FORMAT_VERSION, contents)) => {
+            let mut reader = ArcBytes::from(contents);
+            let index =
+                Index::deserialize_from(&mut reader, None).map_err(Error::data_integrity)?;
+            if reader.is_empty() {
+                Ok(index)
+            } else {
+                Err(Error::data_integrity(format!(
+                    "{} unexpected bytes after index",
+                    reader.len()
+                )))
+            }
+        }
+        Some((version, _)) => Err(Error::data_integrity(format!(
+            "unsupported index format version {}",
+            version
+        ))),
+        None => Err(Error::data_integrity("index is empty")),
+    }
+}
@@ -1,4 +1,10 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use parking_lot::{Mutex, MutexGuard, RwLock};
 
@@ -11,6 +17,8 @@ use crate::chunk_cache::AnySendSync;
 pub struct State<Root: super::Root> {
     reader: Arc<RwLock<Arc<ActiveState<Root>>>>,
     writer: Arc<Mutex<ActiveState<Root>>>,
+    compaction: Arc<Mutex<()>>,
+    vault_generation: Arc<AtomicU64>,
 }
 
 impl<Root> State<Root>
@@ -29,6 +37,8 @@ where
         Self {
             reader: Arc::new(RwLock::new(Arc::new(state.clone()))),
             writer: Arc::new(Mutex::new(state)),
+            compaction: Arc::default(),
+            vault_generation: Arc::default(),
         }
     }
     /// Returns an initialized state. This should only be used if you're
@@ -45,6 +55,8 @@ where
         Self {
             reader: Arc::new(RwLock::new(Arc::new(state.clone()))),
             writer: Arc::new(Mutex::new(state)),
+            compaction: Arc::default(),
+            vault_generation: Arc::default(),
         }
     }
 
@@ -53,12 +65,83 @@ where
         self.writer.lock()
     }
 
+    /// Locks the state for compaction. Only one compaction may be performed on
+    /// a tree at a time.
+    pub(crate) fn lock_compaction(&self) -> MutexGuard<'_, ()> {
+        self.compaction.lock()
+    }
+
+    /// Locks the state for compaction without blocking. Returns None if a
+    /// compaction is in progress.
+    pub(crate) fn try_lock_compaction(&self) -> Option<MutexGuard<'_, ()>> {
+        self.compaction.try_lock()
+    }
+
+    /// Waits for an in-progress compaction to finish. Returns false without
+    /// waiting if no compaction is in progress.
+    pub(crate) fn wait_for_compaction(&self) -> bool {
+        if self.compaction.try_lock().is_some() {
+            false
+        } else {
+            drop(self.compaction.lock());
+            true
+        }
+    }
+
+    /// Returns the number of times the tree's vault has been rotated.
+    pub(crate) fn vault_generation(&self) -> u64 {
+        self.vault_generation.load(Ordering::Acquire)
+    }
+
+    /// Records that the tree's vault has been rotated. Handles that were
+    /// opened with the previous vault must no longer access the tree.
+    pub(crate) fn rotated_vault(&self) {
+        self.vault_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Exchanges the contents of this state with `other`, publishing both.
+    pub(crate) fn swap(&self, other: &Self) {
+        let mut state = self.lock();
+        let mut other_state = other.lock();
+        std::mem::swap(&mut *state, &mut *other_state);
+        state.publish(self);
+        other_state.publish(other);
+    }
+
     /// Reads the current state.
     #[must_use]
     pub fn read(&self) -> Arc<ActiveState<Root>> {
         let reader = self.reader.read();
         reader.clone()
     }
+
+    /// Replaces the maximum order used when modifying the tree. The change is
+    /// published along with the next change to the tree.
+    pub(crate) fn set_max_order(&self, max_order: Option<usize>) {
+        self.lock().max_order = max_order;
+    }
+
+    /// Returns a new state containing a copy of the most recently published
+    /// state. Changes published to `self` aren't reflected in the copy.
+    pub(crate) fn detached(&self) -> Self {
+        Self::from_active(ActiveState::clone(&self.read()))
+    }
+
+    /// Returns a new state whose published state is a copy of the current
+    /// state, including changes that haven't been published yet. Changes
+    /// made to `self` aren't reflected in the copy.
+    pub(crate) fn detached_uncommitted(&self) -> Self {
+        Self::from_active(self.lock().clone())
+    }
+
+    fn from_active(state: ActiveState<Root>) -> Self {
+        Self {
+            reader: Arc::new(RwLock::new(Arc::new(state.clone()))),
+            writer: Arc::new(Mutex::new(state)),
+            compaction: Arc::default(),
+            vault_generation: Arc::default(),
+        }
+    }
 }
 
 impl<Root> Default for State<Root>
@@ -73,6 +156,12 @@ where
 pub trait AnyTreeState: AnySendSync + Debug {
     fn cloned(&self) -> Box<dyn AnyTreeState>;
     fn publish(&self);
+    /// Returns a new state containing a copy of the most recently published
+    /// state. Changes published to `self` aren't reflected in the copy.
+    fn snapshot(&self) -> Box<dyn AnyTreeState>;
+    /// Returns the file id and current position of the most recently
+    /// published state.
+    fn published_position(&self) -> (Option<u64>, u64);
 }
 
 impl<Root: super::Root> AnyTreeState for State<Root> {
@@ -80,10 +169,19 @@ impl<Root: super::Root> AnyTreeState for State<Root> {
         Box::new(self.clone())
     }
 
+    fn snapshot(&self) -> Box<dyn AnyTreeState> {
+        Box::new(self.detached())
+    }
+
     fn publish(&self) {
         let state = self.lock();
         state.publish(self);
     }
+
+    fn published_position(&self) -> (Option<u64>, u64) {
+        let state = self.read();
+        (state.file_id, state.current_position)
+    }
 }
 
 /// An active state for a tree file.
@@ -0,0 +1,55 @@
+/// The structure of a tree, as reported by
+/// [`TreeFile::statistics()`](super::TreeFile::statistics).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStatistics {
+    /// The statistics of each level of the tree. The first entry is the level
+    /// containing the root node, and the last entry is the level containing
+    /// the leaf nodes.
+    pub levels: Vec<LevelStatistics>,
+    /// The number of interior nodes in the tree.
+    pub interior_nodes: u64,
+    /// The number of leaf nodes in the tree.
+    pub leaf_nodes: u64,
+    /// The number of keys that have a stored value. Deleted keys that are
+    /// still tracked by the tree are not included.
+    pub live_keys: u64,
+    /// The order the tree is currently using, which is the maximum number of
+    /// entries each node can hold.
+    pub order: usize,
+}
+
+impl TreeStatistics {
+    /// Returns the number of levels in the tree.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+}
+
+/// The nodes at one level of a tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelStatistics {
+    /// The number of nodes at this level.
+    pub nodes: u64,
+    /// The number of entries stored across all nodes at this level.
+    pub entries: u64,
+    /// The average number of entries in each node divided by the tree's
+    /// order. A value of 1.0 indicates every node at this level is full.
+    pub fill_factor: f64,
+}
+
+impl LevelStatistics {
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new(nodes: u64, entries: u64, order: usize) -> Self {
+        let capacity = nodes as f64 * order as f64;
+        Self {
+            nodes,
+            entries,
+            fill_factor: if capacity > 0. {
+                entries as f64 / capacity
+            } else {
+                0.
+            },
+        }
+    }
+}
@@ -12,7 +12,7 @@ use super::{
     by_id::{ByIdStats, UnversionedByIdIndex},
     modify::Modification,
     serialization::BinarySerialization,
-    PagedWriter, ScanEvaluation,
+    ChunkVerifier, NodeInfo, PagedWriter, ScanEvaluation,
 };
 use crate::{
     chunk_cache::CacheEntry,
@@ -21,9 +21,13 @@ use crate::{
     roots::AbortError,
     transaction::TransactionId,
     tree::{
-        btree_entry::{Indexer, KeyOperation, ModificationContext, NodeInclusion, ScanArgs},
+        btree_entry::{
+            Indexer, KeyOperation, ModificationContext, NodeInclusion, ScanArgs, SortedTreeBuilder,
+        },
         by_id::ByIdIndexer,
-        copy_chunk, dynamic_order,
+        copy_chunk, deserialize_root_metadata, dynamic_order,
+        key_entry::KeyEntry,
+        saturating_value_length, serialize_root_metadata,
         versioned::ChangeResult,
         BTreeNode, ModificationResult, PageHeader, Root,
     },
@@ -49,6 +53,8 @@ where
     pub by_id_root: BTreeEntry<UnversionedByIdIndex<Index>, ByIdStats<Index::Reduced>>,
 
     reducer: <Self as Root>::Reducer,
+    metadata: Option<ArcBytes<'static>>,
+    metadata_dirty: bool,
 }
 
 impl<Index> Default for UnversionedTreeRoot<Index>
@@ -61,6 +67,8 @@ where
             transaction_id: None,
             by_id_root: BTreeEntry::default(),
             reducer: <<Self as Root>::Reducer as Default>::default(),
+            metadata: None,
+            metadata_dirty: false,
         }
     }
 }
@@ -101,9 +109,7 @@ where
                               writer: &mut PagedWriter<'_>| {
                         if let Some(value) = value {
                             let position = writer.write_chunk(value)?;
-                            // write_chunk errors if it can't fit within a u32
-                            #[allow(clippy::cast_possible_truncation)]
-                            let value_length = value.len() as u32;
+                            let value_length = saturating_value_length(value);
                             let new_index = UnversionedByIdIndex {
                                 value_length,
                                 position,
@@ -162,6 +168,8 @@ where
             transaction_id: None,
             by_id_root: BTreeEntry::default(),
             reducer,
+            metadata: None,
+            metadata_dirty: false,
         }
     }
 
@@ -173,12 +181,16 @@ where
         self.by_id_root.stats(self.reducer()).alive_keys
     }
 
+    fn live_bytes(&self) -> u64 {
+        self.by_id_root.stats(self.reducer()).total_indexed_bytes
+    }
+
     fn initialized(&self) -> bool {
         self.transaction_id.is_some()
     }
 
     fn dirty(&self) -> bool {
-        self.by_id_root.dirty
+        self.by_id_root.dirty || self.metadata_dirty
     }
 
     fn initialize_default(&mut self) {
@@ -188,7 +200,7 @@ where
     fn deserialize(mut bytes: ArcBytes<'_>, reducer: Self::Reducer) -> Result<Self, Error> {
         let transaction_id = Some(TransactionId(bytes.read_u64::<BigEndian>()?));
         let by_id_size = bytes.read_u32::<BigEndian>()? as usize;
-        if by_id_size != bytes.len() {
+        if by_id_size > bytes.len() {
             return Err(Error::data_integrity(format!(
                 "Header reported index size {}, but data has {} remaining",
                 by_id_size,
@@ -199,11 +211,14 @@ where
         let mut by_id_bytes = bytes.read_bytes(by_id_size)?.to_owned();
 
         let by_id_root = BTreeEntry::deserialize_from(&mut by_id_bytes, None)?;
+        let metadata = deserialize_root_metadata(bytes)?;
 
         Ok(Self {
             transaction_id,
             by_id_root,
             reducer,
+            metadata,
+            metadata_dirty: false,
         })
     }
 
@@ -226,6 +241,9 @@ where
             .ok_or(ErrorKind::Internal(InternalError::HeaderTooLarge))?;
         BigEndian::write_u32(&mut output[8..12], by_id_size);
 
+        serialize_root_metadata(self.metadata.as_ref(), output)?;
+        self.metadata_dirty = false;
+
         Ok(())
     }
 
@@ -233,6 +251,52 @@ where
         self.transaction_id.unwrap_or_default()
     }
 
+    fn metadata(&self) -> Option<&ArcBytes<'static>> {
+        self.metadata.as_ref()
+    }
+
+    fn set_metadata(
+        &mut self,
+        metadata: Option<ArcBytes<'static>>,
+        transaction_id: Option<TransactionId>,
+    ) {
+        self.metadata = metadata;
+        self.metadata_dirty = true;
+        if let Some(transaction_id) = transaction_id {
+            self.transaction_id = Some(transaction_id);
+        }
+    }
+
+    fn clear(&mut self, transaction_id: Option<TransactionId>) {
+        self.by_id_root = BTreeEntry {
+            dirty: true,
+            ..BTreeEntry::default()
+        };
+        if let Some(transaction_id) = transaction_id {
+            self.transaction_id = Some(transaction_id);
+        }
+    }
+
+    fn bulk_load(
+        &mut self,
+        entries: &mut dyn Iterator<Item = (ArcBytes<'static>, ArcBytes<'static>)>,
+        writer: &mut PagedWriter<'_>,
+        order: usize,
+    ) -> Result<(), Error> {
+        let mut by_id = SortedTreeBuilder::new(order, &self.reducer);
+        for (key, value) in entries {
+            let position = writer.write_chunk(&value)?;
+            let index = UnversionedByIdIndex {
+                value_length: saturating_value_length(&value),
+                position,
+                embedded: self.reducer.0.index(&key, Some(&value)),
+            };
+            by_id.push(KeyEntry { key, index }, writer)?;
+        }
+        self.by_id_root = by_id.finish(writer)?;
+        Ok(())
+    }
+
     fn modify(
         &mut self,
         modification: Modification<'_, ArcBytes<'static>, Self::Index>,
@@ -345,4 +409,20 @@ where
 
         Ok(())
     }
+
+    fn verify(&self, file: &mut dyn File, verifier: &mut ChunkVerifier<'_>) -> Result<(), Error> {
+        self.by_id_root.verify(file, verifier)
+    }
+
+    fn walk_nodes(
+        &self,
+        visitor: &mut dyn FnMut(NodeInfo<'_, Self::Index, Self::ReducedIndex>) -> ScanEvaluation,
+        file: &mut dyn File,
+        vault: Option<&dyn AnyVault>,
+        cache: Option<&ChunkCache>,
+    ) -> Result<(), Error> {
+        self.by_id_root
+            .walk(None, 0, visitor, file, vault, cache)
+            .map(|_| ())
+    }
 }
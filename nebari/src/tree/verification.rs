@@ -0,0 +1,207 @@
+use std::{collections::HashSet, io::SeekFrom};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use super::{CRC32, SEGMENTED_CHUNK_LENGTH};
+use crate::{error::Error, io::File, vault::AnyVault, ArcBytes};
+
+/// The results of [`TreeFile::verify()`](super::TreeFile::verify).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VerificationReport {
+    /// The number of node chunks that were read.
+    pub nodes_verified: u64,
+    /// The number of value chunks that were read.
+    pub values_verified: u64,
+    /// The chunks that failed verification.
+    pub failures: Vec<ChunkFailure>,
+}
+
+impl VerificationReport {
+    /// Returns true if no chunks failed verification.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A chunk that failed verification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkFailure {
+    /// The position of the chunk in the file.
+    pub position: u64,
+    /// The kind of data stored in the chunk.
+    pub kind: ChunkKind,
+    /// The reason the chunk failed verification.
+    pub error: ChunkError,
+}
+
+/// The kind of data stored in a chunk.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChunkKind {
+    /// A B-Tree node.
+    Node,
+    /// A stored value.
+    Value,
+}
+
+/// The reason a chunk failed verification.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChunkError {
+    /// The CRC computed from the chunk's contents doesn't match the CRC
+    /// stored in the chunk's header.
+    CrcMismatch {
+        /// The CRC stored in the chunk's header.
+        expected: u32,
+        /// The CRC computed from the chunk's contents.
+        computed: u32,
+    },
+    /// The chunk extends past the end of the file.
+    Truncated,
+}
+
+/// Verifies the chunks of a tree file, recording the results in a
+/// [`VerificationReport`]. Each chunk is verified at most once.
+#[derive(Debug)]
+pub struct ChunkVerifier<'a> {
+    vault: Option<&'a dyn AnyVault>,
+    file_length: u64,
+    verified: HashSet<u64>,
+    report: VerificationReport,
+}
+
+impl<'a> ChunkVerifier<'a> {
+    pub(crate) fn new(file_length: u64, vault: Option<&'a dyn AnyVault>) -> Self {
+        Self {
+            vault,
+            file_length,
+            verified: HashSet::new(),
+            report: VerificationReport::default(),
+        }
+    }
+
+    /// Verifies the node stored at `position`. Returns the node's decrypted
+    /// contents if it was verified successfully for the first time.
+    pub(crate) fn verify_node(
+        &mut self,
+        position: u64,
+        file: &mut dyn File,
+    ) -> Result<Option<ArcBytes<'static>>, Error> {
+        self.verify_chunk(position, ChunkKind::Node, file)
+    }
+
+    /// Verifies the value stored at `position`, unless `position` is 0,
+    /// which indicates no value is stored.
+    pub(crate) fn verify_value(&mut self, position: u64, file: &mut dyn File) -> Result<(), Error> {
+        if position > 0 {
+            self.verify_chunk(position, ChunkKind::Value, file)?;
+        }
+        Ok(())
+    }
+
+    fn verify_chunk(
+        &mut self,
+        position: u64,
+        kind: ChunkKind,
+        file: &mut dyn File,
+    ) -> Result<Option<ArcBytes<'static>>, Error> {
+        if !self.verified.insert(position) {
+            return Ok(None);
+        }
+        match kind {
+            ChunkKind::Node => self.report.nodes_verified += 1,
+            ChunkKind::Value => self.report.values_verified += 1,
+        }
+
+        if position.saturating_add(8) > self.file_length {
+            self.fail(position, kind, ChunkError::Truncated);
+            return Ok(None);
+        }
+        let mut header = [0_u8; 8];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut header)?;
+        let length = BigEndian::read_u32(&header[0..4]);
+        if length == SEGMENTED_CHUNK_LENGTH {
+            self.verify_segments(position, kind, BigEndian::read_u32(&header[4..8]), file)?;
+            return Ok(None);
+        }
+        let length = u64::from(length);
+        if position + 8 + length > self.file_length {
+            self.fail(position, kind, ChunkError::Truncated);
+            return Ok(None);
+        }
+
+        let mut contents = vec![0; usize::try_from(length).unwrap_or(usize::MAX)];
+        file.read_exact(&mut contents)?;
+        let expected = BigEndian::read_u32(&header[4..8]);
+        let computed = CRC32.checksum(&contents);
+        if expected != computed {
+            self.fail(
+                position,
+                kind,
+                ChunkError::CrcMismatch { expected, computed },
+            );
+            return Ok(None);
+        }
+
+        let decrypted = match self.vault {
+            Some(vault) => vault.decrypt(&contents)?,
+            None => contents,
+        };
+        Ok(Some(ArcBytes::from(decrypted)))
+    }
+
+    /// Verifies the list of segments stored in the segmented chunk at
+    /// `position` and each segment it lists. Each segment is counted as its
+    /// own chunk.
+    fn verify_segments(
+        &mut self,
+        position: u64,
+        kind: ChunkKind,
+        expected: u32,
+        file: &mut dyn File,
+    ) -> Result<(), Error> {
+        let table_start = position + 8;
+        if table_start + 12 > self.file_length {
+            self.fail(position, kind, ChunkError::Truncated);
+            return Ok(());
+        }
+        let mut table = vec![0_u8; 12];
+        file.read_exact(&mut table)?;
+        let segment_count = u64::from(BigEndian::read_u32(&table[8..12]));
+        if table_start + 12 + segment_count * 8 > self.file_length {
+            self.fail(position, kind, ChunkError::Truncated);
+            return Ok(());
+        }
+        table.resize(
+            12 + usize::try_from(segment_count * 8).unwrap_or(usize::MAX),
+            0,
+        );
+        file.read_exact(&mut table[12..])?;
+        let computed = CRC32.checksum(&table);
+        if expected != computed {
+            self.fail(
+                position,
+                kind,
+                ChunkError::CrcMismatch { expected, computed },
+            );
+            return Ok(());
+        }
+
+        for segment in table[12..].chunks(8).map(BigEndian::read_u64) {
+            self.verify_chunk(segment, kind, file)?;
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, position: u64, kind: ChunkKind, error: ChunkError) {
+        self.report.failures.push(ChunkFailure {
+            position,
+            kind,
+            error,
+        });
+    }
+
+    pub(crate) fn finish(self) -> VerificationReport {
+        self.report
+    }
+}
@@ -14,7 +14,7 @@ use super::{
     by_sequence::{BySequenceIndex, BySequenceStats},
     modify::Modification,
     serialization::BinarySerialization,
-    PagedWriter, ScanEvaluation, PAGE_SIZE,
+    ChunkVerifier, NodeInfo, PagedWriter, ScanEvaluation, PAGE_SIZE,
 };
 use crate::{
     chunk_cache::CacheEntry,
@@ -23,13 +23,16 @@ use crate::{
     roots::AbortError,
     transaction::TransactionId,
     tree::{
-        btree_entry::{Indexer, KeyOperation, ModificationContext, NodeInclusion, ScanArgs},
+        btree_entry::{
+            Indexer, KeyOperation, ModificationContext, NodeInclusion, ScanArgs, SortedTreeBuilder,
+        },
         by_id::ByIdIndexer,
         by_sequence::{BySequenceReducer, SequenceId},
-        copy_chunk, dynamic_order,
+        copy_chunk, deserialize_root_metadata, dynamic_order,
         key_entry::KeyEntry,
         modify::Operation,
-        BTreeNode, Interior, ModificationResult, PageHeader, PersistenceMode, Reducer, Root,
+        saturating_value_length, serialize_root_metadata, BTreeNode, Interior, ModificationResult,
+        PageHeader, PersistenceMode, Reducer, Root,
     },
     vault::AnyVault,
     ArcBytes, ChunkCache, ErrorKind,
@@ -58,6 +61,8 @@ where
         BTreeEntry<VersionedByIdIndex<EmbeddedIndex>, ByIdStats<EmbeddedIndex::Reduced>>,
 
     reducer: ByIdIndexer<EmbeddedIndex::Indexer>,
+    metadata: Option<ArcBytes<'static>>,
+    metadata_dirty: bool,
 }
 impl<EmbeddedIndex> Default for VersionedTreeRoot<EmbeddedIndex>
 where
@@ -71,6 +76,8 @@ where
             by_sequence_root: BTreeEntry::default(),
             by_id_root: BTreeEntry::default(),
             reducer: ByIdIndexer(<EmbeddedIndex::Indexer as Default>::default()),
+            metadata: None,
+            metadata_dirty: false,
         }
     }
 }
@@ -187,9 +194,7 @@ where
                                    writer: &mut PagedWriter<'_>| {
                         let (position, value_size) = if let Some(value) = value {
                             let new_position = writer.write_chunk(value)?;
-                            // write_chunk errors if it can't fit within a u32
-                            #[allow(clippy::cast_possible_truncation)]
-                            let value_length = value.len() as u32;
+                            let value_length = saturating_value_length(value);
                             (new_position, value_length)
                         } else {
                             (0, 0)
@@ -273,6 +278,8 @@ where
             by_sequence_root: BTreeEntry::default(),
             by_id_root: BTreeEntry::default(),
             reducer,
+            metadata: None,
+            metadata_dirty: false,
         }
     }
 
@@ -285,7 +292,7 @@ where
     }
 
     fn dirty(&self) -> bool {
-        self.by_id_root.dirty || self.by_sequence_root.dirty
+        self.by_id_root.dirty || self.by_sequence_root.dirty || self.metadata_dirty
     }
 
     fn initialize_default(&mut self) {
@@ -296,12 +303,16 @@ where
         self.by_id_root.stats(self.reducer()).alive_keys
     }
 
+    fn live_bytes(&self) -> u64 {
+        self.by_id_root.stats(self.reducer()).total_indexed_bytes
+    }
+
     fn deserialize(mut bytes: ArcBytes<'_>, reducer: Self::Reducer) -> Result<Self, Error> {
         let transaction_id = TransactionId(bytes.read_u64::<BigEndian>()?);
         let sequence = SequenceId(bytes.read_u64::<BigEndian>()?);
         let by_sequence_size = bytes.read_u32::<BigEndian>()? as usize;
         let by_id_size = bytes.read_u32::<BigEndian>()? as usize;
-        if by_sequence_size + by_id_size != bytes.len() {
+        if by_sequence_size + by_id_size > bytes.len() {
             return Err(Error::data_integrity(format!(
                 "Header reported index sizes {} and {}, but data has {} remaining",
                 by_sequence_size,
@@ -315,6 +326,7 @@ where
 
         let by_sequence_root = BTreeEntry::deserialize_from(&mut by_sequence_bytes, None)?;
         let by_id_root = BTreeEntry::deserialize_from(&mut by_id_bytes, None)?;
+        let metadata = deserialize_root_metadata(bytes)?;
 
         Ok(Self {
             transaction_id,
@@ -322,6 +334,8 @@ where
             by_sequence_root,
             by_id_root,
             reducer,
+            metadata,
+            metadata_dirty: false,
         })
     }
 
@@ -349,6 +363,9 @@ where
             .ok_or(ErrorKind::Internal(InternalError::HeaderTooLarge))?;
         BigEndian::write_u32(&mut output[20..24], by_id_size);
 
+        serialize_root_metadata(self.metadata.as_ref(), output)?;
+        self.metadata_dirty = false;
+
         Ok(())
     }
 
@@ -356,6 +373,83 @@ where
         self.transaction_id
     }
 
+    fn metadata(&self) -> Option<&ArcBytes<'static>> {
+        self.metadata.as_ref()
+    }
+
+    fn set_metadata(
+        &mut self,
+        metadata: Option<ArcBytes<'static>>,
+        transaction_id: Option<TransactionId>,
+    ) {
+        self.metadata = metadata;
+        self.metadata_dirty = true;
+        if let Some(transaction_id) = transaction_id {
+            self.transaction_id = transaction_id;
+        }
+    }
+
+    fn clear(&mut self, transaction_id: Option<TransactionId>) {
+        // The sequence is kept so that sequence ids continue to increase.
+        self.by_sequence_root = BTreeEntry {
+            dirty: true,
+            ..BTreeEntry::default()
+        };
+        self.by_id_root = BTreeEntry {
+            dirty: true,
+            ..BTreeEntry::default()
+        };
+        if let Some(transaction_id) = transaction_id {
+            self.transaction_id = transaction_id;
+        }
+    }
+
+    fn bulk_load(
+        &mut self,
+        entries: &mut dyn Iterator<Item = (ArcBytes<'static>, ArcBytes<'static>)>,
+        writer: &mut PagedWriter<'_>,
+        order: usize,
+    ) -> Result<(), Error> {
+        let mut by_id = SortedTreeBuilder::new(order, &self.reducer);
+        let mut by_sequence = SortedTreeBuilder::new(order, &BySequenceReducer);
+        let mut sequence = self.sequence;
+        for (key, value) in entries {
+            let position = writer.write_chunk(&value)?;
+            let value_length = saturating_value_length(&value);
+            let embedded = self.reducer.0.index(&key, Some(&value));
+            sequence = sequence
+                .next_sequence()
+                .expect("sequence rollover prevented");
+            let sequence_entry = KeyEntry {
+                key: ArcBytes::from(sequence.0.to_be_bytes()),
+                index: BySequenceIndex {
+                    key: key.clone(),
+                    last_sequence: None,
+                    value_length,
+                    position,
+                    embedded: Some(embedded.clone()),
+                },
+            };
+            by_id.push(
+                KeyEntry {
+                    key,
+                    index: VersionedByIdIndex {
+                        sequence_id: sequence,
+                        position,
+                        value_length,
+                        embedded,
+                    },
+                },
+                writer,
+            )?;
+            by_sequence.push(sequence_entry, writer)?;
+        }
+        self.by_sequence_root = by_sequence.finish(writer)?;
+        self.by_id_root = by_id.finish(writer)?;
+        self.sequence = sequence;
+        Ok(())
+    }
+
     fn modify(
         &mut self,
         modification: Modification<'_, ArcBytes<'static>, Self::Index>,
@@ -550,6 +644,23 @@ where
 
         Ok(())
     }
+
+    fn verify(&self, file: &mut dyn File, verifier: &mut ChunkVerifier<'_>) -> Result<(), Error> {
+        self.by_id_root.verify(file, verifier)?;
+        self.by_sequence_root.verify(file, verifier)
+    }
+
+    fn walk_nodes(
+        &self,
+        visitor: &mut dyn FnMut(NodeInfo<'_, Self::Index, Self::ReducedIndex>) -> ScanEvaluation,
+        file: &mut dyn File,
+        vault: Option<&dyn AnyVault>,
+        cache: Option<&ChunkCache>,
+    ) -> Result<(), Error> {
+        self.by_id_root
+            .walk(None, 0, visitor, file, vault, cache)
+            .map(|_| ())
+    }
 }
 
 pub struct EntryChanges<Embedded> {
@@ -0,0 +1,60 @@
+use super::{interior::Interior, key_entry::KeyEntry};
+use crate::ArcBytes;
+
+/// A node visited by [`TreeFile::walk_nodes()`](super::TreeFile::walk_nodes).
+#[derive(Debug)]
+pub struct NodeInfo<'a, Index, ReducedIndex> {
+    /// The depth of the node. The root node is depth 0.
+    pub depth: usize,
+    /// The location of the node on-disk. This is `None` for the root node,
+    /// which is stored within the tree's root, and for nodes that have been
+    /// modified but not yet written.
+    pub position: Option<u64>,
+    /// The entries stored in the node.
+    pub contents: NodeContents<'a, Index, ReducedIndex>,
+}
+
+impl<'a, Index, ReducedIndex> NodeInfo<'a, Index, ReducedIndex> {
+    /// Returns the number of entries stored in the node. For interior nodes,
+    /// this is the number of child nodes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match &self.contents {
+            NodeContents::Interior(children) => children.len(),
+            NodeContents::Leaf(entries) => entries.len(),
+        }
+    }
+
+    /// Returns true if the node contains no entries. Only the root node of an
+    /// empty tree contains no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if this is a leaf node.
+    #[must_use]
+    pub const fn is_leaf(&self) -> bool {
+        matches!(self.contents, NodeContents::Leaf(_))
+    }
+
+    /// Returns the largest key stored within the node and its children.
+    #[must_use]
+    pub fn max_key(&self) -> Option<&'a ArcBytes<'static>> {
+        match self.contents {
+            NodeContents::Interior(children) => children.last().map(|child| &child.key),
+            NodeContents::Leaf(entries) => entries.last().map(|entry| &entry.key),
+        }
+    }
+}
+
+/// The entries stored in a node visited by
+/// [`TreeFile::walk_nodes()`](super::TreeFile::walk_nodes).
+#[derive(Debug)]
+pub enum NodeContents<'a, Index, ReducedIndex> {
+    /// An interior node. Each entry contains the largest key stored within the
+    /// child, the child's location, and the child's reduced index.
+    Interior(&'a [Interior<Index, ReducedIndex>]),
+    /// A leaf node. Each entry contains a key and its index.
+    Leaf(&'a [KeyEntry<Index>]),
+}
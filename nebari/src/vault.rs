@@ -1,4 +1,17 @@
-use crate::{error::SendSyncError, ErrorKind};
+//! Vaults pre-process each chunk before it is written to disk.
+
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    error::SendSyncError,
+    io::{File, FileManager, FileOp, OperableFile},
+    transaction::{State, TransactionLog, TransactionManager},
+    Context, Error, ErrorKind,
+};
 
 /// A provider of encryption for blocks of data.
 pub trait Vault: std::fmt::Debug + Send + Sync + 'static {
@@ -7,6 +20,12 @@ pub trait Vault: std::fmt::Debug + Send + Sync + 'static {
 
     /// Encrypts `payload`, returning a new buffer that contains all information
     /// necessary to decrypt it in the future.
+    ///
+    /// Stored chunks are limited to 4 gigabytes (2^32 bytes) after being
+    /// encrypted. Any overhead added by the vault reduces the largest value
+    /// that can be stored by the same amount. Writing a value that exceeds
+    /// this limit only after being encrypted returns
+    /// [`ErrorKind::VaultOverheadTooLarge`].
     fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Self::Error>;
 
     /// Decrypts a previously encrypted `payload`, returning the decrypted
@@ -14,6 +33,8 @@ pub trait Vault: std::fmt::Debug + Send + Sync + 'static {
     fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Self::Error>;
 }
 
+/// A [`Vault`] whose error type has been erased. This trait is implemented for
+/// every [`Vault`].
 pub trait AnyVault: std::fmt::Debug + Send + Sync + 'static {
     /// Encrypts `payload`, returning a new buffer that contains all information
     /// necessary to decrypt it in the future.
@@ -39,3 +60,233 @@ where
             .map_err(|err| crate::Error::from(ErrorKind::Vault(Box::new(err))))
     }
 }
+
+/// A vault that applies multiple vaults in order. When encrypting, each vault
+/// processes the output of the vault before it. When decrypting, the vaults
+/// are applied in reverse order.
+///
+/// This allows vaults to be composed, such as compressing chunks before
+/// encrypting them. An error returned by any vault in the chain is returned
+/// without applying the remaining vaults.
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct ChainedVault {
+    vaults: Vec<Arc<dyn AnyVault>>,
+}
+
+impl ChainedVault {
+    /// Returns a vault that applies `vaults` in order.
+    pub fn new(vaults: Vec<Arc<dyn AnyVault>>) -> Self {
+        Self { vaults }
+    }
+
+    /// Appends `vault` to the chain, which will process the output of the
+    /// vaults already in the chain.
+    pub fn with<V: AnyVault>(self, vault: V) -> Self {
+        self.with_any_vault(Arc::new(vault))
+    }
+
+    /// Appends `vault` to the chain, which will process the output of the
+    /// vaults already in the chain.
+    pub fn with_any_vault(mut self, vault: Arc<dyn AnyVault>) -> Self {
+        self.vaults.push(vault);
+        self
+    }
+
+    /// Returns the vaults in the order they are applied when encrypting.
+    #[must_use]
+    pub fn vaults(&self) -> &[Arc<dyn AnyVault>] {
+        &self.vaults
+    }
+}
+
+impl AnyVault for ChainedVault {
+    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let mut vaults = self.vaults.iter();
+        let mut output = match vaults.next() {
+            Some(vault) => vault.encrypt(payload)?,
+            None => return Ok(payload.to_vec()),
+        };
+        for vault in vaults {
+            output = vault.encrypt(&output)?;
+        }
+        Ok(output)
+    }
+
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let mut vaults = self.vaults.iter().rev();
+        let mut output = match vaults.next() {
+            Some(vault) => vault.decrypt(payload)?,
+            None => return Ok(payload.to_vec()),
+        };
+        for vault in vaults {
+            output = vault.decrypt(&output)?;
+        }
+        Ok(output)
+    }
+}
+
+/// A [`Vault`] that compresses chunks using
+/// [Zstandard](https://facebook.github.io/zstd/). Any sequence of bytes,
+/// including an empty one, is restored exactly by `decrypt()`. To compress
+/// chunks before encrypting them, use a [`ChainedVault`].
+///
+/// This type requires the `zstd` feature.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ZstdVault {
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdVault {
+    /// Returns a vault that compresses chunks using compression `level`.
+    /// Higher levels produce smaller chunks but compress more slowly. Zstd
+    /// supports levels from 1 to 22, and 0 selects zstd's default level.
+    #[must_use]
+    pub const fn new(level: i32) -> Self {
+        Self { level }
+    }
+
+    /// Returns the compression level.
+    #[must_use]
+    pub const fn level(&self) -> i32 {
+        self.level
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdVault {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Vault for ZstdVault {
+    type Error = std::io::Error;
+
+    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        zstd::stream::encode_all(payload, self.level)
+    }
+
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        zstd::stream::decode_all(payload)
+    }
+}
+
+/// The contents stored in the vault check file, before being encrypted.
+const VAULT_CHECK: &[u8] = b"nebari vault check";
+
+/// Returns the path of the file used to verify that a database is opened
+/// with the same vault it was created with.
+pub(crate) fn vault_check_path(directory: &Path) -> PathBuf {
+    directory.join("_vault")
+}
+
+/// Verifies that the vault in `context` is the vault the database in
+/// `directory` was created with, returning [`ErrorKind::VaultMismatch`] if it
+/// isn't.
+///
+/// The first time a database is opened for writing, a check value encrypted
+/// by the vault is recorded. Databases created before the check value was
+/// introduced are verified by reading the newest entry of their transaction log
+/// with the vault before the check value is recorded. This detects vaults
+/// that fail to decrypt the log, but not vaults that decrypt it into
+/// different bytes without an error.
+pub(crate) fn verify_vault<Manager: FileManager>(
+    directory: &Path,
+    context: &Context<Manager>,
+    new_database: bool,
+    read_only: bool,
+) -> Result<(), Error> {
+    let path = vault_check_path(directory);
+    if context.file_manager.exists(&path)? {
+        let mut file = context.file_manager.read(&path)?;
+        return file.execute(VaultCheckVerifier {
+            vault: context.vault(),
+        });
+    }
+
+    if !new_database {
+        verify_log_is_readable(directory, context)?;
+    }
+    if read_only {
+        Ok(())
+    } else {
+        write_vault_check(&path, context)
+    }
+}
+
+/// Reads the newest entry of the transaction log in `directory` using the
+/// vault in `context`, returning [`ErrorKind::VaultMismatch`] if it can't be
+/// decrypted.
+fn verify_log_is_readable<Manager: FileManager>(
+    directory: &Path,
+    context: &Context<Manager>,
+) -> Result<(), Error> {
+    let state = State::from_path(TransactionManager::<Manager>::log_path(directory));
+    match TransactionLog::<Manager::File>::initialize_state_read_only(&state, context) {
+        Ok(()) => Ok(()),
+        Err(err) if matches!(err.kind, ErrorKind::Vault(_)) || err.kind.is_data_integrity() => {
+            Err(Error::from(ErrorKind::VaultMismatch))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes the vault check file to a temporary path, and moves it to `path`
+/// once it has been synchronized. An interrupted write can't leave an
+/// incomplete check file behind.
+fn write_vault_check<Manager: FileManager>(
+    path: &Path,
+    context: &Context<Manager>,
+) -> Result<(), Error> {
+    let temporary_path = path.with_extension("writing");
+    context.file_manager.delete(&temporary_path)?;
+    let mut file = context.file_manager.append(&temporary_path)?;
+    file.execute(VaultCheckWriter {
+        vault: context.vault(),
+    })?;
+    drop(file);
+    context.file_manager.rename(&temporary_path, path)
+}
+
+struct VaultCheckWriter<'a> {
+    vault: Option<&'a dyn AnyVault>,
+}
+
+impl FileOp<Result<(), Error>> for VaultCheckWriter<'_> {
+    fn execute(self, file: &mut dyn File) -> Result<(), Error> {
+        let contents = match self.vault {
+            Some(vault) => vault.encrypt(VAULT_CHECK)?,
+            None => VAULT_CHECK.to_vec(),
+        };
+        file.write_all(&contents)?;
+        file.synchronize()
+    }
+}
+
+struct VaultCheckVerifier<'a> {
+    vault: Option<&'a dyn AnyVault>,
+}
+
+impl FileOp<Result<(), Error>> for VaultCheckVerifier<'_> {
+    fn execute(self, file: &mut dyn File) -> Result<(), Error> {
+        let length = usize::try_from(file.length()?).map_err(|_| ErrorKind::VaultMismatch)?;
+        let mut contents = vec![0; length];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut contents)?;
+        let matches = match self.vault {
+            Some(vault) => vault
+                .decrypt(&contents)
+                .map_or(false, |decrypted| decrypted == VAULT_CHECK),
+            None => contents == VAULT_CHECK,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::VaultMismatch))
+        }
+    }
+}